@@ -0,0 +1,246 @@
+use std::cmp;
+
+use screeps::constants::Part;
+
+use crate::world::part_cost;
+
+/// A creep can't have more parts than this.
+pub const MAX_PARTS: u32 = 50;
+
+/// How parts are arranged in the final body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyOrder {
+    /// All parts of a kind next to each other: tough first, heal last.
+    /// Damage hits the front of the body first, so this keeps the useful parts alive the longest.
+    Grouped,
+    /// The pattern repeated as-is, so the creep loses a bit of everything when damaged.
+    Interleaved
+}
+
+/// A repeating pattern of parts, scaled to the energy available.
+/// 
+/// ```ignore
+/// // 1 CARRY, then as many WORK+MOVE pairs as possible, between 1 and 5
+/// BodyTemplate::new(&[(Part::Work, 1), (Part::Move, 1)])
+///     .fixed(&[(Part::Carry, 1)])
+///     .size(1, 5)
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyTemplate {
+    /// Parts added once per repetition, with their counts.
+    pattern: Vec<(Part, u32)>,
+    /// Parts added once, regardless of size.
+    fixed: Vec<(Part, u32)>,
+    min_repeats: u32,
+    max_repeats: u32,
+    order: BodyOrder
+}
+
+/// Total spawn cost of a body.
+pub fn body_cost(body: &[Part]) -> u32 {
+    body.iter().map(|part| part_cost(*part)).sum()
+}
+
+fn group_rank(part: Part) -> u32 {
+    match part {
+        Part::Tough => 0,
+        Part::Work => 1,
+        Part::Carry => 2,
+        Part::Attack => 3,
+        Part::RangedAttack => 4,
+        Part::Claim => 5,
+        Part::Move => 6,
+        Part::Heal => 7
+    }
+}
+
+impl BodyTemplate {
+    pub fn new(pattern: &[(Part, u32)]) -> BodyTemplate {
+        BodyTemplate{
+            pattern: pattern.to_vec(),
+            fixed: Vec::new(),
+            min_repeats: 1,
+            max_repeats: MAX_PARTS,
+            order: BodyOrder::Grouped
+        }
+    }
+
+    pub fn fixed(mut self, fixed: &[(Part, u32)]) -> BodyTemplate {
+        self.fixed = fixed.to_vec();
+        self
+    }
+
+    /// The minimum and maximum number of times the pattern is repeated.
+    pub fn size(mut self, min_repeats: u32, max_repeats: u32) -> BodyTemplate {
+        self.min_repeats = min_repeats;
+        self.max_repeats = cmp::max(min_repeats, max_repeats);
+        self
+    }
+
+    pub fn order(mut self, order: BodyOrder) -> BodyTemplate {
+        self.order = order;
+        self
+    }
+
+    fn count(parts: &[(Part, u32)]) -> u32 {
+        parts.iter().map(|(_, count)| count).sum()
+    }
+
+    fn cost(parts: &[(Part, u32)]) -> u32 {
+        parts.iter().map(|(part, count)| part_cost(*part) * count).sum()
+    }
+
+    pub fn pattern_cost(&self) -> u32 {
+        BodyTemplate::cost(&self.pattern)
+    }
+
+    pub fn fixed_cost(&self) -> u32 {
+        BodyTemplate::cost(&self.fixed)
+    }
+
+    /// Cost of the smallest body this template makes.
+    pub fn min_cost(&self) -> u32 {
+        self.fixed_cost() + self.min_repeats * self.pattern_cost()
+    }
+
+    /// The number of repetitions that fit into `energy` and the part cap, or None if not even the minimum fits.
+    pub fn repeats_for(&self, energy: u32) -> Option<u32> {
+        let fixed_count = BodyTemplate::count(&self.fixed);
+        let pattern_count = BodyTemplate::count(&self.pattern);
+        let fixed_cost = self.fixed_cost();
+        let pattern_cost = self.pattern_cost();
+
+        if fixed_count > MAX_PARTS || fixed_cost > energy {
+            return None;
+        }
+
+        let by_parts = match pattern_count {
+            0 => self.max_repeats,
+            count => (MAX_PARTS - fixed_count) / count
+        };
+        let by_energy = match pattern_cost {
+            0 => self.max_repeats,
+            cost => (energy - fixed_cost) / cost
+        };
+        let repeats = cmp::min(self.max_repeats, cmp::min(by_parts, by_energy));
+
+        if repeats < self.min_repeats {
+            None
+        } else {
+            Some(repeats)
+        }
+    }
+
+    /// The biggest body that fits into `energy`.
+    pub fn generate(&self, energy: u32) -> Option<Vec<Part>> {
+        self.repeats_for(energy).map(|repeats| self.build(repeats))
+    }
+
+    /// The smallest body this template makes.
+    pub fn minimal(&self) -> Vec<Part> {
+        self.build(self.min_repeats)
+    }
+
+    fn build(&self, repeats: u32) -> Vec<Part> {
+        let mut body = Vec::new();
+
+        match self.order {
+            BodyOrder::Grouped => {
+                for (part, count) in self.fixed.iter() {
+                    body.extend((0..*count).map(|_| *part));
+                }
+                for (part, count) in self.pattern.iter() {
+                    body.extend((0..count * repeats).map(|_| *part));
+                }
+                body.sort_by_key(|part| group_rank(*part));
+            },
+            BodyOrder::Interleaved => {
+                for (part, count) in self.fixed.iter() {
+                    body.extend((0..*count).map(|_| *part));
+                }
+                for _ in 0..repeats {
+                    for (part, count) in self.pattern.iter() {
+                        body.extend((0..*count).map(|_| *part));
+                    }
+                }
+            }
+        }
+
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_cost_sums_parts() {
+        assert_eq!(body_cost(&[]), 0);
+        assert_eq!(body_cost(&[Part::Work, Part::Carry, Part::Move]), 200);
+        assert_eq!(body_cost(&[Part::Tough, Part::Heal, Part::Claim]), 860);
+    }
+
+    #[test]
+    fn repeats_between_min_and_max() {
+        let template = BodyTemplate::new(&[(Part::Work, 1), (Part::Move, 1)]).size(2, 5);
+
+        assert_eq!(template.repeats_for(299), None);
+        assert_eq!(template.repeats_for(300), Some(2));
+        assert_eq!(template.repeats_for(10000), Some(5));
+    }
+
+    #[test]
+    fn repeats_at_exact_energy() {
+        let template = BodyTemplate::new(&[(Part::Work, 1), (Part::Move, 1)])
+            .fixed(&[(Part::Carry, 1)])
+            .size(1, 5);
+
+        assert_eq!(template.min_cost(), 200);
+        assert_eq!(template.repeats_for(49), None);
+        assert_eq!(template.repeats_for(199), None);
+        assert_eq!(template.repeats_for(200), Some(1));
+        assert_eq!(template.repeats_for(349), Some(1));
+        assert_eq!(template.repeats_for(350), Some(2));
+    }
+
+    #[test]
+    fn repeats_capped_at_max_parts() {
+        let template = BodyTemplate::new(&[(Part::Work, 1), (Part::Move, 1)]);
+        assert_eq!(template.repeats_for(100000), Some(25));
+        assert_eq!(template.generate(100000).map(|body| body.len()), Some(MAX_PARTS as usize));
+
+        let template = template.fixed(&[(Part::Carry, 1)]);
+        assert_eq!(template.repeats_for(100000), Some(24));
+        assert_eq!(template.generate(100000).map(|body| body.len()), Some(49));
+    }
+
+    #[test]
+    fn generate_below_minimum() {
+        let template = BodyTemplate::new(&[(Part::Carry, 2), (Part::Move, 1)]).size(2, 10);
+
+        assert_eq!(template.generate(299), None);
+        assert_eq!(template.minimal().len(), 6);
+        assert_eq!(template.generate(300).map(|body| body_cost(&body)), Some(300));
+    }
+
+    #[test]
+    fn grouped_puts_tough_first_and_heal_last() {
+        let body = BodyTemplate::new(&[(Part::Heal, 1), (Part::Move, 1), (Part::Tough, 1)])
+            .size(2, 2)
+            .generate(10000);
+
+        assert_eq!(body, Some(vec![Part::Tough, Part::Tough, Part::Move, Part::Move, Part::Heal, Part::Heal]));
+    }
+
+    #[test]
+    fn interleaved_repeats_the_pattern() {
+        let body = BodyTemplate::new(&[(Part::Heal, 1), (Part::Move, 1), (Part::Tough, 1)])
+            .fixed(&[(Part::Carry, 1)])
+            .size(2, 2)
+            .order(BodyOrder::Interleaved)
+            .generate(10000);
+
+        assert_eq!(body, Some(vec![Part::Carry, Part::Heal, Part::Move, Part::Tough, Part::Heal, Part::Move, Part::Tough]));
+    }
+}
@@ -0,0 +1,242 @@
+use std::rc::Rc;
+
+use screeps::constants::Color;
+
+use crate::{
+    links::LinkManager,
+    logistics::Logistics,
+    military::{
+        defense::Defense,
+        invaders::Invaders,
+        squad::Squads,
+        threat::ThreatAssessment,
+        tower::Tower
+    },
+    planner::RoomPlanner,
+    reservations::Reservations,
+    spawning::SpawnQueue,
+    traits::{
+        Role,
+        FlagProcessor
+    },
+    roles::{
+        defender::{
+            DefenderKind,
+            RoleDefender
+        },
+        hauler::RoleHauler,
+        looter::RoleLooter,
+        miner::RoleMiner,
+        registry::RoleRegistry,
+        squad::RoleSquad
+    },
+    tasks::{
+        build::TaskBuild,
+        collect::TaskCollect,
+        haul::TaskHaul,
+        harvest::TaskHarvest,
+        mine::TaskMine,
+        refill::TaskRefill,
+        registry::TaskRegistry,
+        upgrade::TaskUpgrade
+    },
+    world::World
+};
+
+/// Everything the bot keeps between ticks.
+/// 
+/// Lives on the heap for as long as the VM does, so tasks and roles are only built on a cold start.  
+/// Anything that has to survive a VM reset is kept in `Memory` instead.
+pub struct Bot {
+    reservations: Rc<Reservations>,
+    threats: Rc<ThreatAssessment>,
+    tower_handler: Tower,
+    defense: Defense,
+    invaders: Invaders,
+    squads: Squads,
+    link_manager: LinkManager,
+    room_planner: RoomPlanner,
+    spawn_queue: SpawnQueue,
+    tasks: Rc<TaskRegistry>,
+    roles: RoleRegistry
+}
+
+impl Bot {
+    /// Builds everything from scratch, should only happen when the VM is reset.
+    pub fn new() -> Bot {
+        let reservations = Rc::new(Reservations::new());
+        let threats = Rc::new(ThreatAssessment::new());
+        let logistics = Rc::new(Logistics::new(reservations.clone()));
+        let tasks = {
+            let mut tasks = TaskRegistry::new();
+            tasks.register(Box::new(TaskBuild::new(reservations.clone())));
+            tasks.register(Box::new(TaskCollect::pickup(logistics.clone(), reservations.clone())));
+            tasks.register(Box::new(TaskCollect::withdraw(logistics.clone(), reservations.clone())));
+            tasks.register(Box::new(TaskCollect::supply(logistics.clone(), reservations.clone())));
+            tasks.register(Box::new(TaskHarvest::new()));
+            tasks.register(Box::new(TaskHaul::new(logistics.clone(), reservations.clone())));
+            tasks.register(Box::new(TaskMine::new()));
+            tasks.register(Box::new(TaskRefill::new(logistics.clone(), reservations.clone())));
+            tasks.register(Box::new(TaskUpgrade::new(reservations.clone())));
+
+            Rc::new(tasks)
+        };
+        let mut roles = RoleRegistry::from_definitions(&tasks);
+        roles.register(Box::new(RoleMiner::new(tasks.clone())));
+        roles.register(Box::new(RoleHauler::new(tasks.clone())));
+        roles.register(Box::new(RoleDefender::new(DefenderKind::Melee, threats.clone())));
+        roles.register(Box::new(RoleDefender::new(DefenderKind::Ranged, threats.clone())));
+        roles.register(Box::new(RoleDefender::new(DefenderKind::Healer, threats.clone())));
+        roles.register(Box::new(RoleSquad::new()));
+        roles.register(Box::new(RoleLooter::new()));
+
+        Bot{
+            tower_handler: Tower::new(reservations.clone(), threats.clone()),
+            defense: Defense::new(threats.clone()),
+            invaders: Invaders::new(),
+            squads: Squads::new(),
+            link_manager: LinkManager::new(),
+            room_planner: RoomPlanner::new(),
+            reservations: reservations,
+            threats: threats,
+            spawn_queue: SpawnQueue::new(),
+            tasks: tasks,
+            roles: roles
+        }
+    }
+
+    /// Runs a single tick of the bot.
+    pub fn run(&self, world: &dyn World) {
+        let mut err_counter = 0;
+        let roles = &self.roles;
+
+        self.reservations.clear();
+        self.threats.update(world);
+        self.tasks.begin_tick(world);
+        for role in roles.values() {
+            role.begin_tick(world);
+        }
+
+        // Military tasks
+        self.tower_handler.run(world).unwrap_or_else(|err| {
+                warn!("failed to execute tower handler: {}", err.to_string());
+                err_counter += 1;
+            });
+        self.defense.run(world).unwrap_or_else(|err| {
+                warn!("failed to execute defense: {}", err.to_string());
+                err_counter += 1;
+            });
+        self.invaders.run(world).unwrap_or_else(|err| {
+                warn!("failed to execute invaders: {}", err.to_string());
+                err_counter += 1;
+            });
+        self.squads.run(world).unwrap_or_else(|err| {
+                warn!("failed to execute squads: {}", err.to_string());
+                err_counter += 1;
+            });
+        self.link_manager.run(world).unwrap_or_else(|err| {
+                warn!("failed to execute link manager: {}", err.to_string());
+                err_counter += 1;
+            });
+        self.room_planner.run(world).unwrap_or_else(|err| {
+                warn!("failed to execute room planner: {}", err.to_string());
+                err_counter += 1;
+            });
+
+
+        for creep in world.my_creeps() {
+            let role_str = match world.memory().creeps.get(&creep.name) {
+                Some(creep_memory) => creep_memory.role.clone(),
+                None => "missing".to_string()
+            };
+            if let Err(err) = roles.get(role_str.as_str()).ok_or(Box::from(format!("unknown role {}", role_str)))
+                                .and_then(|role| role.run(world, &creep)) {
+                warn!("failed to execute task for creep {}: {}", creep.name, err.to_string());
+                err_counter += 1;
+            }
+        }
+
+        // New creep creation
+        self.spawn_queue.run(world, roles);
+
+        // Process commands given via flags
+        if let Some(done_flag) = world.flags().into_iter().find(|flag| flag.name == "done") {
+            let flag_proc_result: Result<(), String> = {
+                let mut errs = Vec::new();
+            
+                for flag in world.flags() {
+                    let name = flag.name.clone();
+                    let mut cmd = name.split_whitespace();
+
+                    let result = {
+                        let module = if let Some(x) = cmd.next() { x } else { continue };
+
+                        if let Some(role) = roles.get(module) {
+                            role.flag(cmd, flag.pos.clone())
+                        } else {
+                            match module {
+                                "ok" => Ok(true),
+                                "err" => Ok(true),
+                                "error" => Err(Box::from( format!("'{}' - intentional error", flag.name) )),
+                                _ => Ok(false)
+                            }
+                        }
+                    };
+
+                    match result {
+                        Ok(true) => world.remove_flag(&flag.name),
+                        Ok(false) => (),
+                        Err(err) => errs.push(err.to_string())
+                    };
+                }
+            
+                match errs.len() {
+                    0 => Ok(()),
+                    _ => Err( format!("<ul>{}</ul>", errs.iter().fold( String::from("<ul>"), |acc, next|
+                            format!("{acc}<li>{next}</li>", acc=acc, next=next) 
+                        )) )
+                }
+            };
+
+            let flag_pos = done_flag.pos;
+            world.remove_flag(&done_flag.name);
+
+            match flag_proc_result {
+                Ok(()) => {
+                    world.create_flag(&flag_pos, "ok", Color::Green, Color::Cyan);
+                    info!("processed all flags successfully");
+                },
+                Err(err) => {
+                    world.create_flag(&flag_pos, "err", Color::Red, Color::Orange);
+                    error!("there were errors with flags: {}", err);
+                },
+            }
+        }
+
+        if world.time() % 50 == 3 {
+            clear_deceased(world);
+        
+            info!("creep counts: <ul>{}</ul>", roles.iter().fold(String::new(), |acc, (role_str, role)| {
+                    format!("{acc}<li>{name}: {run}/{limit}</li>", acc=acc, name=role_str, run=role.run_count(world), limit=role.limit())
+                }
+            ));
+            info!("spawn queue: <ul>{}</ul>", self.spawn_queue.describe(world));
+        }
+
+        if err_counter > 10 {
+            error!("too many errors, sending notification");
+            world.notify("Encountered too many errors!");
+        }
+    }
+}
+
+fn clear_deceased(world: &dyn World) {
+    let mut memory = world.memory();
+    let deceased: Vec<String> = memory.creeps.keys()
+                                      .filter(|name| world.creep(name).is_none())
+                                      .cloned()
+                                      .collect();
+    for name in deceased {
+        memory.creeps.remove(&name);
+    }
+}
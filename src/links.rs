@@ -0,0 +1,302 @@
+use std::{
+    cmp,
+    error::Error
+};
+
+use hashbrown::HashMap;
+use screeps::constants::ReturnCode;
+
+use crate::{
+    memory::{
+        LinkKind,
+        LinkMemory
+    },
+    world::{
+        Position,
+        StructureIntent,
+        StructureKind,
+        StructureState,
+        World
+    }
+};
+
+/// A link this close to a source is filled by its miner.
+const SOURCE_RANGE: u32 = 2;
+/// A link this close to the controller feeds upgraders.
+const CONTROLLER_RANGE: u32 = 3;
+/// A link this close to the storage is filled from it.
+const STORAGE_RANGE: u32 = 2;
+/// Sending less than this isn't worth the cooldown.
+const MIN_TRANSFER: u32 = 100;
+/// How often known links are classified again.
+const RECLASSIFY_INTERVAL: u32 = 100;
+
+/// Classifies links, and sends energy between them.
+/// 
+/// Links are classified by what they're close to, see `memory::LinkKind`, and kept in `Memory.links`.  
+/// New links are classified right away, and all of them again every `RECLASSIFY_INTERVAL` ticks.  
+/// Every tick, each link that's off cooldown sends to the first receiver with room:
+///   * source links send to controller links, then hub links, then storage links
+///   * storage links top up controller links that are below half
+pub struct LinkManager;
+
+impl LinkManager {
+    pub fn new() -> LinkManager {
+        LinkManager{}
+    }
+
+    fn classify(&self, world: &dyn World, link: &StructureState, sources: &[Position]) -> LinkKind {
+        let room = &link.pos.room;
+
+        if sources.iter().any(|source| link.pos.in_range_to(source, SOURCE_RANGE)) {
+            return LinkKind::Source;
+        }
+
+        let controller = world.room(room).and_then(|room| room.controller);
+        if controller.map_or(false, |controller| link.pos.in_range_to(&controller.pos, CONTROLLER_RANGE)) {
+            return LinkKind::Controller;
+        }
+
+        let near_storage = world.structures(room).iter().any(|structure| {
+            structure.kind == StructureKind::Storage && link.pos.in_range_to(&structure.pos, STORAGE_RANGE)
+        });
+        if near_storage {
+            LinkKind::Storage
+        } else {
+            LinkKind::Hub
+        }
+    }
+
+    /// Finds our links in every room, classifies the new ones and forgets the missing ones.  
+    /// Every `RECLASSIFY_INTERVAL` ticks all of them are classified again, to follow storage and sources being built or lost.
+    fn update_links(&self, world: &dyn World) -> Vec<(StructureState, LinkKind)> {
+        let known = world.memory().links.clone();
+        let reclassify = world.time() % RECLASSIFY_INTERVAL == 0;
+
+        let mut links = Vec::new();
+        for room in world.rooms() {
+            let sources: Vec<Position> = world.sources(&room.name).into_iter().map(|source| source.pos).collect();
+
+            for structure in world.structures(&room.name) {
+                if structure.kind != StructureKind::Link || !structure.my {
+                    continue;
+                }
+
+                let kind = match known.get(&structure.id) {
+                    Some(link) if !reclassify => link.kind,
+                    Some(link) => {
+                        let kind = self.classify(world, &structure, &sources);
+                        if kind != link.kind {
+                            info!("{:?} link {} in {} is now a {:?} link", link.kind, structure.id, room.name, kind);
+                        }
+                        kind
+                    },
+                    None => {
+                        let kind = self.classify(world, &structure, &sources);
+                        info!("new {:?} link {} in {}", kind, structure.id, room.name);
+                        kind
+                    }
+                };
+                links.push((structure, kind));
+            }
+        }
+
+        world.memory().links = links.iter()
+            .map(|(link, kind)| (link.id.clone(), LinkMemory{ kind: *kind }))
+            .collect();
+        links
+    }
+
+    pub fn run(&self, world: &dyn World) -> Result<(), Box<dyn Error>> {
+        let links = self.update_links(world);
+
+        // energy each link receives this tick
+        let mut incoming: HashMap<String, u32> = HashMap::new();
+
+        for (sender, sender_kind) in links.iter() {
+            if sender.cooldown > 0 || sender.energy < MIN_TRANSFER {
+                continue;
+            }
+
+            let receivers: &[LinkKind] = match *sender_kind {
+                LinkKind::Source => &[LinkKind::Controller, LinkKind::Hub, LinkKind::Storage],
+                LinkKind::Storage => &[LinkKind::Controller],
+                _ => &[]
+            };
+
+            let target = receivers.iter().filter_map(|receiver_kind| {
+                links.iter()
+                    .filter(|(link, kind)| kind == receiver_kind && link.pos.room == sender.pos.room && link.id != sender.id)
+                    .filter(|(link, _)| {
+                        let energy = link.energy + incoming.get(&link.id).cloned().unwrap_or(0);
+                        if *sender_kind == LinkKind::Storage {
+                            // only top up, so storage energy isn't sent back and forth
+                            energy * 2 < link.energy_capacity
+                        } else {
+                            link.energy_capacity.saturating_sub(energy) >= MIN_TRANSFER
+                        }
+                    })
+                    .min_by_key(|(link, _)| sender.pos.range_to(&link.pos))
+            }).next();
+
+            if let Some((target, _)) = target {
+                let code = world.structure_intent(&sender.id, StructureIntent::LinkTransfer(target.id.clone()));
+                if code == ReturnCode::Ok {
+                    let incoming = incoming.entry(target.id.clone()).or_insert(0);
+                    let free = target.energy_capacity.saturating_sub(target.energy + *incoming);
+                    *incoming += cmp::min(sender.energy, free);
+                } else {
+                    warn!("link {} failed to send to {}: {:?}", sender.id, target.id, code);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        ControllerState,
+        RoomState,
+        SourceState
+    };
+
+    fn link(world: &MockWorld, id: &str, x: u32, y: u32, energy: u32) {
+        let mut link = mock::structure(id, StructureKind::Link, Position::new(x, y, "W1N1"));
+        link.energy = energy;
+        link.energy_capacity = 800;
+        world.add_structure(link);
+    }
+
+    /// A source at (10, 10), the controller at (40, 40), and the storage at (25, 25).
+    fn world_with_room() -> MockWorld {
+        let world = MockWorld::new();
+        world.add_room(RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: Some(ControllerState{
+                id: "controller".to_string(),
+                pos: Position::new(40, 40, "W1N1"),
+                my: true,
+                level: 6,
+                progress: 0,
+                progress_total: 1215000,
+                safe_mode: 0,
+                safe_mode_available: 0,
+                safe_mode_cooldown: 0
+            })
+        });
+        world.add_source(SourceState{
+            id: "source".to_string(),
+            pos: Position::new(10, 10, "W1N1"),
+            energy: 3000,
+            energy_capacity: 3000,
+            ticks_to_regeneration: 300
+        });
+        world.add_structure(mock::structure("storage", StructureKind::Storage, Position::new(25, 25, "W1N1")));
+        world
+    }
+
+    fn kind(world: &MockWorld, id: &str) -> LinkKind {
+        world.memory().links[id].kind
+    }
+
+    fn transfers(world: &MockWorld) -> Vec<(String, String)> {
+        world.structure_intents().into_iter().filter_map(|(id, intent)| match intent {
+            StructureIntent::LinkTransfer(target) => Some((id, target)),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn classifies_links() {
+        let world = world_with_room();
+        link(&world, "source", 11, 11, 0);
+        link(&world, "controller", 39, 39, 0);
+        link(&world, "storage", 26, 25, 0);
+        link(&world, "hub", 25, 40, 0);
+        LinkManager::new().run(&world).unwrap();
+
+        assert_eq!(kind(&world, "source"), LinkKind::Source);
+        assert_eq!(kind(&world, "controller"), LinkKind::Controller);
+        assert_eq!(kind(&world, "storage"), LinkKind::Storage);
+        assert_eq!(kind(&world, "hub"), LinkKind::Hub);
+    }
+
+    #[test]
+    fn reclassifies_links() {
+        let world = MockWorld::new();
+        world.add_room(RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: None
+        });
+        link(&world, "link", 26, 25, 0);
+        let links = LinkManager::new();
+        links.run(&world).unwrap();
+        assert_eq!(kind(&world, "link"), LinkKind::Hub);
+
+        world.add_structure(mock::structure("storage", StructureKind::Storage, Position::new(25, 25, "W1N1")));
+        world.set_time(RECLASSIFY_INTERVAL - 1);
+        links.run(&world).unwrap();
+        assert_eq!(kind(&world, "link"), LinkKind::Hub);
+
+        world.set_time(RECLASSIFY_INTERVAL);
+        links.run(&world).unwrap();
+        assert_eq!(kind(&world, "link"), LinkKind::Storage);
+    }
+
+    #[test]
+    fn fills_controller_link_before_hub() {
+        let world = world_with_room();
+        link(&world, "controller", 39, 39, 300);
+        link(&world, "hub", 25, 40, 0);
+        link(&world, "source1", 11, 11, 400);
+        link(&world, "source2", 9, 9, 400);
+        link(&world, "source3", 11, 9, 400);
+        LinkManager::new().run(&world).unwrap();
+
+        // the first two fill the controller link, counting what's already on its way
+        assert_eq!(transfers(&world), vec![
+            ("source1".to_string(), "controller".to_string()),
+            ("source2".to_string(), "controller".to_string()),
+            ("source3".to_string(), "hub".to_string())
+        ]);
+    }
+
+    #[test]
+    fn skips_small_transfers() {
+        let world = world_with_room();
+        link(&world, "controller", 39, 39, 750);
+        link(&world, "source", 11, 11, 50);
+        link(&world, "hub", 25, 40, 0);
+        LinkManager::new().run(&world).unwrap();
+
+        assert!(transfers(&world).is_empty());
+    }
+
+    #[test]
+    fn storage_link_tops_up_controller_link() {
+        let world = world_with_room();
+        link(&world, "controller", 39, 39, 300);
+        link(&world, "storage", 26, 25, 800);
+        LinkManager::new().run(&world).unwrap();
+        assert_eq!(transfers(&world), vec![("storage".to_string(), "controller".to_string())]);
+
+        world.clear_intents();
+        let mut controller = world.structure("controller").unwrap();
+        controller.energy = 400;
+        world.update_structure(controller);
+        LinkManager::new().run(&world).unwrap();
+        assert!(transfers(&world).is_empty());
+    }
+}
@@ -4,9 +4,12 @@ use screeps;
 
 pub use log::LevelFilter::*;
 
+#[cfg(target_arch = "wasm32")]
 struct JsLog;
+#[cfg(target_arch = "wasm32")]
 struct JsNotify;
 
+#[cfg(target_arch = "wasm32")]
 impl log::Log for JsLog {
     fn enabled(&self, _: &log::Metadata) -> bool {
         true
@@ -19,6 +22,7 @@ impl log::Log for JsLog {
     }
     fn flush(&self) {}
 }
+#[cfg(target_arch = "wasm32")]
 impl log::Log for JsNotify {
     fn enabled(&self, _: &log::Metadata) -> bool {
         true
@@ -32,6 +36,7 @@ impl log::Log for JsNotify {
     fn flush(&self) {}
 }
 
+#[cfg(target_arch = "wasm32")]
 pub fn setup_logging(verbosity: log::LevelFilter) {
     fern::Dispatch::new()
         .level(verbosity)
@@ -53,3 +58,20 @@ pub fn setup_logging(verbosity: log::LevelFilter) {
         ).apply()
         .expect("expected setup_logging to only ever be called once per instance");
 }
+
+/// Logs to stdout when running natively, such as in the simulator.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn setup_logging(verbosity: log::LevelFilter) {
+    fern::Dispatch::new()
+        .level(verbosity)
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "({}) {}: {}",
+                record.level(),
+                record.target(),
+                message
+            ))
+        }).chain(::std::io::stdout())
+        .apply()
+        .expect("expected setup_logging to only ever be called once per instance");
+}
@@ -0,0 +1,271 @@
+use std::{
+    cell::RefCell,
+    cmp,
+    rc::Rc
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    memory::{
+        LinkKind,
+        RouteStop,
+        StopAction
+    },
+    reservations::{
+        ReservationKind,
+        Reservations
+    },
+    world::{
+        ControllerState,
+        ObjectId,
+        Position,
+        StructureKind,
+        StructureState,
+        World
+    }
+};
+
+/// A container this close to the controller feeds upgraders, instead of being emptied.
+const UPGRADE_CONTAINER_RANGE: u32 = 3;
+
+/// Energy a provider has to give, or a requester wants.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offer {
+    pub id: ObjectId,
+    pub pos: Position,
+    /// None for dropped energy, tombstones and ruins.
+    pub kind: Option<StructureKind>,
+    /// What a creep does to take or give the energy.
+    pub action: StopAction,
+    pub amount: u32,
+    /// Lower is served first.
+    pub priority: u32,
+    /// Ticks until the energy is gone, if it decays.
+    pub expires_in: Option<u32>
+}
+
+impl Offer {
+    /// What's left after `ticks`, for a creep that's that far away.
+    pub fn amount_after(&self, ticks: u32) -> u32 {
+        match self.expires_in {
+            Some(expires_in) if expires_in <= ticks => 0,
+            _ if self.action == StopAction::Pickup => {
+                // dropped energy loses a thousandth per tick, rounded up
+                self.amount.saturating_sub(ticks.saturating_mul(dropped_decay(self.amount)))
+            },
+            _ => self.amount
+        }
+    }
+
+    /// A route stop for `amount` of this offer.
+    pub fn stop(&self, amount: u32) -> RouteStop {
+        RouteStop{
+            id: self.id.clone(),
+            pos: self.pos.clone(),
+            action: self.action,
+            amount: amount
+        }
+    }
+}
+
+/// Which reservation taking or giving energy holds.
+pub fn reservation_kind(action: StopAction) -> ReservationKind {
+    match action {
+        StopAction::Transfer => ReservationKind::EnergyIn,
+        _ => ReservationKind::EnergyOut
+    }
+}
+
+/// Whether a container is next to the controller, where it feeds upgraders.
+pub fn is_upgrade_container(structure: &StructureState, controller: Option<&ControllerState>) -> bool {
+    structure.kind == StructureKind::Container &&
+        controller.map_or(false, |controller| structure.pos.in_range_to(&controller.pos, UPGRADE_CONTAINER_RANGE))
+}
+
+fn dropped_decay(amount: u32) -> u32 {
+    cmp::max(1, (amount + 999) / 1000)
+}
+
+/// Picks the provider whose energy will be gone the soonest, then the closest one.  
+/// Skips the ones that would be gone before the creep got there.
+pub fn best_provider<'a, I: IntoIterator<Item = &'a Offer>>(offers: I, from: &Position) -> Option<&'a Offer> {
+    offers.into_iter()
+        .filter(|offer| offer.pos.room == from.room && offer.amount_after(from.range_to(&offer.pos)) > 0)
+        .min_by_key(|offer| (offer.priority, offer.expires_in.unwrap_or(u32::max_value()), from.range_to(&offer.pos)))
+}
+
+/// Picks the most urgent offer, then the closest one.
+pub fn best_offer<'a, I: IntoIterator<Item = &'a Offer>>(offers: I, from: &Position) -> Option<&'a Offer> {
+    offers.into_iter()
+        .filter(|offer| offer.pos.room == from.room)
+        .min_by_key(|offer| (offer.priority, from.range_to(&offer.pos)))
+}
+
+fn requester_priority(kind: StructureKind) -> Option<u32> {
+    match kind {
+        StructureKind::Spawn | StructureKind::Extension => Some(0),
+        StructureKind::Tower => Some(1),
+        StructureKind::Container => Some(2),
+        StructureKind::Lab | StructureKind::PowerSpawn | StructureKind::Nuker => Some(3),
+        StructureKind::Storage => Some(4),
+        _ => None
+    }
+}
+
+/// Decaying energy comes first, so none of it is lost.
+const DECAYING_PRIORITY: u32 = 0;
+
+fn provider_priority(kind: StructureKind) -> Option<u32> {
+    match kind {
+        StructureKind::Container => Some(1),
+        StructureKind::Storage => Some(2),
+        _ => None
+    }
+}
+
+struct RoomOffers {
+    time: u32,
+    providers: Vec<Offer>,
+    requesters: Vec<Offer>,
+    upgrade: Vec<Offer>
+}
+
+/// Where energy is in each room, and where it's needed.
+/// 
+/// Structures, dropped energy, tombstones and ruins register as providers, structures that need energy as requesters,
+/// each with an amount and a priority:
+///   * providers: decaying energy (dropped, tombstones and ruins) the soonest to be gone first, then containers, then storage
+///   * requesters: spawns and extensions, then towers, then containers next to the controller,
+///     then labs, power spawns and nukers, then storage
+/// 
+/// Hub and storage links are providers, see `links::LinkManager`. Containers and links next to the controller
+/// are kept for upgraders instead, see `upgrade_supply`.
+/// 
+/// Offers are collected once per tick and room. Amounts returned are net of what's reserved.
+pub struct Logistics {
+    reservations: Rc<Reservations>,
+    rooms: RefCell<HashMap<String, RoomOffers>>
+}
+
+impl Logistics {
+    pub fn new(reservations: Rc<Reservations>) -> Logistics {
+        Logistics{
+            reservations: reservations,
+            rooms: RefCell::new(HashMap::new())
+        }
+    }
+
+    fn collect(&self, world: &dyn World, room: &str) -> RoomOffers {
+        let controller = world.room(room).and_then(|room| room.controller);
+        let links = world.memory().links.clone();
+        let mut providers = Vec::new();
+        let mut requesters = Vec::new();
+        let mut upgrade = Vec::new();
+
+        for structure in world.structures(room) {
+            if !structure.my && structure.kind != StructureKind::Container {
+                continue;
+            }
+
+            let upgrade_container = is_upgrade_container(&structure, controller.as_ref());
+            let offer = |action, amount, priority| Offer{
+                id: structure.id.clone(),
+                pos: structure.pos.clone(),
+                kind: Some(structure.kind),
+                action: action,
+                amount: amount,
+                priority: priority,
+                expires_in: None
+            };
+
+            if structure.kind != StructureKind::Container || upgrade_container {
+                if let Some(priority) = requester_priority(structure.kind) {
+                    let missing = structure.energy_capacity.saturating_sub(structure.energy);
+                    if missing > 0 {
+                        requesters.push(offer(StopAction::Transfer, missing, priority));
+                    }
+                }
+            }
+            if structure.energy == 0 {
+                continue;
+            }
+
+            let link_kind = links.get(&structure.id).map(|link| link.kind);
+            if upgrade_container || link_kind == Some(LinkKind::Controller) {
+                upgrade.push(offer(StopAction::Withdraw, structure.energy, 0));
+            } else if link_kind == Some(LinkKind::Hub) || link_kind == Some(LinkKind::Storage) {
+                providers.push(offer(StopAction::Withdraw, structure.energy, 1));
+            } else if let Some(priority) = provider_priority(structure.kind) {
+                providers.push(offer(StopAction::Withdraw, structure.energy, priority));
+            }
+        }
+
+        for resource in world.dropped_resources(room) {
+            providers.push(Offer{
+                id: resource.id,
+                pos: resource.pos,
+                kind: None,
+                action: StopAction::Pickup,
+                expires_in: Some(resource.amount / dropped_decay(resource.amount)),
+                amount: resource.amount,
+                priority: DECAYING_PRIORITY
+            });
+        }
+        for remains in world.remains(room) {
+            if remains.energy == 0 {
+                continue;
+            }
+
+            providers.push(Offer{
+                id: remains.id,
+                pos: remains.pos,
+                kind: None,
+                action: StopAction::Withdraw,
+                amount: remains.energy,
+                priority: DECAYING_PRIORITY,
+                expires_in: Some(remains.ticks_to_decay)
+            });
+        }
+
+        RoomOffers{
+            time: world.time(),
+            providers: providers,
+            requesters: requesters,
+            upgrade: upgrade
+        }
+    }
+
+    fn offers<F: Fn(&RoomOffers) -> &Vec<Offer>>(&self, world: &dyn World, room: &str, select: F) -> Vec<Offer> {
+        let mut rooms = self.rooms.borrow_mut();
+        let stale = rooms.get(room).map_or(true, |offers| offers.time != world.time());
+        if stale {
+            rooms.insert(room.to_string(), self.collect(world, room));
+        }
+
+        select(&rooms[room]).iter()
+            .filter_map(|offer| {
+                let reserved = self.reservations.amount(&offer.id, reservation_kind(offer.action));
+                match offer.amount.saturating_sub(reserved) {
+                    0 => None,
+                    amount => Some(Offer{ amount: amount, ..offer.clone() })
+                }
+            })
+            .collect()
+    }
+
+    /// Energy that can be taken in the room.
+    pub fn providers(&self, world: &dyn World, room: &str) -> Vec<Offer> {
+        self.offers(world, room, |offers| &offers.providers)
+    }
+
+    /// Energy next to the controller, for upgraders.
+    pub fn upgrade_supply(&self, world: &dyn World, room: &str) -> Vec<Offer> {
+        self.offers(world, room, |offers| &offers.upgrade)
+    }
+
+    /// Energy that's needed in the room.
+    pub fn requesters(&self, world: &dyn World, room: &str) -> Vec<Offer> {
+        self.offers(world, room, |offers| &offers.requesters)
+    }
+}
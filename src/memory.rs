@@ -1 +1,518 @@
+use std::{
+    collections::BTreeMap,
+    error::Error
+};
 
+use serde_json::{
+    self,
+    Value
+};
+
+use crate::world::{
+    Position,
+    StructureKind
+};
+
+/// The layout version written by this build.  
+/// Bump it, and add a step to `MIGRATIONS`, whenever the layout changes incompatibly.
+pub const VERSION: u32 = 1;
+
+/// Upgrades the layout of the given version to the next one.
+const MIGRATIONS: &[fn(&mut Value) -> Result<(), Box<dyn Error>>] = &[
+    migrate_v0
+];
+
+/// The root of `Memory`.
+/// 
+/// Kept on the heap between ticks, serialized back at the end of each,
+/// and deserialized again only when it was changed from the console.  
+/// Keys we don't know about are kept as-is, so that data set from the console isn't lost.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Memory {
+    pub version: u32,
+    /// The id of the next creep to spawn.
+    pub id: u32,
+    pub creeps: BTreeMap<String, CreepMemory>,
+    pub roles: BTreeMap<String, RoleMemory>,
+    pub tasks: TasksMemory,
+    pub military: MilitaryMemory,
+    /// Every link we own, by id.
+    pub links: BTreeMap<String, LinkMemory>,
+    /// The layout of every room we own, by room.
+    pub plans: BTreeMap<String, RoomPlan>,
+    /// The creeps waiting to be spawned, by room.
+    pub spawn_queue: BTreeMap<String, Vec<QueuedSpawn>>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>
+}
+
+/// `Memory.creeps.<name>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CreepMemory {
+    pub role: String,
+    pub id: u32,
+    /// The room the creep was spawned for.
+    pub home: String,
+    /// Whether the creep is refilling itself.
+    pub harvesting: bool,
+    /// Per-task memory, keyed by `Task::name`.
+    pub tasks: BTreeMap<String, CreepTaskMemory>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>
+}
+
+/// `Memory.creeps.<name>.tasks.<task>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CreepTaskMemory {
+    /// The id of the object the task is working on.
+    pub target: Option<String>,
+    /// How much the creep reserved on its target, see `reservations::Reservations`.
+    pub reserved: u32,
+    /// Stops the creep still has to visit, in order.
+    pub route: Vec<RouteStop>,
+    /// The last tick the creep executed the task.
+    pub last_run: u32
+}
+
+/// What a creep does at a stop of its route.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StopAction {
+    Withdraw,
+    Pickup,
+    Transfer
+}
+
+/// `Memory.creeps.<name>.tasks.<task>.route[]`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouteStop {
+    pub id: String,
+    pub pos: Position,
+    pub action: StopAction,
+    /// How much energy is reserved for the stop.
+    pub amount: u32
+}
+
+/// `Memory.roles.<role>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoleMemory {
+    /// How many creeps ran this role in the current tick.
+    pub run_count: u32
+}
+
+/// `Memory.tasks`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TasksMemory {
+    pub harvest: HarvestMemory,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>
+}
+
+/// `Memory.tasks.harvest`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarvestMemory {
+    pub sources: BTreeMap<String, SourceMemory>
+}
+
+/// `Memory.tasks.harvest.sources.<id>`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceMemory {
+    /// How many creeps can harvest at the same time.
+    pub creep_limit: u32,
+    /// How many creeps harvested in this tick.
+    pub counter: u32,
+    /// How many creeps harvested in the previous tick.
+    pub prev_counter: u32,
+    /// Populates when the first creep begins harvesting there.
+    pub pos: Option<Position>,
+    /// The tile a static miner parks on, populates when the first miner arrives.
+    pub container: Option<Position>
+}
+
+impl Default for SourceMemory {
+    fn default() -> SourceMemory {
+        SourceMemory{
+            creep_limit: 4,
+            counter: 0,
+            prev_counter: 0,
+            pos: None,
+            container: None
+        }
+    }
+}
+
+/// What a link is used for, see `links::LinkManager`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// Next to a source, sends what the miner puts in.
+    Source,
+    /// Next to the controller, feeds upgraders.
+    Controller,
+    /// Next to the storage, takes what no one else needs, and tops up the controller link.
+    Storage,
+    /// Anywhere else, emptied by haulers.
+    Hub
+}
+
+impl Default for LinkKind {
+    fn default() -> LinkKind {
+        LinkKind::Hub
+    }
+}
+
+/// `Memory.links.<id>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkMemory {
+    pub kind: LinkKind
+}
+
+/// `Memory.plans.<room>`, see `planner::RoomPlanner`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomPlan {
+    /// The tile the base is built around.
+    pub anchor: (u32, u32),
+    /// Ordered by the controller level they unlock at, then by importance.
+    pub structures: Vec<PlannedStructure>
+}
+
+/// `Memory.plans.<room>.structures[]`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlannedStructure {
+    pub kind: StructureKind,
+    pub x: u32,
+    pub y: u32,
+    /// The controller level to build it at.
+    pub level: u32
+}
+
+/// `Memory.spawn_queue.<room>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueuedSpawn {
+    pub role: String,
+    /// Lower is more urgent.
+    pub priority: i32,
+    pub cost: u32,
+    /// The tick the request was first made.
+    pub since: u32,
+    /// How many times spawning failed in a row.
+    pub attempts: u32,
+    /// The request is on hold until this tick, after failing too many times or costing more than the room holds.
+    pub retry_at: u32,
+    /// Whether it's an emergency spawn, that uses any available energy.
+    pub bootstrap: bool
+}
+
+/// `Memory.military`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MilitaryMemory {
+    pub tower: TowerHandlerMemory,
+    /// Rooms with hostiles in them, by room.
+    pub threats: BTreeMap<String, ThreatMemory>,
+    /// Every player whose creeps we've seen, by name.
+    pub players: BTreeMap<String, PlayerMemory>,
+    /// Safe mode, by room.
+    pub defense: BTreeMap<String, DefenseMemory>,
+    /// Every squad, by the name of the flag it's sent to.
+    pub squads: BTreeMap<String, SquadMemory>,
+    /// Invader cores, and the loot they left behind, by room.
+    pub invaders: BTreeMap<String, InvaderMemory>
+}
+
+/// `Memory.military.tower`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TowerHandlerMemory {
+    /// Keyed by the tower's id.
+    pub towers: BTreeMap<String, TowerMemory>,
+    /// Energy towers keep for defense, they only repair with what's above it.
+    pub energy_reserve: u32
+}
+
+impl Default for TowerHandlerMemory {
+    fn default() -> TowerHandlerMemory {
+        TowerHandlerMemory{
+            towers: BTreeMap::new(),
+            energy_reserve: 500
+        }
+    }
+}
+
+/// `Memory.military.tower.towers.<id>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TowerMemory {
+    /// The creep being attacked.
+    pub target: Option<String>,
+    /// The structure being repaired.
+    pub job: Option<String>
+}
+
+/// `Memory.military.threats.<room>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreatMemory {
+    /// The threat of the hostiles in the room, last time we saw them.
+    pub score: u32,
+    /// Players that own them.
+    pub owners: Vec<String>,
+    /// The tick the room got hostiles in it.
+    pub since: u32,
+    pub last_seen: u32
+}
+
+/// `Memory.military.defense.<room>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DefenseMemory {
+    /// Never activate safe mode in the room, set by hand.
+    pub no_safe_mode: bool,
+    /// The tick safe mode was last activated.
+    pub activated_at: Option<u32>,
+    /// The last time we warned that safe mode is needed but can't be activated.
+    pub warned_at: Option<u32>
+}
+
+/// How a squad's creeps are arranged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Formation {
+    /// An attacker, followed by its healer.
+    Duo,
+    /// Two ranged attackers and two healers, moving as a 2x2 block.
+    Quad
+}
+
+impl Default for Formation {
+    fn default() -> Formation {
+        Formation::Duo
+    }
+}
+
+/// What a squad is doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SquadState {
+    /// Waiting for its members to spawn.
+    Forming,
+    /// On its way to the target room.
+    Travelling,
+    /// Fighting in the target room.
+    Engaged,
+    /// Falling back to its home room to heal up.
+    Retreating,
+    /// Going home to be recycled, its flag or objective is gone.
+    Disbanding
+}
+
+impl Default for SquadState {
+    fn default() -> SquadState {
+        SquadState::Forming
+    }
+}
+
+/// `Memory.military.squads.<name>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SquadMemory {
+    pub formation: Formation,
+    pub state: SquadState,
+    /// The room the squad is spawned in and retreats to.
+    pub home: String,
+    /// Names of the members.
+    pub members: Vec<String>,
+    /// Where the squad's flag is.
+    pub target: Option<Position>,
+    /// The structure the squad is sent to destroy, for squads without a flag.
+    pub objective: Option<String>
+}
+
+/// `Memory.military.invaders.<room>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvaderMemory {
+    /// The core's id, None once it's destroyed and only its loot is left.
+    pub core: Option<String>,
+    pub level: u32,
+    /// Where the core is, or was.
+    pub pos: Option<Position>,
+    /// The tick the stronghold collapses, squads stay out of the room until then.
+    pub avoid_until: Option<u32>,
+    /// The room strike groups are sent from, which also collects the loot.
+    pub home: Option<String>,
+    /// Strike groups sent at the core so far.
+    pub strikes: u32,
+    /// Whether the ruins in the room still have loot in them.
+    pub loot: bool
+}
+
+/// `Memory.military.players.<name>`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerMemory {
+    pub first_seen: u32,
+    pub last_seen: u32,
+    /// The highest threat of their creeps in a single room.
+    pub max_threat: u32,
+    /// Rooms we've seen their creeps in, the most recent last.  
+    /// Only the last few are kept.
+    pub rooms: Vec<String>
+}
+
+impl Memory {
+    /// Parses raw memory, migrating it from older layouts if needed.  
+    /// Empty memory starts from scratch.
+    pub fn parse(raw: &str) -> Result<Memory, Box<dyn Error>> {
+        if raw.is_empty() {
+            return Ok(Memory::fresh());
+        }
+
+        serde_json::from_str(raw).map_err(|err| Box::from(err))
+            .and_then(Memory::from_value)
+    }
+
+    pub fn from_value(mut value: Value) -> Result<Memory, Box<dyn Error>> {
+        if value.is_null() {
+            return Ok(Memory::fresh());
+        }
+
+        let mut version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+        if version > VERSION {
+            return Err(Box::from(format!("memory version {} is newer than {}", version, VERSION)));
+        }
+
+        while version < VERSION {
+            MIGRATIONS[version as usize](&mut value)?;
+            version += 1;
+            info!("migrated memory to version {}", version);
+        }
+
+        let mut memory: Memory = serde_json::from_value(value)?;
+        memory.version = VERSION;
+        Ok(memory)
+    }
+
+    pub fn fresh() -> Memory {
+        Memory{
+            version: VERSION,
+            ..Memory::default()
+        }
+    }
+
+    pub fn serialize(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn creep(&mut self, name: &str) -> &mut CreepMemory {
+        self.creeps.entry(name.to_string()).or_insert_with(CreepMemory::default)
+    }
+
+    pub fn role(&mut self, name: &str) -> &mut RoleMemory {
+        self.roles.entry(name.to_string()).or_insert_with(RoleMemory::default)
+    }
+}
+
+/// The original, unversioned layout:
+///   * the harvest task stored its target as `creeps.<name>.tasks.harvest.source`
+///   * creep and source counters could be negative or missing
+fn migrate_v0(value: &mut Value) -> Result<(), Box<dyn Error>> {
+    let root = value.as_object_mut().ok_or("memory is not a dict")?;
+
+    if let Some(creeps) = root.get_mut("creeps").and_then(Value::as_object_mut) {
+        for creep in creeps.values_mut() {
+            if let Some(harvest) = creep.pointer_mut("/tasks/harvest").and_then(Value::as_object_mut) {
+                if let Some(source) = harvest.remove("source") {
+                    harvest.insert("target".to_string(), source);
+                }
+            }
+
+            clamp_counter(creep, "id");
+        }
+    }
+
+    if let Some(sources) = root.get_mut("tasks")
+                               .and_then(|tasks| tasks.pointer_mut("/harvest/sources"))
+                               .and_then(Value::as_object_mut) {
+        for source in sources.values_mut() {
+            clamp_counter(source, "creep_limit");
+            clamp_counter(source, "counter");
+            clamp_counter(source, "prev_counter");
+        }
+    }
+
+    if let Some(roles) = root.get_mut("roles").and_then(Value::as_object_mut) {
+        for role in roles.values_mut() {
+            clamp_counter(role, "run_count");
+        }
+    }
+
+    clamp_counter(value, "id");
+    Ok(())
+}
+
+fn clamp_counter(parent: &mut Value, key: &str) {
+    if let Some(counter) = parent.get_mut(key) {
+        if counter.as_i64().map(|x| x < 0).unwrap_or(false) {
+            *counter = json!(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_unversioned_memory() {
+        let raw = r#"{
+            "id": -3,
+            "creeps": {
+                "harvester1": { "role": "harvester", "id": 4, "tasks": { "harvest": { "source": "source1" } } }
+            },
+            "tasks": {
+                "harvest": { "sources": { "source1": { "creep_limit": 2, "counter": -1, "prev_counter": 1 } } }
+            }
+        }"#;
+        let mut memory = Memory::parse(raw).unwrap();
+
+        assert_eq!(memory.version, VERSION);
+        assert_eq!(memory.id, 0);
+        assert_eq!(memory.creep("harvester1").tasks["harvest"].target, Some("source1".to_string()));
+
+        let source = &memory.tasks.harvest.sources["source1"];
+        assert_eq!((source.creep_limit, source.counter, source.prev_counter), (2, 0, 1));
+    }
+
+    #[test]
+    fn keeps_unknown_keys() {
+        let raw = r#"{
+            "version": 1,
+            "stats": { "cpu": 12 },
+            "creeps": { "upgrader1": { "role": "upgrader", "note": "by hand" } },
+            "tasks": { "scout": { "rooms": ["W2N1"] } }
+        }"#;
+        let saved: Value = serde_json::from_str(&Memory::parse(raw).unwrap().serialize().unwrap()).unwrap();
+
+        assert_eq!(saved["stats"], json!({ "cpu": 12 }));
+        assert_eq!(saved["creeps"]["upgrader1"]["note"], json!("by hand"));
+        assert_eq!(saved["tasks"]["scout"], json!({ "rooms": ["W2N1"] }));
+    }
+
+    #[test]
+    fn rejects_newer_memory() {
+        assert!(Memory::parse(r#"{ "version": 99 }"#).is_err());
+        assert!(Memory::parse("not json").is_err());
+        assert_eq!(Memory::parse("").unwrap().version, VERSION);
+    }
+}
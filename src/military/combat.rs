@@ -0,0 +1,177 @@
+use std::cmp;
+
+use screeps::constants::Part;
+
+use crate::world::{
+    CreepState,
+    StructureState
+};
+
+/// A tower's attack at close range, falloff applied with `tower_power`.
+pub const TOWER_POWER_ATTACK: u32 = 600;
+pub const TOWER_POWER_HEAL: u32 = 400;
+pub const TOWER_POWER_REPAIR: u32 = 800;
+/// Energy a tower spends on any action.
+pub const TOWER_ENERGY_COST: u32 = 10;
+
+const TOWER_OPTIMAL_RANGE: u32 = 5;
+const TOWER_FALLOFF_RANGE: u32 = 20;
+/// The fraction of power lost at `TOWER_FALLOFF_RANGE` and beyond.
+const TOWER_FALLOFF: f64 = 0.75;
+
+/// Damage or heal a part does per tick, before boosts.
+pub const ATTACK_POWER: u32 = 30;
+pub const RANGED_ATTACK_POWER: u32 = 10;
+pub const HEAL_POWER: u32 = 12;
+pub const RANGED_HEAL_POWER: u32 = 4;
+pub const DISMANTLE_POWER: u32 = 50;
+
+/// The power of a tower action at the given range.
+/// Full up to range 5, then falling off linearly to a quarter at range 20.
+pub fn tower_power(power: u32, range: u32) -> u32 {
+    let range = cmp::min(cmp::max(range, TOWER_OPTIMAL_RANGE), TOWER_FALLOFF_RANGE);
+    let falloff = TOWER_FALLOFF * (range - TOWER_OPTIMAL_RANGE) as f64 / (TOWER_FALLOFF_RANGE - TOWER_OPTIMAL_RANGE) as f64;
+    (power as f64 * (1.0 - falloff)) as u32
+}
+
+/// How much a boost multiplies what the part does.
+pub fn boost_multiplier(part: Part, boost: Option<&str>) -> u32 {
+    match (part, boost) {
+        (Part::Attack, Some("UH")) | (Part::RangedAttack, Some("KO")) |
+        (Part::Heal, Some("LO")) | (Part::Work, Some("ZH")) => 2,
+        (Part::Attack, Some("UH2O")) | (Part::RangedAttack, Some("KHO2")) |
+        (Part::Heal, Some("LHO2")) | (Part::Work, Some("ZH2O")) => 3,
+        (Part::Attack, Some("XUH2O")) | (Part::RangedAttack, Some("XKHO2")) |
+        (Part::Heal, Some("XLHO2")) | (Part::Work, Some("XZH2O")) => 4,
+        _ => 1
+    }
+}
+
+/// The share of damage a TOUGH part takes with a boost.
+fn tough_ratio(boost: Option<&str>) -> f64 {
+    match boost {
+        Some("GO") => 0.7,
+        Some("GHO2") => 0.5,
+        Some("XGHO2") => 0.3,
+        _ => 1.0
+    }
+}
+
+/// Hits the creep can heal in a tick on a target at the given range, 0 if it's out of range.
+pub fn heal_power(creep: &CreepState, range: u32) -> u32 {
+    let per_part = match range {
+        0..=1 => HEAL_POWER,
+        2..=3 => RANGED_HEAL_POWER,
+        _ => return 0
+    };
+
+    creep.body.iter()
+        .filter(|part| part.part == Part::Heal && part.hits > 0)
+        .map(|part| per_part * boost_multiplier(Part::Heal, part.boost.as_ref().map(String::as_str)))
+        .sum()
+}
+
+/// The most the target can be healed next tick, by itself and the creeps around it.
+/// `healers` may include the target.
+pub fn predicted_heal(target: &CreepState, healers: &[CreepState]) -> u32 {
+    healers.iter().map(|healer| heal_power(healer, healer.pos.range_to(&target.pos))).sum()
+}
+
+/// Hits the towers take off the target in a tick, after its TOUGH parts and the heal of `healers`.
+/// Negative if it's healed faster than the towers can damage it. Towers without the energy to fire don't count.
+pub fn tower_net_damage(target: &CreepState, towers: &[&StructureState], healers: &[CreepState]) -> i64 {
+    let damage = towers.iter()
+        .filter(|tower| tower.energy >= TOWER_ENERGY_COST)
+        .map(|tower| tower_power(TOWER_POWER_ATTACK, tower.pos.range_to(&target.pos)))
+        .sum();
+    damage_taken(target, damage) as i64 - predicted_heal(target, healers) as i64
+}
+
+/// Hits the creep loses to `damage`, after its boosted TOUGH parts absorb their share.
+/// Parts are hit in the order of the body, the way the game applies damage.
+pub fn damage_taken(target: &CreepState, damage: u32) -> u32 {
+    let mut remaining = damage as f64;
+    let mut taken = 0.0;
+
+    for part in target.body.iter().filter(|part| part.hits > 0) {
+        if remaining <= 0.0 {
+            break;
+        }
+
+        let ratio = if part.part == Part::Tough { tough_ratio(part.boost.as_ref().map(String::as_str)) } else { 1.0 };
+        let breaks_at = part.hits as f64 / ratio;
+        if remaining >= breaks_at {
+            taken += part.hits as f64;
+            remaining -= breaks_at;
+        } else {
+            taken += remaining * ratio;
+            remaining = 0.0;
+        }
+    }
+
+    (taken + remaining) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock,
+        Position
+    };
+
+    fn creep(body: &[Part]) -> CreepState {
+        mock::creep("target", Position::new(25, 25, "W1N1"), body)
+    }
+
+    fn tough(boost: &str) -> CreepState {
+        let mut creep = creep(&[Part::Tough, Part::Move]);
+        creep.body[0].boost = Some(boost.to_string());
+        creep
+    }
+
+    #[test]
+    fn tower_power_falls_off() {
+        let cases = [(0, 600), (1, 600), (5, 600), (10, 450), (15, 300), (20, 150), (30, 150)];
+        for &(range, power) in cases.iter() {
+            assert_eq!(tower_power(TOWER_POWER_ATTACK, range), power, "at range {}", range);
+        }
+        assert_eq!(tower_power(TOWER_POWER_HEAL, 20), 100);
+    }
+
+    #[test]
+    fn damage_taken_by_unboosted_creeps() {
+        let target = creep(&[Part::Tough, Part::Move]);
+        assert_eq!(damage_taken(&target, 150), 150);
+        // more than the creep has, the rest is still dealt
+        assert_eq!(damage_taken(&target, 500), 500);
+    }
+
+    #[test]
+    fn damage_taken_by_boosted_tough() {
+        let cases = [("GO", 100, 70), ("GHO2", 100, 50), ("XGHO2", 100, 30), ("XGHO2", 300, 90)];
+        for &(boost, damage, taken) in cases.iter() {
+            assert_eq!(damage_taken(&tough(boost), damage), taken, "{} damage with {}", damage, boost);
+        }
+
+        // the tough part breaks at 333 damage, the rest hits the next part in full
+        assert_eq!(damage_taken(&tough("XGHO2"), 400), 166);
+    }
+
+    #[test]
+    fn damage_skips_broken_parts() {
+        let mut target = tough("XGHO2");
+        target.body[0].hits = 0;
+        assert_eq!(damage_taken(&target, 100), 100);
+    }
+
+    #[test]
+    fn boosts_multiply_power() {
+        assert_eq!(boost_multiplier(Part::Attack, None), 1);
+        assert_eq!(boost_multiplier(Part::Heal, Some("LO")), 2);
+        assert_eq!(boost_multiplier(Part::RangedAttack, Some("KHO2")), 3);
+        assert_eq!(boost_multiplier(Part::Work, Some("XZH2O")), 4);
+        // boosts only work on their own part
+        assert_eq!(boost_multiplier(Part::Attack, Some("XZH2O")), 1);
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    rc::Rc
+};
+
+use screeps::constants::ReturnCode;
+
+use crate::{
+    memory::DefenseMemory,
+    military::{
+        combat,
+        threat::{
+            RoomThreat,
+            ThreatAssessment
+        }
+    },
+    world::{
+        ControllerState,
+        StructureIntent,
+        StructureKind,
+        StructureState,
+        World
+    }
+};
+
+/// A spawn or storage is about to fall if the hostiles in range destroy it within this many ticks.
+const DANGER_TICKS: u32 = 20;
+/// Hostiles this close to a structure can hit it, the range of a ranged attack.
+const ATTACK_RANGE: u32 = 3;
+/// Ticks between warnings that safe mode is needed but can't be activated.
+const WARN_INTERVAL: u32 = 1000;
+
+/// Whether the towers in `structures` out-damage the heal on every hostile that can hurt us.
+pub fn towers_win(structures: &[StructureState], threat: &RoomThreat) -> bool {
+    let towers: Vec<&StructureState> = structures.iter().filter(|structure| structure.kind == StructureKind::Tower && structure.my).collect();
+    let hostiles: Vec<_> = threat.hostiles.iter().map(|hostile| hostile.creep.clone()).collect();
+
+    threat.hostiles.iter()
+        .filter(|hostile| !hostile.kind.is_harmless())
+        .all(|hostile| combat::tower_net_damage(&hostile.creep, &towers, &hostiles) > 0)
+}
+
+/// Activates safe mode in rooms the towers can't hold.
+///
+/// Safe mode is needed when hostiles that can hurt us are inside the ramparts,
+/// or are about to destroy a spawn or the storage,
+/// and the towers can't out-damage the heal on all of them.
+/// It's skipped in rooms with `Memory.military.defense.<room>.no_safe_mode` set.
+/// Activations are logged as errors and failures as warnings, both of which are sent as notifications.
+/// Failures are only reported once every `WARN_INTERVAL` ticks per room.
+pub struct Defense {
+    threats: Rc<ThreatAssessment>
+}
+
+impl Defense {
+    pub fn new(threats: Rc<ThreatAssessment>) -> Defense {
+        Defense{
+            threats: threats
+        }
+    }
+
+    /// Whether a dangerous hostile is somewhere it can't reach from the exits without passing our walls and ramparts.
+    fn breached(&self, world: &dyn World, room: &str, structures: &[StructureState], threat: &RoomThreat) -> bool {
+        let terrain = world.terrain(room);
+        let mut blocked = vec![false; 2500];
+        for structure in structures.iter() {
+            if !structure.kind.is_walkable() || (structure.kind == StructureKind::Rampart && structure.my) {
+                blocked[(structure.pos.y * 50 + structure.pos.x) as usize] = true;
+            }
+        }
+
+        let mut reached = vec![false; 2500];
+        let mut queue = VecDeque::new();
+        for i in 0..50 {
+            for &(x, y) in [(i, 0), (i, 49), (0, i), (49, i)].iter() {
+                let index = (y * 50 + x) as usize;
+                if !terrain.is_wall(x, y) && !blocked[index] && !reached[index] {
+                    reached[index] = true;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+        while let Some((x, y)) = queue.pop_front() {
+            for dy in -1..2 {
+                for dx in -1..2 {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx > 49 || ny > 49 {
+                        continue;
+                    }
+                    let index = (ny * 50 + nx) as usize;
+                    if !reached[index] && !blocked[index] && !terrain.is_wall(nx, ny) {
+                        reached[index] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+        }
+
+        threat.hostiles.iter()
+            .filter(|hostile| !hostile.kind.is_harmless())
+            .any(|hostile| !reached[(hostile.creep.pos.y * 50 + hostile.creep.pos.x) as usize])
+    }
+
+    /// A spawn or storage the hostiles in range destroy within `DANGER_TICKS`.
+    fn endangered<'a>(&self, structures: &'a [StructureState], threat: &RoomThreat) -> Option<&'a StructureState> {
+        structures.iter()
+            .filter(|structure| structure.my && (structure.kind == StructureKind::Spawn || structure.kind == StructureKind::Storage))
+            .find(|structure| {
+                let damage: u32 = threat.hostiles.iter()
+                    .filter(|hostile| hostile.creep.pos.range_to(&structure.pos) <= ATTACK_RANGE)
+                    .map(|hostile| hostile.damage)
+                    .sum();
+                damage > 0 && structure.hits <= damage * DANGER_TICKS
+            })
+    }
+
+    pub fn run(&self, world: &dyn World) -> Result<(), Box<dyn Error>> {
+        for room in world.rooms() {
+            let controller = match room.controller {
+                Some(ref controller) if controller.my => controller,
+                _ => continue
+            };
+            let threat = self.threats.room(&room.name);
+            if threat.hostiles.iter().all(|hostile| hostile.kind.is_harmless()) || controller.safe_mode > 0 {
+                continue;
+            }
+
+            let structures = world.structures(&room.name);
+            let reason = if let Some(structure) = self.endangered(&structures, &threat) {
+                format!("{} {} is about to be destroyed", structure.kind.as_str(), structure.id)
+            } else if self.breached(world, &room.name, &structures, &threat) {
+                "hostiles are inside the ramparts".to_string()
+            } else {
+                continue;
+            };
+            if towers_win(&structures, &threat) {
+                debug!("{} in {}, but the towers can handle it", reason, room.name);
+                continue;
+            }
+
+            self.activate(world, &room.name, controller, &reason, &threat);
+        }
+
+        Ok(())
+    }
+
+    fn activate(&self, world: &dyn World, room: &str, controller: &ControllerState, reason: &str, threat: &RoomThreat) {
+        let time = world.time();
+        let mut memory = world.memory();
+        let defense = memory.military.defense.entry(room.to_string()).or_insert_with(DefenseMemory::default);
+
+        let failure = if defense.no_safe_mode {
+            "it's disabled for the room".to_string()
+        } else if controller.safe_mode_available == 0 {
+            "none are available".to_string()
+        } else if controller.safe_mode_cooldown > 0 {
+            format!("it's on cooldown for {} ticks", controller.safe_mode_cooldown)
+        } else {
+            match world.structure_intent(&controller.id, StructureIntent::ActivateSafeMode) {
+                ReturnCode::Ok => {
+                    let attackers = if threat.is_player_attack() { format!("{:?}", threat.owners) } else { "NPCs".to_string() };
+                    error!("activated safe mode in {}, {}, threat {} from {}", room, reason, threat.score, attackers);
+                    defense.activated_at = Some(time);
+                    return;
+                },
+                code => format!("activation failed with {:?}", code)
+            }
+        };
+
+        if defense.warned_at.map_or(true, |warned_at| time >= warned_at + WARN_INTERVAL) {
+            warn!("{} needs safe mode, {}, but {}", room, reason, failure);
+            defense.warned_at = Some(time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use screeps::constants::Part;
+
+    use super::*;
+    use crate::{
+        military::threat,
+        world::{
+            mock::{
+                self,
+                MockWorld
+            },
+            CreepState,
+            Position,
+            RoomState
+        }
+    };
+
+    fn hostile(name: &str, x: u32, y: u32, body: &[Part]) -> CreepState {
+        let mut creep = mock::creep(name, Position::new(x, y, "W1N1"), body);
+        creep.owner = "player".to_string();
+        creep.my = false;
+        creep
+    }
+
+    fn room(safe_mode_available: u32, safe_mode_cooldown: u32) -> RoomState {
+        RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: Some(ControllerState{
+                id: "controller".to_string(),
+                pos: Position::new(40, 40, "W1N1"),
+                my: true,
+                level: 4,
+                progress: 0,
+                progress_total: 405000,
+                safe_mode: 0,
+                safe_mode_available: safe_mode_available,
+                safe_mode_cooldown: safe_mode_cooldown
+            })
+        }
+    }
+
+    /// An owned room with a ring of ramparts from (20, 20) to (30, 30), and an attacker at the given tile.
+    fn world_with_attacker(x: u32, y: u32) -> MockWorld {
+        let world = MockWorld::new();
+        world.add_room(room(1, 0));
+        for ring_y in 20..31 {
+            for ring_x in 20..31 {
+                if ring_x == 20 || ring_x == 30 || ring_y == 20 || ring_y == 30 {
+                    let id = format!("rampart-{}-{}", ring_x, ring_y);
+                    world.add_structure(mock::structure(&id, StructureKind::Rampart, Position::new(ring_x, ring_y, "W1N1")));
+                }
+            }
+        }
+        world.add_hostile(hostile("attacker", x, y, &[Part::Attack, Part::Move]));
+        world
+    }
+
+    /// Runs the defense for a tick, returns whether safe mode was activated.
+    fn run(world: &MockWorld) -> bool {
+        world.clear_intents();
+        let threats = Rc::new(ThreatAssessment::new());
+        threats.update(world);
+        Defense::new(threats).run(world).unwrap();
+
+        world.structure_intents().iter().any(|(_, intent)| *intent == StructureIntent::ActivateSafeMode)
+    }
+
+    fn warned_at(world: &MockWorld) -> Option<u32> {
+        world.memory().military.defense.get("W1N1").and_then(|defense| defense.warned_at)
+    }
+
+    #[test]
+    fn activates_when_breached() {
+        let world = world_with_attacker(25, 25);
+
+        assert!(run(&world));
+        assert_eq!(world.memory().military.defense["W1N1"].activated_at, Some(1));
+    }
+
+    #[test]
+    fn ignores_hostiles_outside() {
+        let world = world_with_attacker(10, 10);
+
+        assert!(!run(&world));
+        assert!(world.memory().military.defense.get("W1N1").is_none());
+    }
+
+    #[test]
+    fn ignores_harmless_hostiles_inside() {
+        let world = MockWorld::new();
+        world.add_room(room(1, 0));
+        world.add_hostile(hostile("scout", 25, 25, &[Part::Move]));
+        world.add_structure(mock::structure("spawn", StructureKind::Spawn, Position::new(25, 26, "W1N1")));
+
+        assert!(!run(&world));
+    }
+
+    #[test]
+    fn activates_when_spawn_endangered() {
+        let world = world_with_attacker(10, 10);
+        let mut spawn = mock::structure("spawn", StructureKind::Spawn, Position::new(10, 12, "W1N1"));
+        spawn.hits = 500;
+        world.add_structure(spawn);
+
+        // 30 damage a tick brings down its 500 hits within DANGER_TICKS
+        assert!(run(&world));
+    }
+
+    #[test]
+    fn trusts_winning_towers() {
+        let world = world_with_attacker(25, 25);
+        let mut tower = mock::structure("tower", StructureKind::Tower, Position::new(26, 26, "W1N1"));
+        tower.energy = 1000;
+        world.add_structure(tower);
+
+        assert!(!run(&world));
+    }
+
+    #[test]
+    fn towers_lose_to_boosted_heal() {
+        let mut tower = mock::structure("tower", StructureKind::Tower, Position::new(25, 25, "W1N1"));
+        tower.energy = 1000;
+        let structures = vec![tower];
+
+        let mut body = vec![Part::Attack];
+        body.extend(vec![Part::Heal; 13]);
+        let mut healer = hostile("healer", 25, 35, &body);
+        let threat = RoomThreat{
+            hostiles: vec![threat::assess(&healer)],
+            ..RoomThreat::default()
+        };
+        // 10 tiles away the tower does 450, the heal is 156
+        assert!(towers_win(&structures, &threat));
+
+        for part in healer.body.iter_mut() {
+            part.boost = Some("XLHO2".to_string());
+        }
+        let threat = RoomThreat{
+            hostiles: vec![threat::assess(&healer)],
+            ..RoomThreat::default()
+        };
+        assert!(!towers_win(&structures, &threat));
+    }
+
+    #[test]
+    fn warns_once_per_interval() {
+        let blocked = [room(0, 0), room(1, 5000)];
+        for blocking in blocked.iter() {
+            let world = world_with_attacker(25, 25);
+            world.update_room(blocking.clone());
+
+            assert!(!run(&world));
+            assert_eq!(warned_at(&world), Some(1));
+            world.set_time(WARN_INTERVAL);
+            run(&world);
+            assert_eq!(warned_at(&world), Some(1));
+            world.set_time(1 + WARN_INTERVAL);
+            run(&world);
+            assert_eq!(warned_at(&world), Some(1 + WARN_INTERVAL));
+        }
+    }
+
+    #[test]
+    fn respects_no_safe_mode() {
+        let world = world_with_attacker(25, 25);
+        world.memory().military.defense.entry("W1N1".to_string()).or_insert_with(DefenseMemory::default).no_safe_mode = true;
+
+        assert!(!run(&world));
+        assert_eq!(warned_at(&world), Some(1));
+        world.set_time(2);
+        run(&world);
+        assert_eq!(warned_at(&world), Some(1));
+    }
+}
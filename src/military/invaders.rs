@@ -0,0 +1,250 @@
+use std::{
+    cmp,
+    error::Error
+};
+
+use crate::{
+    memory::{
+        Formation,
+        InvaderMemory,
+        SquadMemory,
+        SquadState
+    },
+    world::{
+        InvaderCoreState,
+        RemainsKind,
+        SpawnState,
+        World
+    }
+};
+
+/// Cores up to this level are attacked, stronger strongholds are left to collapse.
+const MAX_STRIKE_LEVEL: u32 = 1;
+/// Strike groups are only sent this many rooms away from their home.
+const MAX_STRIKE_DISTANCE: u32 = 2;
+/// After this many strike groups failed, the core is left alone.
+const MAX_STRIKES: u32 = 3;
+
+/// Coordinates of a room from its name, `E0S0` is (0, 0) and `W0N0` is (-1, -1).
+fn room_coords(name: &str) -> Option<(i32, i32)> {
+    let split = name.find(|c: char| c == 'N' || c == 'S')?;
+    let (horizontal, vertical) = name.split_at(split);
+    let x: i32 = horizontal.get(1..)?.parse().ok()?;
+    let y: i32 = vertical.get(1..)?.parse().ok()?;
+
+    let x = match horizontal.chars().next()? {
+        'W' => -x - 1,
+        'E' => x,
+        _ => return None
+    };
+    let y = match vertical.chars().next()? {
+        'N' => -y - 1,
+        'S' => y,
+        _ => return None
+    };
+    Some((x, y))
+}
+
+/// Rooms between the two, in a straight line, ignoring which exits exist.
+pub fn room_distance(from: &str, to: &str) -> Option<u32> {
+    let (from, to) = (room_coords(from)?, room_coords(to)?);
+    Some(cmp::max((from.0 - to.0).abs(), (from.1 - to.1).abs()) as u32)
+}
+
+/// Whether squads should stay out of the room, because of a stronghold that hasn't collapsed yet.  
+/// Strike groups sent by `Invaders` ignore it.  
+/// The bot doesn't mine remote rooms yet, flag squads are the only ones checking it for now.
+/// Picking remote rooms to mine has to skip the avoided ones too.
+pub fn is_avoided(world: &dyn World, room: &str) -> bool {
+    let time = world.time();
+    world.memory().military.invaders.get(room).map_or(false, |invader| {
+        invader.core.is_some() && invader.level > 0 && invader.avoid_until.map_or(true, |until| time < until)
+    })
+}
+
+/// The room of the closest spawn, if it's close enough to send a strike group from.
+fn strike_home(room: &str, spawns: &[SpawnState]) -> Option<String> {
+    spawns.iter()
+        .filter_map(|spawn| room_distance(&spawn.pos.room, room).map(|distance| (distance, spawn.pos.room.clone())))
+        .filter(|&(distance, _)| distance <= MAX_STRIKE_DISTANCE)
+        .min()
+        .map(|(_, home)| home)
+}
+
+/// Finds NPC invader cores in every room we can see, and clears the weak ones.
+///
+/// Cores are kept in `Memory.military.invaders`, by room.
+/// Level 0 cores only reserve the room and get a duo, level 1 strongholds get a quad,
+/// both spawned in the closest room within `MAX_STRIKE_DISTANCE` and sent as squads without a flag, see `military::squad`.
+/// A core that has beaten `MAX_STRIKES` of them is left alone.
+/// Stronger strongholds aren't attacked, and flag squads avoid any stronghold room until it collapses, see `is_avoided`.
+///
+/// Once a core is destroyed its squad goes home, and looters from the same room empty the ruins, see `roles::looter`.
+pub struct Invaders;
+
+impl Invaders {
+    pub fn new() -> Invaders {
+        Invaders{}
+    }
+
+    /// Records the core, and sends a strike group at it if we can take it and none is on its way.
+    fn found(&self, world: &dyn World, room: &str, core: InvaderCoreState, spawns: &[SpawnState]) {
+        let time = world.time();
+        let mut memory = world.memory();
+        let memory = &mut *memory;
+
+        let invader = memory.military.invaders.entry(room.to_string()).or_insert_with(|| {
+            match core.level {
+                0 => info!("invader core in {}", room),
+                level => warn!("level {} stronghold in {}, avoiding it", level, room)
+            }
+            InvaderMemory::default()
+        });
+        invader.core = Some(core.id.clone());
+        invader.level = core.level;
+        invader.pos = Some(core.pos.clone());
+        invader.avoid_until = match core.level {
+            0 => None,
+            _ => core.ticks_to_collapse.map(|ticks| time + ticks)
+        };
+
+        // it can't be damaged before it's deployed
+        let name = format!("invaders {}", room);
+        if core.level > MAX_STRIKE_LEVEL || core.ticks_to_deploy > 0 || invader.strikes >= MAX_STRIKES ||
+           memory.military.squads.contains_key(&name) {
+            return;
+        }
+        let home = match strike_home(room, spawns) {
+            Some(home) => home,
+            None => return
+        };
+
+        let formation = match core.level {
+            0 => Formation::Duo,
+            _ => Formation::Quad
+        };
+        info!("sending a {:?} from {} at the level {} invader core in {}", formation, home, core.level, room);
+        memory.military.squads.insert(name, SquadMemory{
+            formation: formation,
+            home: home.clone(),
+            target: Some(core.pos),
+            objective: Some(core.id),
+            ..SquadMemory::default()
+        });
+        invader.home = Some(home);
+        invader.strikes += 1;
+    }
+
+    /// The room has no core (anymore), disbands its strike group and checks the ruins for loot.
+    fn cleared(&self, world: &dyn World, room: &str) {
+        if !world.memory().military.invaders.contains_key(room) {
+            return;
+        }
+        let loot = world.remains(room).iter().any(|remains| remains.kind == RemainsKind::Ruin && remains.store > 0);
+        let mut memory = world.memory();
+        let memory = &mut *memory;
+
+        let invader = match memory.military.invaders.get_mut(room) {
+            Some(invader) => invader,
+            None => return
+        };
+        if let Some(core) = invader.core.take() {
+            info!("invader core in {} is gone", room);
+            for squad in memory.military.squads.values_mut().filter(|squad| squad.objective.as_ref() == Some(&core)) {
+                squad.state = SquadState::Disbanding;
+            }
+        }
+
+        invader.loot = loot && invader.home.is_some();
+        if !invader.loot {
+            memory.military.invaders.remove(room);
+        }
+    }
+
+    pub fn run(&self, world: &dyn World) -> Result<(), Box<dyn Error>> {
+        let time = world.time();
+        let spawns = world.spawns();
+        let visible: Vec<String> = world.rooms().into_iter().map(|room| room.name).collect();
+
+        for room in visible.iter() {
+            match world.invader_cores(room).into_iter().next() {
+                Some(core) => self.found(world, room, core, &spawns),
+                None => self.cleared(world, room)
+            }
+        }
+
+        // strongholds collapse whether we see it or not
+        let mut memory = world.memory();
+        let collapsed: Vec<String> = memory.military.invaders.iter()
+            .filter(|&(room, invader)| !visible.contains(room) && invader.avoid_until.map_or(false, |until| time >= until))
+            .map(|(room, _)| room.clone())
+            .collect();
+        for room in collapsed {
+            info!("stronghold in {} has collapsed", room);
+            memory.military.invaders.remove(&room);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::mock::MockWorld;
+
+    #[test]
+    fn room_coords_around_the_center() {
+        let cases = [
+            ("E0S0", Some((0, 0))),
+            ("E0N0", Some((0, -1))),
+            ("W0S0", Some((-1, 0))),
+            ("W0N0", Some((-1, -1))),
+            ("E12S3", Some((12, 3))),
+            ("W12N3", Some((-13, -4))),
+            ("sim", None),
+            ("E1", None),
+            ("X1N1", None),
+            ("E1NN", None)
+        ];
+        for &(name, coords) in cases.iter() {
+            assert_eq!(room_coords(name), coords, "{}", name);
+        }
+    }
+
+    #[test]
+    fn room_distance_across_the_center() {
+        assert_eq!(room_distance("W1N1", "W1N1"), Some(0));
+        assert_eq!(room_distance("W0N0", "E0S0"), Some(1));
+        assert_eq!(room_distance("W0N5", "E0N5"), Some(1));
+        assert_eq!(room_distance("W2N1", "E1S3"), Some(5));
+        assert_eq!(room_distance("W1N1", "sim"), None);
+    }
+
+    #[test]
+    fn avoids_strongholds_until_collapse() {
+        let world = MockWorld::new();
+        {
+            let mut memory = world.memory();
+            memory.military.invaders.insert("W1N1".to_string(), InvaderMemory{
+                core: Some("core".to_string()),
+                level: 2,
+                avoid_until: Some(100),
+                ..InvaderMemory::default()
+            });
+            memory.military.invaders.insert("W2N1".to_string(), InvaderMemory{
+                core: Some("core".to_string()),
+                level: 0,
+                ..InvaderMemory::default()
+            });
+        }
+
+        assert!(is_avoided(&world, "W1N1"));
+        // level 0 cores don't fight back
+        assert!(!is_avoided(&world, "W2N1"));
+        assert!(!is_avoided(&world, "W3N1"));
+
+        world.set_time(100);
+        assert!(!is_avoided(&world, "W1N1"));
+    }
+}
@@ -1,2 +1,12 @@
+/// Damage and heal calculations.
+pub mod combat;
+/// Activating safe mode in rooms the towers can't hold.
+pub mod defense;
+/// Clearing NPC invader cores, and avoiding strongholds.
+pub mod invaders;
+/// Squads of creeps moving and fighting together.
+pub mod squad;
+/// Classifying hostiles, and how dangerous they are.
+pub mod threat;
 /// Handles all towers.
 pub mod tower;
\ No newline at end of file
@@ -0,0 +1,669 @@
+use std::{
+    cmp::{
+        self,
+        Reverse
+    },
+    error::Error
+};
+
+use hashbrown::HashMap;
+use screeps::constants::{
+    Part,
+    ReturnCode
+};
+
+use crate::{
+    body::BodyTemplate,
+    memory::{
+        Formation,
+        SquadMemory,
+        SquadState
+    },
+    military::{
+        combat,
+        invaders
+    },
+    roles::actions::intent,
+    world::{
+        self,
+        CreepIntent,
+        CreepState,
+        ObjectId,
+        Position,
+        SpawnState,
+        StructureIntent,
+        StructureKind,
+        World
+    }
+};
+
+/// The first word of a squad's flag: `squad <duo|quad> <name> [home room]`.
+const FLAG_PREFIX: &str = "squad";
+/// The role squad members are spawned with.
+pub const ROLE: &str = "squad";
+/// A squad falls back when its members are below this share of their hits, in percent.
+const RETREAT_BELOW: u32 = 50;
+/// And goes back in once they're healed above this.
+const REGROUP_ABOVE: u32 = 90;
+/// Where the members of a quad stand, relative to the first one.
+const QUAD_SLOTS: [(i32, i32); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+/// What a member is spawned to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    Attacker,
+    Ranged,
+    Healer
+}
+
+impl Slot {
+    /// Guessed from the body, so members keep their slot when damaged.
+    pub fn of(creep: &CreepState) -> Option<Slot> {
+        let has = |part: Part| creep.body.iter().any(|body_part| body_part.part == part);
+        if has(Part::Heal) {
+            Some(Slot::Healer)
+        } else if has(Part::Attack) {
+            Some(Slot::Attacker)
+        } else if has(Part::RangedAttack) {
+            Some(Slot::Ranged)
+        } else {
+            None
+        }
+    }
+
+    pub fn body(&self) -> BodyTemplate {
+        let part = match *self {
+            Slot::Attacker => Part::Attack,
+            Slot::Ranged => Part::RangedAttack,
+            Slot::Healer => Part::Heal
+        };
+        BodyTemplate::new(&[(part, 1), (Part::Move, 1)])
+            .size(1, 25)
+    }
+}
+
+pub fn slots(formation: Formation) -> &'static [Slot] {
+    match formation {
+        Formation::Duo => &[Slot::Attacker, Slot::Healer],
+        Formation::Quad => &[Slot::Ranged, Slot::Ranged, Slot::Healer, Slot::Healer]
+    }
+}
+
+/// Slots of the squad that no living member fills.
+fn open_slots(squad: &SquadMemory, creeps: &[CreepState]) -> Vec<Slot> {
+    let mut open = slots(squad.formation).to_vec();
+    for creep in creeps.iter().filter(|creep| squad.members.contains(&creep.name)) {
+        if let Some(index) = Slot::of(creep).and_then(|slot| open.iter().position(|open| *open == slot)) {
+            open.remove(index);
+        }
+    }
+    open
+}
+
+/// The next member a forming squad from the room is waiting for.
+pub fn next_member(world: &dyn World, room: &str) -> Option<Slot> {
+    let creeps = world.my_creeps();
+    let memory = world.memory();
+    memory.military.squads.values()
+        .filter(|squad| squad.state == SquadState::Forming && squad.home == room)
+        .filter_map(|squad| open_slots(squad, &creeps).into_iter().next())
+        .next()
+}
+
+/// Lower is attacked first.
+fn structure_rank(kind: StructureKind) -> Option<u32> {
+    match kind {
+        StructureKind::Tower => Some(0),
+        StructureKind::Spawn => Some(1),
+        StructureKind::Road | StructureKind::Container | StructureKind::Wall | StructureKind::KeeperLair |
+        StructureKind::Portal | StructureKind::Controller | StructureKind::PowerBank | StructureKind::Other => None,
+        _ => Some(2)
+    }
+}
+
+/// Groups creeps into squads that move and fight as one.
+///
+/// A squad is created for every flag named `squad <duo|quad> <name> [home room]`,
+/// spawned in the home room, or the room of the first spawn, and sent to the flag.
+/// Moving the flag moves the squad, removing it sends the squad home to be recycled.
+/// So does a stronghold in the flag's room, see `invaders::is_avoided`, until it collapses.
+/// Squads are kept in `Memory.military.squads`, by the name of the flag.
+/// Squads with an objective have no flag, they are created and disbanded by `military::invaders::Invaders`.
+///
+/// Duos, and quads on their way, move in a column behind the first member, which waits for the others.
+/// In the target room, quads form a 2x2 block and step as one.
+/// All members shoot at the same target, the weakest hostile in reach, or the towers and spawns of the room.
+/// Healers spread their heal over the most damaged members, or heal the first member ahead of time.
+/// When the members are below `RETREAT_BELOW` percent of their hits, the squad falls back home until healed.
+pub struct Squads;
+
+impl Squads {
+    pub fn new() -> Squads {
+        Squads{}
+    }
+
+    /// Creates squads for new flags, and disbands the ones whose flag is gone.
+    fn update_flags(&self, world: &dyn World) {
+        let default_home = world.spawns().into_iter().next().map(|spawn| spawn.pos.room);
+        let flags: Vec<(String, Formation, Option<String>, Position)> = world.flags().into_iter()
+            .filter_map(|flag| {
+                let (formation, home) = {
+                    let mut words = flag.name.split_whitespace();
+                    if words.next() != Some(FLAG_PREFIX) {
+                        return None;
+                    }
+                    let formation = match words.next() {
+                        Some("duo") => Formation::Duo,
+                        Some("quad") => Formation::Quad,
+                        _ => return None
+                    };
+                    words.next();
+                    (formation, words.next().map(str::to_string))
+                };
+                if invaders::is_avoided(world, &flag.pos.room) {
+                    debug!("not sending squad {} into the stronghold in {}", flag.name, flag.pos.room);
+                    return None;
+                }
+                Some((flag.name, formation, home, flag.pos))
+            })
+            .collect();
+
+        let mut memory = world.memory();
+        let squads = &mut memory.military.squads;
+        for &(ref name, formation, ref home, ref pos) in flags.iter() {
+            let home = match home.clone().or_else(|| default_home.clone()) {
+                Some(home) => home,
+                None => continue
+            };
+            let squad = squads.entry(name.clone()).or_insert_with(|| {
+                info!("forming {:?} squad {} in {}", formation, name, home);
+                SquadMemory{
+                    formation: formation,
+                    home: home,
+                    ..SquadMemory::default()
+                }
+            });
+            squad.target = Some(pos.clone());
+        }
+
+        for (name, squad) in squads.iter_mut() {
+            if squad.objective.is_none() && squad.state != SquadState::Disbanding && flags.iter().all(|flag| flag.0 != *name) {
+                info!("disbanding squad {}", name);
+                squad.state = SquadState::Disbanding;
+            }
+        }
+    }
+
+    /// Forgets dead members, and adds new creeps to squads forming in their home room.
+    fn update_members(&self, world: &dyn World) {
+        let creeps = world.my_creeps();
+        let mut memory = world.memory();
+        let memory = &mut *memory;
+
+        for squad in memory.military.squads.values_mut() {
+            squad.members.retain(|name| creeps.iter().any(|creep| creep.name == *name));
+        }
+
+        for creep in creeps.iter() {
+            let home = match memory.creeps.get(&creep.name) {
+                Some(creep_memory) if creep_memory.role == ROLE => creep_memory.home.clone(),
+                _ => continue
+            };
+            if memory.military.squads.values().any(|squad| squad.members.contains(&creep.name)) {
+                continue;
+            }
+            let slot = match Slot::of(creep) {
+                Some(slot) => slot,
+                None => continue
+            };
+
+            let squad = memory.military.squads.iter_mut().find(|entry| {
+                let squad = &entry.1;
+                squad.state == SquadState::Forming && squad.home == home && open_slots(squad, &creeps).contains(&slot)
+            });
+            if let Some((name, squad)) = squad {
+                info!("{} joins squad {}", creep.name, name);
+                squad.members.push(creep.name.clone());
+            }
+        }
+
+        let gone: Vec<String> = memory.military.squads.iter()
+            .filter(|&(_, squad)| squad.members.is_empty() && squad.state != SquadState::Forming)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in gone {
+            if let Some(squad) = memory.military.squads.remove(&name) {
+                match squad.state {
+                    SquadState::Disbanding => info!("squad {} disbanded", name),
+                    _ => warn!("squad {} was wiped out", name)
+                }
+            }
+        }
+    }
+
+    fn update_state(&self, name: &str, squad: &mut SquadMemory, members: &[CreepState]) {
+        let hits: u32 = members.iter().map(|member| member.hits).sum();
+        let hits_max: u32 = members.iter().map(|member| member.hits_max).sum();
+        let health = hits * 100 / cmp::max(hits_max, 1);
+        let in_target_room = squad.target.as_ref().map_or(false, |target| target.room == members[0].pos.room);
+
+        let next = match squad.state {
+            SquadState::Forming if members.len() == slots(squad.formation).len() => SquadState::Travelling,
+            SquadState::Travelling | SquadState::Engaged if health < RETREAT_BELOW => SquadState::Retreating,
+            SquadState::Travelling if in_target_room => SquadState::Engaged,
+            SquadState::Engaged if !in_target_room => SquadState::Travelling,
+            SquadState::Retreating if health >= REGROUP_ABOVE => SquadState::Travelling,
+            state => state
+        };
+
+        if next != squad.state {
+            match next {
+                SquadState::Retreating => warn!("squad {} is retreating with {}% of its hits", name, health),
+                _ => info!("squad {} is {:?}", name, next)
+            }
+            squad.state = next;
+        }
+    }
+
+    /// What the whole squad shoots at: the weakest hostile in reach,
+    /// or the towers, spawns and other structures of the target room.
+    fn shared_target(&self, world: &dyn World, squad: &SquadMemory, members: &[CreepState],
+                     hostiles: &[CreepState]) -> Option<(ObjectId, Position)> {
+        let reach = |pos: &Position| members.iter().map(|member| member.pos.range_to(pos)).min().unwrap_or(u32::max_value());
+
+        if let Some(hostile) = hostiles.iter().min_by_key(|hostile| (reach(&hostile.pos) > 3, hostile.hits, reach(&hostile.pos))) {
+            return Some((hostile.id.clone(), hostile.pos.clone()));
+        }
+        if squad.state != SquadState::Engaged {
+            return None;
+        }
+
+        world.structures(&members[0].pos.room).into_iter()
+            .filter(|structure| !structure.my && structure.hits > 0)
+            .filter_map(|structure| structure_rank(structure.kind).map(|rank| (rank, structure)))
+            .min_by_key(|&(rank, ref structure)| (rank, reach(&structure.pos)))
+            .map(|(_, structure)| (structure.id, structure.pos))
+    }
+
+    /// Every fighter hits the shared target if it's in range, or any hostile that is.
+    fn attack(&self, world: &dyn World, members: &[CreepState], target: Option<&(ObjectId, Position)>,
+              hostiles: &[CreepState]) -> Result<(), Box<dyn Error>> {
+        for member in members.iter() {
+            let (range, attack): (u32, fn(ObjectId) -> CreepIntent) = if member.active_parts(Part::Attack) > 0 {
+                (1, CreepIntent::Attack)
+            } else if member.active_parts(Part::RangedAttack) > 0 {
+                (3, CreepIntent::RangedAttack)
+            } else {
+                continue;
+            };
+
+            let chosen = target.filter(|target| member.pos.range_to(&target.1) <= range).map(|target| target.0.clone())
+                .or_else(|| hostiles.iter().find(|hostile| member.pos.range_to(&hostile.pos) <= range).map(|hostile| hostile.id.clone()));
+            if let Some(id) = chosen {
+                intent(world, member, attack(id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Healers take the most damaged member that isn't covered by the others' heal yet.
+    /// Without damage, they heal the first member, which takes the hits first.
+    fn heal(&self, world: &dyn World, members: &[CreepState]) -> Result<(), Box<dyn Error>> {
+        let mut incoming: HashMap<String, u32> = HashMap::new();
+
+        for healer in members.iter().filter(|member| member.active_parts(Part::Heal) > 0) {
+            let patient = members.iter()
+                .filter(|member| member.pos.range_to(&healer.pos) <= 3)
+                .map(|member| (member, member.hits_max.saturating_sub(member.hits + incoming.get(&member.name).cloned().unwrap_or(0))))
+                .filter(|&(_, missing)| missing > 0)
+                .max_by_key(|&(member, missing)| (missing, Reverse(member.pos.range_to(&healer.pos))))
+                .map(|(member, _)| member)
+                .unwrap_or(&members[0]);
+
+            let range = healer.pos.range_to(&patient.pos);
+            let heal = match range {
+                0..=1 => CreepIntent::Heal(patient.id.clone()),
+                2..=3 => CreepIntent::RangedHeal(patient.id.clone()),
+                _ => continue
+            };
+            *incoming.entry(patient.name.clone()).or_insert(0) += combat::heal_power(healer, range);
+            intent(world, healer, heal)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the members in a line behind the first one, which waits for the others to catch up.
+    /// The first member never waits on an exit tile, so the others can follow it into the next room.
+    fn move_column(&self, world: &dyn World, members: &[CreepState], goal: &Position, range: u32) -> Result<(), Box<dyn Error>> {
+        let leader = &members[0];
+        let together = members.windows(2).all(|pair| pair[0].pos.range_to(&pair[1].pos) <= 1);
+        let ready = members.iter().all(|member| member.fatigue == 0);
+
+        let moving = leader.pos.is_exit() || (together && ready && leader.pos.range_to(goal) > range);
+        if moving {
+            intent(world, leader, CreepIntent::MoveTo(goal.clone()))?;
+        }
+        for pair in members.windows(2) {
+            if moving || pair[1].pos.range_to(&pair[0].pos) > 1 || pair[1].pos.is_exit() {
+                intent(world, &pair[1], CreepIntent::MoveTo(pair[0].pos.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the members into a 2x2 block, then steps the whole block towards the goal.
+    /// Falls back to a column where the block doesn't fit.
+    fn move_quad(&self, world: &dyn World, members: &[CreepState], goal: &Position, range: u32) -> Result<(), Box<dyn Error>> {
+        let leader = &members[0];
+        let room = leader.pos.room.clone();
+        let terrain = world.terrain(&room);
+        let blocked: Vec<Position> = world.structures(&room).into_iter()
+            .filter(|structure| !structure.kind.is_walkable() || (structure.kind == StructureKind::Rampart && !structure.my))
+            .map(|structure| structure.pos)
+            .collect();
+        let clear = |x: i32, y: i32| QUAD_SLOTS.iter().all(|&(dx, dy)| {
+            let (x, y) = (x + dx, y + dy);
+            x >= 1 && y >= 1 && x <= 48 && y <= 48 && !terrain.is_wall(x, y) &&
+                !blocked.contains(&Position::new(x as u32, y as u32, &room))
+        });
+        let slot = |anchor: (i32, i32), index: usize| {
+            Position::new((anchor.0 + QUAD_SLOTS[index].0) as u32, (anchor.1 + QUAD_SLOTS[index].1) as u32, &room)
+        };
+
+        let (x, y) = (leader.pos.x as i32, leader.pos.y as i32);
+        let anchor = match [(x, y), (x - 1, y), (x, y - 1), (x - 1, y - 1)].iter().cloned().find(|&(x, y)| clear(x, y)) {
+            Some(anchor) => anchor,
+            None => return self.move_column(world, members, goal, range)
+        };
+
+        if members.iter().enumerate().any(|(index, member)| member.pos != slot(anchor, index)) {
+            for (index, member) in members.iter().enumerate() {
+                if member.pos != slot(anchor, index) {
+                    intent(world, member, CreepIntent::MoveTo(slot(anchor, index)))?;
+                }
+            }
+            return Ok(());
+        }
+        if members.iter().any(|member| member.fatigue > 0 || member.pos.range_to(goal) <= range) {
+            return Ok(());
+        }
+
+        let distance = |anchor: (i32, i32)| (0..QUAD_SLOTS.len()).map(|index| slot(anchor, index).range_to(goal)).min().unwrap_or(u32::max_value());
+        let current = distance(anchor);
+        let step = world::DIRECTIONS.iter().cloned()
+            .map(|direction| {
+                let (dx, dy) = world::direction_offset(direction);
+                (direction, (anchor.0 + dx, anchor.1 + dy))
+            })
+            .filter(|&(_, next)| clear(next.0, next.1) && distance(next) < current)
+            .min_by_key(|&(_, next)| distance(next));
+
+        match step {
+            Some((direction, _)) => {
+                for member in members.iter() {
+                    intent(world, member, CreepIntent::Move(direction))?;
+                }
+                Ok(())
+            },
+            None => self.move_column(world, members, goal, range)
+        }
+    }
+
+    /// Recycles the members next to the spawn.
+    fn recycle(&self, world: &dyn World, members: &[CreepState], spawn: &SpawnState) {
+        for member in members.iter().filter(|member| member.pos.range_to(&spawn.pos) <= 1) {
+            let code = world.structure_intent(&spawn.id, StructureIntent::RecycleCreep(member.id.clone()));
+            if code != ReturnCode::Ok {
+                warn!("failed to recycle {} in {}: {:?}", member.name, spawn.name, code);
+            }
+        }
+    }
+
+    fn run_squad(&self, world: &dyn World, name: &str, squad: &mut SquadMemory) -> Result<(), Box<dyn Error>> {
+        let mut members: Vec<CreepState> = squad.members.iter()
+            .filter_map(|member| world.creep(member))
+            .filter(|member| !member.spawning)
+            .collect();
+        if members.is_empty() {
+            return Ok(());
+        }
+        // fighters in front, healers behind them
+        members.sort_by_key(|member| (Slot::of(member) == Some(Slot::Healer), member.name.clone()));
+
+        self.update_state(name, squad, &members);
+
+        let spawn = world.spawns().into_iter().find(|spawn| spawn.pos.room == squad.home);
+        let rally = spawn.as_ref().map(|spawn| spawn.pos.clone()).unwrap_or_else(|| members[0].pos.clone());
+        let hostiles = world.hostile_creeps(&members[0].pos.room);
+        let target = self.shared_target(world, squad, &members, &hostiles);
+
+        self.heal(world, &members)?;
+        self.attack(world, &members, target.as_ref(), &hostiles)?;
+
+        match squad.state {
+            SquadState::Forming => {
+                for member in members.iter().filter(|member| member.pos.range_to(&rally) > 3) {
+                    intent(world, member, CreepIntent::MoveTo(rally.clone()))?;
+                }
+                Ok(())
+            },
+            SquadState::Travelling => {
+                let goal = squad.target.clone().unwrap_or(rally);
+                self.move_column(world, &members, &goal, 1)
+            },
+            SquadState::Engaged => {
+                let range = if members[0].active_parts(Part::Attack) > 0 { 1 } else { 3 };
+                let goal = target.map(|(_, pos)| pos).or_else(|| squad.target.clone()).unwrap_or(rally);
+                match squad.formation {
+                    Formation::Duo => self.move_column(world, &members, &goal, range),
+                    Formation::Quad => self.move_quad(world, &members, &goal, range)
+                }
+            },
+            SquadState::Retreating => self.move_column(world, &members, &rally, 3),
+            SquadState::Disbanding => {
+                if let Some(ref spawn) = spawn {
+                    self.recycle(world, &members, spawn);
+                }
+                self.move_column(world, &members, &rally, 1)
+            }
+        }
+    }
+
+    pub fn run(&self, world: &dyn World) -> Result<(), Box<dyn Error>> {
+        self.update_flags(world);
+        self.update_members(world);
+
+        let squads: Vec<(String, SquadMemory)> = world.memory().military.squads.iter()
+            .map(|(name, squad)| (name.clone(), squad.clone()))
+            .collect();
+        let mut errors = Vec::new();
+        for (name, mut squad) in squads {
+            if let Err(err) = self.run_squad(world, &name, &mut squad) {
+                errors.push(format!("squad {}: {}", name, err));
+            }
+            world.memory().military.squads.insert(name, squad);
+        }
+
+        match errors.len() {
+            0 => Ok(()),
+            _ => Err(Box::from(errors.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        RoomTerrain
+    };
+
+    fn member(name: &str, x: u32, y: u32, body: &[Part]) -> CreepState {
+        mock::creep(name, Position::new(x, y, "W1N1"), body)
+    }
+
+    fn damaged(mut creep: CreepState, missing: u32) -> CreepState {
+        creep.hits -= missing;
+        creep
+    }
+
+    fn squad(formation: Formation, state: SquadState, target_room: &str) -> SquadMemory {
+        SquadMemory{
+            formation: formation,
+            state: state,
+            home: "W1N1".to_string(),
+            target: Some(Position::new(25, 25, target_room)),
+            ..SquadMemory::default()
+        }
+    }
+
+    /// The state after one update of a duo in W1N1, at the given percent of its hits.
+    fn next_state(state: SquadState, target_room: &str, health: u32) -> SquadState {
+        let missing = 200 - health * 2;
+        let members = [
+            damaged(member("attacker", 25, 25, &[Part::Attack, Part::Move]), missing),
+            damaged(member("healer", 25, 26, &[Part::Heal, Part::Move]), missing)
+        ];
+        let mut squad = squad(Formation::Duo, state, target_room);
+
+        Squads::new().update_state("test", &mut squad, &members);
+        squad.state
+    }
+
+    #[test]
+    fn retreats_and_regroups() {
+        assert_eq!(next_state(SquadState::Engaged, "W1N1", 50), SquadState::Engaged);
+        assert_eq!(next_state(SquadState::Engaged, "W1N1", 49), SquadState::Retreating);
+        assert_eq!(next_state(SquadState::Travelling, "W2N1", 49), SquadState::Retreating);
+        assert_eq!(next_state(SquadState::Retreating, "W1N1", 89), SquadState::Retreating);
+        assert_eq!(next_state(SquadState::Retreating, "W1N1", 90), SquadState::Travelling);
+    }
+
+    #[test]
+    fn engages_in_target_room() {
+        assert_eq!(next_state(SquadState::Forming, "W2N1", 100), SquadState::Travelling);
+        assert_eq!(next_state(SquadState::Travelling, "W2N1", 100), SquadState::Travelling);
+        assert_eq!(next_state(SquadState::Travelling, "W1N1", 100), SquadState::Engaged);
+        assert_eq!(next_state(SquadState::Engaged, "W2N1", 100), SquadState::Travelling);
+    }
+
+    #[test]
+    fn waits_for_members_to_form() {
+        let mut squad = squad(Formation::Quad, SquadState::Forming, "W2N1");
+        let members = [member("ranged", 25, 25, &[Part::RangedAttack, Part::Move])];
+        Squads::new().update_state("test", &mut squad, &members);
+
+        assert_eq!(squad.state, SquadState::Forming);
+    }
+
+    fn heals(world: &MockWorld) -> Vec<(String, CreepIntent)> {
+        world.creep_intents().into_iter().filter(|(_, intent)| match *intent {
+            CreepIntent::Heal(_) | CreepIntent::RangedHeal(_) => true,
+            _ => false
+        }).collect()
+    }
+
+    #[test]
+    fn spreads_heal_over_damaged_members() {
+        let world = MockWorld::new();
+        let members = vec![
+            damaged(member("ranged1", 25, 25, &[Part::RangedAttack, Part::Move]), 100),
+            damaged(member("ranged2", 26, 25, &[Part::RangedAttack, Part::Move]), 90),
+            member("healer1", 25, 26, &[Part::Heal, Part::Heal, Part::Move]),
+            member("healer2", 26, 26, &[Part::Heal, Part::Heal, Part::Move])
+        ];
+        for member in members.iter() {
+            world.add_creep(member.clone());
+        }
+        Squads::new().heal(&world, &members).unwrap();
+
+        // the first heal leaves ranged1 missing 76, less than ranged2
+        assert_eq!(heals(&world), vec![
+            ("healer1".to_string(), CreepIntent::Heal("ranged1".to_string())),
+            ("healer2".to_string(), CreepIntent::Heal("ranged2".to_string()))
+        ]);
+    }
+
+    #[test]
+    fn heals_leader_without_damage() {
+        let world = MockWorld::new();
+        let members = vec![
+            member("attacker", 25, 25, &[Part::Attack, Part::Move]),
+            member("healer", 25, 27, &[Part::Heal, Part::Move])
+        ];
+        for member in members.iter() {
+            world.add_creep(member.clone());
+        }
+        Squads::new().heal(&world, &members).unwrap();
+
+        assert_eq!(heals(&world), vec![("healer".to_string(), CreepIntent::RangedHeal("attacker".to_string()))]);
+    }
+
+    fn quad(world: &MockWorld, tiles: &[(u32, u32)]) -> Vec<CreepState> {
+        let bodies: [&[Part]; 4] = [
+            &[Part::RangedAttack, Part::Move], &[Part::RangedAttack, Part::Move],
+            &[Part::Heal, Part::Move], &[Part::Heal, Part::Move]
+        ];
+        tiles.iter().zip(bodies.iter()).enumerate().map(|(index, (&(x, y), body))| {
+            let member = member(&format!("member{}", index), x, y, body);
+            world.add_creep(member.clone());
+            member
+        }).collect()
+    }
+
+    fn moves(world: &MockWorld) -> Vec<(String, CreepIntent)> {
+        world.creep_intents().into_iter().filter(|(_, intent)| match *intent {
+            CreepIntent::Move(_) | CreepIntent::MoveTo(_) => true,
+            _ => false
+        }).collect()
+    }
+
+    #[test]
+    fn quad_forms_block() {
+        let world = MockWorld::new();
+        let members = quad(&world, &[(25, 25), (26, 25), (25, 27), (24, 27)]);
+        Squads::new().move_quad(&world, &members, &Position::new(25, 10, "W1N1"), 3).unwrap();
+
+        assert_eq!(moves(&world), vec![
+            ("member2".to_string(), CreepIntent::MoveTo(Position::new(25, 26, "W1N1"))),
+            ("member3".to_string(), CreepIntent::MoveTo(Position::new(26, 26, "W1N1")))
+        ]);
+    }
+
+    #[test]
+    fn quad_steps_as_one() {
+        let world = MockWorld::new();
+        let members = quad(&world, &[(25, 25), (26, 25), (25, 26), (26, 26)]);
+        Squads::new().move_quad(&world, &members, &Position::new(25, 10, "W1N1"), 3).unwrap();
+
+        let moves = moves(&world);
+        assert_eq!(moves.len(), 4);
+        match moves[0].1 {
+            CreepIntent::Move(_) => (),
+            ref intent => panic!("{:?} isn't a step", intent)
+        }
+        assert!(moves.iter().all(|(_, intent)| *intent == moves[0].1));
+    }
+
+    #[test]
+    fn quad_falls_back_to_column_next_to_walls() {
+        let world = MockWorld::new();
+        // a corridor one tile wide along x = 25
+        let rows: Vec<String> = (0..50).map(|_| format!("{}x x{}", " ".repeat(24), " ".repeat(23))).collect();
+        world.set_terrain("W1N1", RoomTerrain::parse(&rows));
+        let members = quad(&world, &[(25, 25), (25, 26), (25, 27), (25, 28)]);
+        let goal = Position::new(25, 10, "W1N1");
+        Squads::new().move_quad(&world, &members, &goal, 3).unwrap();
+
+        assert_eq!(moves(&world), vec![
+            ("member0".to_string(), CreepIntent::MoveTo(goal.clone())),
+            ("member1".to_string(), CreepIntent::MoveTo(Position::new(25, 25, "W1N1"))),
+            ("member2".to_string(), CreepIntent::MoveTo(Position::new(25, 26, "W1N1"))),
+            ("member3".to_string(), CreepIntent::MoveTo(Position::new(25, 27, "W1N1")))
+        ]);
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    cell::RefCell,
+    cmp
+};
+
+use hashbrown::HashMap;
+use screeps::constants::Part;
+
+use crate::{
+    memory::{
+        PlayerMemory,
+        ThreatMemory
+    },
+    military::combat::{
+        self,
+        ATTACK_POWER,
+        DISMANTLE_POWER,
+        HEAL_POWER,
+        RANGED_ATTACK_POWER
+    },
+    world::{
+        CreepState,
+        World
+    }
+};
+
+/// Owners of the NPC creeps.
+const INVADER: &str = "Invader";
+const SOURCE_KEEPER: &str = "Source Keeper";
+/// Summaries of rooms we can't see are dropped this long after hostiles were last seen there.
+const THREAT_EXPIRY: u32 = 1500;
+/// Rooms remembered for each player, the most recent ones.
+const PLAYER_ROOMS: usize = 10;
+
+/// What a hostile creep is there for, guessed from its owner and body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HostileKind {
+    /// Only MOVE parts, or nothing that can hurt.
+    Scout,
+    /// NPC invaders, that attack anything in rooms they spawn in.
+    Invader,
+    SourceKeeper,
+    /// WORK and CARRY, after our sources.
+    Harvester,
+    /// WORK without CARRY, after our structures.
+    Dismantler,
+    /// HEAL, but nothing to attack with.
+    Healer,
+    /// ATTACK or RANGED_ATTACK.
+    Attacker
+}
+
+impl HostileKind {
+    /// Whether it can't hurt our creeps or structures.
+    pub fn is_harmless(&self) -> bool {
+        match *self {
+            HostileKind::Scout | HostileKind::Harvester => true,
+            _ => false
+        }
+    }
+}
+
+/// A hostile creep, and how dangerous it is.
+#[derive(Clone, Debug)]
+pub struct Hostile {
+    pub creep: CreepState,
+    pub kind: HostileKind,
+    pub boosted: bool,
+    /// Damage and dismantling it can do per tick.
+    pub damage: u32,
+    pub heal: u32,
+    /// Damage and heal together.
+    pub threat: u32
+}
+
+/// The hostiles in a room this tick.
+#[derive(Clone, Debug, Default)]
+pub struct RoomThreat {
+    pub hostiles: Vec<Hostile>,
+    /// The sum of the hostiles' threat.
+    pub score: u32,
+    /// Players that own the hostiles, without the NPCs.
+    pub owners: Vec<String>
+}
+
+impl RoomThreat {
+    pub fn hostile(&self, id: &str) -> Option<&Hostile> {
+        self.hostiles.iter().find(|hostile| hostile.creep.id == id)
+    }
+
+    /// Whether a player, rather than NPCs, is attacking.
+    pub fn is_player_attack(&self) -> bool {
+        self.hostiles.iter().any(|hostile| !hostile.kind.is_harmless() && self.owners.contains(&hostile.creep.owner))
+    }
+}
+
+/// What the creep's active parts of a type do per tick, boosts included.
+fn power(creep: &CreepState, part: Part, per_part: u32) -> u32 {
+    creep.body.iter()
+        .filter(|body_part| body_part.part == part && body_part.hits > 0)
+        .map(|body_part| per_part * combat::boost_multiplier(part, body_part.boost.as_ref().map(String::as_str)))
+        .sum()
+}
+
+/// Damage and heal an unboosted creep with this body does per tick, to compare our creeps with hostiles.
+pub fn body_threat(body: &[Part]) -> u32 {
+    body.iter().map(|part| match *part {
+        Part::Attack => ATTACK_POWER,
+        Part::RangedAttack => RANGED_ATTACK_POWER,
+        Part::Heal => HEAL_POWER,
+        _ => 0
+    }).sum()
+}
+
+pub fn classify(creep: &CreepState) -> HostileKind {
+    let has = |part: Part| creep.active_parts(part) > 0;
+
+    if creep.owner == INVADER {
+        HostileKind::Invader
+    } else if creep.owner == SOURCE_KEEPER {
+        HostileKind::SourceKeeper
+    } else if has(Part::Attack) || has(Part::RangedAttack) {
+        HostileKind::Attacker
+    } else if has(Part::Heal) {
+        HostileKind::Healer
+    } else if has(Part::Work) && !has(Part::Carry) {
+        HostileKind::Dismantler
+    } else if has(Part::Work) || has(Part::Carry) || has(Part::Claim) {
+        HostileKind::Harvester
+    } else {
+        HostileKind::Scout
+    }
+}
+
+pub fn assess(creep: &CreepState) -> Hostile {
+    let kind = classify(creep);
+    let dismantle = if kind == HostileKind::Dismantler { power(creep, Part::Work, DISMANTLE_POWER) } else { 0 };
+    let damage = power(creep, Part::Attack, ATTACK_POWER) + power(creep, Part::RangedAttack, RANGED_ATTACK_POWER) + dismantle;
+    let heal = power(creep, Part::Heal, HEAL_POWER);
+
+    Hostile{
+        creep: creep.clone(),
+        kind: kind,
+        boosted: creep.body.iter().any(|part| part.boost.is_some()),
+        damage: damage,
+        heal: heal,
+        threat: damage + heal
+    }
+}
+
+/// Classifies the hostiles in every room we see, once per tick.
+///
+/// The result is shared with anything that reacts to hostiles, like towers and spawning.
+/// A summary of each room with hostiles is kept in `Memory.military.threats.<room>`,
+/// and the players we've seen in `Memory.military.players.<name>`.  
+/// Summaries of rooms we lost vision of expire after `THREAT_EXPIRY` ticks.
+pub struct ThreatAssessment {
+    rooms: RefCell<HashMap<String, RoomThreat>>
+}
+
+impl ThreatAssessment {
+    pub fn new() -> ThreatAssessment {
+        ThreatAssessment{
+            rooms: RefCell::new(HashMap::new())
+        }
+    }
+
+    /// Hostiles in the room in the current tick, empty if there aren't any or we can't see it.
+    pub fn room(&self, name: &str) -> RoomThreat {
+        self.rooms.borrow().get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn update(&self, world: &dyn World) {
+        let time = world.time();
+        let mut rooms = self.rooms.borrow_mut();
+        rooms.clear();
+
+        for room in world.rooms() {
+            let hostiles: Vec<Hostile> = world.hostile_creeps(&room.name).iter().map(assess).collect();
+            if hostiles.is_empty() {
+                world.memory().military.threats.remove(&room.name);
+                continue;
+            }
+
+            let mut owners: Vec<String> = hostiles.iter()
+                .map(|hostile| hostile.creep.owner.clone())
+                .filter(|owner| owner != INVADER && owner != SOURCE_KEEPER)
+                .collect();
+            owners.sort();
+            owners.dedup();
+
+            let threat = RoomThreat{
+                score: hostiles.iter().map(|hostile| hostile.threat).sum(),
+                hostiles: hostiles,
+                owners: owners
+            };
+
+            {
+                let mut memory = world.memory();
+                let summary = memory.military.threats.entry(room.name.clone()).or_insert_with(|| ThreatMemory{
+                    since: time,
+                    ..ThreatMemory::default()
+                });
+                summary.score = threat.score;
+                summary.owners = threat.owners.clone();
+                summary.last_seen = time;
+
+                for owner in threat.owners.iter() {
+                    let score: u32 = threat.hostiles.iter().filter(|hostile| hostile.creep.owner == *owner).map(|hostile| hostile.threat).sum();
+                    let player = memory.military.players.entry(owner.clone()).or_insert_with(|| PlayerMemory{
+                        first_seen: time,
+                        ..PlayerMemory::default()
+                    });
+                    player.last_seen = time;
+                    player.max_threat = cmp::max(player.max_threat, score);
+                    player.rooms.retain(|seen| *seen != room.name);
+                    player.rooms.push(room.name.clone());
+                    if player.rooms.len() > PLAYER_ROOMS {
+                        let excess = player.rooms.len() - PLAYER_ROOMS;
+                        player.rooms.drain(..excess);
+                    }
+                }
+            }
+
+            if threat.score > 0 {
+                debug!("threat {} in {} from {:?}", threat.score, room.name, threat.owners);
+            }
+            rooms.insert(room.name, threat);
+        }
+
+        let mut memory = world.memory();
+        let expired: Vec<String> = memory.military.threats.iter()
+            .filter(|(_, summary)| time.saturating_sub(summary.last_seen) >= THREAT_EXPIRY)
+            .map(|(room, _)| room.clone())
+            .collect();
+        for room in expired {
+            debug!("forgetting the hostiles in {}, not seen since {}", room, memory.military.threats[&room].last_seen);
+            memory.military.threats.remove(&room);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::ThreatMemory,
+        world::{
+            mock::{
+                self,
+                MockWorld
+            },
+            Position,
+            RoomState
+        }
+    };
+
+    fn hostile(owner: &str, body: &[Part]) -> CreepState {
+        let mut creep = mock::creep("hostile", Position::new(25, 25, "W1N1"), body);
+        creep.owner = owner.to_string();
+        creep.my = false;
+        creep
+    }
+
+    fn boosted(mut creep: CreepState, part: Part, boost: &str) -> CreepState {
+        for body_part in creep.body.iter_mut().filter(|body_part| body_part.part == part) {
+            body_part.boost = Some(boost.to_string());
+        }
+        creep
+    }
+
+    #[test]
+    fn classifies_hostiles() {
+        let mut broken = hostile("player", &[Part::Attack, Part::Work, Part::Carry]);
+        broken.body[0].hits = 0;
+
+        let cases = [
+            // owners go before bodies
+            (hostile(INVADER, &[Part::Move]), HostileKind::Invader),
+            (hostile(SOURCE_KEEPER, &[Part::Attack]), HostileKind::SourceKeeper),
+            // attacking goes before healing
+            (hostile("player", &[Part::Heal, Part::RangedAttack]), HostileKind::Attacker),
+            (hostile("player", &[Part::Heal, Part::Work]), HostileKind::Healer),
+            (hostile("player", &[Part::Work, Part::Move]), HostileKind::Dismantler),
+            (hostile("player", &[Part::Work, Part::Carry]), HostileKind::Harvester),
+            (hostile("player", &[Part::Claim, Part::Move]), HostileKind::Harvester),
+            (hostile("player", &[Part::Tough, Part::Move]), HostileKind::Scout),
+            // broken parts don't count
+            (broken, HostileKind::Harvester)
+        ];
+
+        for (creep, kind) in cases.iter() {
+            assert_eq!(classify(creep), *kind, "{:?}", creep.body);
+        }
+    }
+
+    #[test]
+    fn assesses_damage_and_heal() {
+        let hostile = assess(&hostile("player", &[Part::Attack, Part::RangedAttack, Part::Heal]));
+        assert_eq!((hostile.damage, hostile.heal, hostile.threat), (40, 12, 52));
+        assert!(!hostile.boosted);
+
+        let hostile = assess(&boosted(hostile.creep, Part::Attack, "XUH2O"));
+        assert_eq!(hostile.damage, 130);
+        assert!(hostile.boosted);
+    }
+
+    #[test]
+    fn assesses_dismantlers_only() {
+        let dismantler = assess(&boosted(hostile("player", &[Part::Work, Part::Work]), Part::Work, "ZH"));
+        assert_eq!(dismantler.damage, 200);
+
+        let harvester = assess(&hostile("player", &[Part::Work, Part::Carry]));
+        assert_eq!(harvester.damage, 0);
+    }
+
+    fn world_with_room() -> MockWorld {
+        let world = MockWorld::new();
+        world.add_room(RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: None
+        });
+        world
+    }
+
+    #[test]
+    fn summarizes_rooms() {
+        let world = world_with_room();
+        world.add_hostile(hostile("player", &[Part::Attack]));
+        world.add_hostile(hostile(INVADER, &[Part::RangedAttack]));
+        let threats = ThreatAssessment::new();
+        threats.update(&world);
+
+        let threat = threats.room("W1N1");
+        assert_eq!(threat.score, 40);
+        assert_eq!(threat.owners, vec!["player".to_string()]);
+        assert!(threat.is_player_attack());
+
+        let memory = world.memory();
+        assert_eq!(memory.military.threats["W1N1"].score, 40);
+        assert_eq!(memory.military.players["player"].max_threat, 30);
+        assert_eq!(memory.military.players["player"].rooms, vec!["W1N1".to_string()]);
+    }
+
+    #[test]
+    fn expires_rooms_out_of_sight() {
+        let world = world_with_room();
+        world.memory().military.threats.insert("W2N2".to_string(), ThreatMemory{
+            last_seen: 1,
+            ..ThreatMemory::default()
+        });
+        let threats = ThreatAssessment::new();
+
+        world.set_time(THREAT_EXPIRY);
+        threats.update(&world);
+        assert!(world.memory().military.threats.contains_key("W2N2"));
+
+        world.set_time(1 + THREAT_EXPIRY);
+        threats.update(&world);
+        assert!(!world.memory().military.threats.contains_key("W2N2"));
+    }
+
+    #[test]
+    fn keeps_recent_player_rooms() {
+        let world = world_with_room();
+        world.add_hostile(hostile("player", &[Part::Attack]));
+        {
+            let mut memory = world.memory();
+            let player = memory.military.players.entry("player".to_string()).or_insert_with(PlayerMemory::default);
+            // already full, with the room to be seen again first
+            player.rooms = Some("W1N1".to_string()).into_iter()
+                .chain((0..PLAYER_ROOMS).map(|i| format!("W{}N1", i + 10)))
+                .collect();
+        }
+        ThreatAssessment::new().update(&world);
+
+        let rooms = world.memory().military.players["player"].rooms.clone();
+        assert_eq!(rooms.len(), PLAYER_ROOMS);
+        assert_eq!(rooms.last().unwrap(), "W1N1");
+        assert_eq!(rooms.first().unwrap(), "W11N1");
+        assert_eq!(rooms.iter().filter(|room| *room == "W1N1").count(), 1);
+    }
+}
@@ -1,90 +1,339 @@
-use std::error::Error;
-
-use screeps::{
-    constants::find,
-    memory::MemoryReference,
-    objects::{
-        Attackable,
-        Creep,
-        Structure,
-        StructureTower
+use std::{
+    cmp::{
+        self,
+        Reverse
     },
-    prelude::*,
-    game
+    error::Error,
+    rc::Rc
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    memory::TowerMemory,
+    military::{
+        combat,
+        threat::ThreatAssessment
+    },
+    planner::ramparts,
+    reservations::{
+        ReservationKind,
+        Reservations
+    },
+    world::{
+        CreepState,
+        StructureIntent,
+        StructureKind,
+        StructureState,
+        World
+    }
 };
 
 /// Handles all towers.
-/// Each added tower has a separate target stored in memory.
-/// Add a new tower by creating a dict inside `memory`, with the tower's id as its name.
+/// Our towers are found in every room we own each tick, and kept in `Memory.military.tower.towers` by id.  
+/// Entries of towers that are gone are removed.
+/// 
+/// All towers of a room fire at the same target, the one they kill the soonest despite its healers.
+/// Hostiles that can't hurt us, like scouts and harvesters, are only shot when there's nothing else.
+/// If they can't out-damage the heal on any target, they hold fire instead of wasting energy.  
+/// A tower that fires doesn't repair in the same tick.
+/// 
+/// Towers that don't fire heal our most damaged creep in the room, before repairing anything.
+/// Heal is reserved like repairs, so towers don't all heal the same creep.
+/// 
+/// Repairs are reserved, so towers spread out over the damaged structures. In order:
+///   * spawns and towers
+///   * ramparts about to fall, below a tenth of their target hits
+///   * roads and containers that decayed below half, they're left alone until then
+///   * everything else
+///   * walls and ramparts, the weakest first, up to `ramparts::target_hits` for the room's level
+/// 
+/// Towers don't repair while there are hostiles in the room,
+/// nor below `Memory.military.tower.energy_reserve`, which is kept for defense.
 pub struct Tower {
-    memory: MemoryReference
+    reservations: Rc<Reservations>,
+    threats: Rc<ThreatAssessment>
 }
 
+/// Roads and containers are repaired below this fraction of their hits, in percent.
+const DECAY_REPAIR_BELOW: u32 = 50;
+/// Ramparts are about to fall below this fraction of their target hits, in percent.
+const RAMPART_CRITICAL_BELOW: u32 = 10;
+
 impl Tower {
-    pub fn new(memory: MemoryReference) -> Tower {
+    pub fn new(reservations: Rc<Reservations>, threats: Rc<ThreatAssessment>) -> Tower {
         Tower{
-            memory: memory
+            reservations: reservations,
+            threats: threats
+        }
+    }
+
+    /// Damage left on the structure that no other tower is repairing.
+    fn unreserved(&self, structure: &StructureState, level: u32) -> u32 {
+        let hits_max = match structure.kind {
+            StructureKind::Wall | StructureKind::Rampart => cmp::min(structure.hits_max, ramparts::target_hits(level)),
+            _ => structure.hits_max
+        };
+
+        hits_max.saturating_sub(structure.hits)
+                .saturating_sub(self.reservations.amount(&structure.id, ReservationKind::Work))
+    }
+
+    /// Lower is more urgent, None if the structure doesn't need repairs.
+    fn repair_priority(&self, structure: &StructureState, level: u32) -> Option<u32> {
+        if self.unreserved(structure, level) == 0 {
+            return None;
+        }
+
+        let percent = structure.hits * 100 / cmp::max(structure.hits_max, 1);
+        match structure.kind {
+            StructureKind::Spawn | StructureKind::Tower => Some(0),
+            StructureKind::Rampart if structure.hits * 100 < ramparts::target_hits(level) * RAMPART_CRITICAL_BELOW => Some(1),
+            StructureKind::Road | StructureKind::Container if percent < DECAY_REPAIR_BELOW => Some(2),
+            StructureKind::Road | StructureKind::Container => None,
+            StructureKind::Wall | StructureKind::Rampart => Some(4),
+            _ => Some(3)
         }
     }
 
-    fn get_target(&self, memory: &MemoryReference) -> Option<Creep> {
-        memory.string("target").unwrap_or(None)
-            .and_then( |target_id| game::get_object_typed(&target_id).unwrap_or(None) )
+    /// The hostile the towers kill in the fewest ticks, counting falloff, boosted TOUGH parts and heal.
+    fn focus_target(&self, room: &str, towers: &[&StructureState]) -> Option<CreepState> {
+        let threat = self.threats.room(room);
+        let hostiles: Vec<CreepState> = threat.hostiles.iter().map(|hostile| hostile.creep.clone()).collect();
+
+        let best = hostiles.iter()
+            .filter_map(|target| {
+                let net = combat::tower_net_damage(target, towers, &hostiles);
+                if net > 0 { Some((target, net as u32)) } else { None }
+            })
+            .min_by_key(|&(target, net)| {
+                let harmless = threat.hostile(&target.id).map_or(false, |hostile| hostile.kind.is_harmless());
+                (harmless, (target.hits + net - 1) / net, Reverse(net))
+            });
+
+        if best.is_none() && !hostiles.is_empty() {
+            debug!("holding fire in {}, {} hostiles heal more than the towers deal", room, hostiles.len());
+        }
+        best.map(|(target, _)| target.clone())
     }
 
-    fn new_target(&self, tower: &StructureTower) -> Option<Creep> {
-        let target = tower.pos().find_closest_by_range(find::HOSTILE_CREEPS);
-        target
+    /// Our most damaged creep in the room that the other towers aren't healing fully yet.
+    fn patient(&self, world: &dyn World, tower: &StructureState) -> Option<CreepState> {
+        world.my_creeps().into_iter()
+            .filter(|creep| creep.pos.room == tower.pos.room && !creep.spawning)
+            .filter(|creep| creep.hits_max.saturating_sub(creep.hits) > self.reservations.amount(&creep.id, ReservationKind::Work))
+            .min_by_key(|creep| creep.hits * 100 / cmp::max(creep.hits_max, 1))
     }
 
-    fn get_job(&self, memory: &MemoryReference) -> Option<Structure> {
-        memory.string("job").unwrap_or(None)
-            .and_then( |job_id| game::get_object_typed(&job_id).unwrap_or(None) )
-            .filter( |job: &Structure| job.as_attackable().map(|x| x.hits() < x.hits_max()).unwrap_or(false) )
+    /// The most urgent repair, the most damaged first within the same priority.
+    fn new_job(&self, world: &dyn World, tower: &StructureState, level: u32) -> Option<StructureState> {
+        world.structures(&tower.pos.room).into_iter()
+            .filter_map(|target| self.repair_priority(&target, level).map(|priority| (priority, target)))
+            .min_by_key(|&(priority, ref target)| {
+                let damage = match target.kind {
+                    StructureKind::Wall | StructureKind::Rampart => target.hits,
+                    _ => target.hits * 100 / cmp::max(target.hits_max, 1)
+                };
+                (priority, damage)
+            })
+            .map(|(_, target)| target)
     }
 
-    fn new_job(&self, tower: &StructureTower) -> Option<Structure> {
-        let mut targets: Vec<Structure> = tower.room().find(find::STRUCTURES);
+    /// Finds our towers in every room we own, and keeps their memory in sync.
+    fn update_towers(&self, world: &dyn World) -> Vec<StructureState> {
+        let towers: Vec<StructureState> = world.rooms().into_iter()
+            .filter(|room| room.controller.as_ref().map_or(false, |controller| controller.my))
+            .flat_map(|room| world.structures(&room.name))
+            .filter(|structure| structure.kind == StructureKind::Tower && structure.my)
+            .collect();
 
-        for target in targets.drain(..) {
-            if target.as_attackable().map(|x| x.hits() < x.hits_max()).unwrap_or(false) {
-                return Some(target);
+        let mut memory = world.memory();
+        let tower_list = &mut memory.military.tower.towers;
+
+        let gone: Vec<String> = tower_list.keys().filter(|id| towers.iter().all(|tower| tower.id != **id)).cloned().collect();
+        for id in gone {
+            info!("forgetting tower {}", id);
+            tower_list.remove(&id);
+        }
+        for tower in towers.iter() {
+            if !tower_list.contains_key(&tower.id) {
+                info!("found tower {} in {}", tower.id, tower.pos.room);
+                tower_list.insert(tower.id.clone(), TowerMemory::default());
             }
         }
-        None
+
+        towers
     }
 
-    pub fn run(&self) -> Result<(), Box<dyn Error>> {
-        let tower_list = self.memory.dict_or_create("towers").map_err(|x| x.to_string())?;
+    pub fn run(&self, world: &dyn World) -> Result<(), Box<dyn Error>> {
+        let towers = self.update_towers(world);
 
-        for tower_id in tower_list.keys() {
-            if let Ok(Some(tower)) = game::get_object_typed(&tower_id) {
-                let dict = tower_list.dict_or_create(&tower_id).map_err(|x| x.to_string())?;
-                self.run_tower(&tower, &dict)?;
-            } else {
-                warn!("Deleting nonexistent tower {}", tower_id);
-                tower_list.del(&tower_id);
+        // (target, whether there are hostiles) by room
+        let mut rooms: HashMap<String, (Option<CreepState>, bool)> = HashMap::new();
+        for tower in towers.iter() {
+            if !rooms.contains_key(&tower.pos.room) {
+                let in_room: Vec<&StructureState> = towers.iter().filter(|other| other.pos.room == tower.pos.room).collect();
+                let target = self.focus_target(&tower.pos.room, &in_room);
+                let hostiles = !self.threats.room(&tower.pos.room).hostiles.is_empty();
+                rooms.insert(tower.pos.room.clone(), (target, hostiles));
             }
         }
 
+        let reserve = world.memory().military.tower.energy_reserve;
+        for tower in towers {
+            let (target, hostiles) = rooms.get(&tower.pos.room).cloned().unwrap_or((None, false));
+            let repair = !hostiles && tower.energy > reserve;
+            let mut memory = world.memory();
+            let dict = memory.military.tower.towers.entry(tower.id.clone()).or_insert_with(TowerMemory::default);
+            self.run_tower(world, &tower, dict, target, repair)?;
+        }
+
         Ok(())
     }
 
-    pub fn run_tower(&self, tower: &StructureTower, memory: &MemoryReference) -> Result<(), Box<dyn Error>> {
-        if let Some(target) = self.get_target(&memory).or_else(|| self.new_target(&tower)) {
-            memory.set("target", target.id());
-            tower.attack(&target);
-        } else {
-            memory.del("target");
+    pub fn run_tower(&self, world: &dyn World, tower: &StructureState, memory: &mut TowerMemory,
+                     target: Option<CreepState>, repair: bool) -> Result<(), Box<dyn Error>> {
+        if let Some(target) = target {
+            memory.target = Some(target.id.clone());
+            world.structure_intent(&tower.id, StructureIntent::TowerAttack(target.id));
+            return Ok(());
         }
-        
-        if let Some(job) = self.get_job(&memory).or_else(|| self.new_job(&tower)) {
-            memory.set("job", job.id());
-            tower.repair(&job);
+        memory.target = None;
+
+        if tower.energy >= combat::TOWER_ENERGY_COST {
+            if let Some(patient) = self.patient(world, tower) {
+                let power = combat::tower_power(combat::TOWER_POWER_HEAL, tower.pos.range_to(&patient.pos));
+                memory.job = None;
+                self.reservations.reserve(&patient.id, ReservationKind::Work, cmp::min(patient.hits_max - patient.hits, power));
+                world.structure_intent(&tower.id, StructureIntent::TowerHeal(patient.id));
+                return Ok(());
+            }
+        }
+
+        if !repair {
+            memory.job = None;
+            return Ok(());
+        }
+
+        let level = world.room(&tower.pos.room).and_then(|room| room.controller).map_or(0, |controller| controller.level);
+        if let Some(job) = self.new_job(world, tower, level) {
+            let power = combat::tower_power(combat::TOWER_POWER_REPAIR, tower.pos.range_to(&job.pos));
+            memory.job = Some(job.id.clone());
+            self.reservations.reserve(&job.id, ReservationKind::Work, self.unreserved(&job, level).min(power));
+            world.structure_intent(&tower.id, StructureIntent::TowerRepair(job.id));
         } else {
-            memory.del("job");
+            memory.job = None;
         }
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use screeps::constants::Part;
+
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        ControllerState,
+        Position,
+        RoomState
+    };
+
+    /// An owned room with a full tower in the middle.
+    fn world_with_tower() -> MockWorld {
+        let world = MockWorld::new();
+        world.add_room(RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: Some(ControllerState{
+                id: "controller".to_string(),
+                pos: Position::new(40, 40, "W1N1"),
+                my: true,
+                level: 3,
+                progress: 0,
+                progress_total: 45000,
+                safe_mode: 0,
+                safe_mode_available: 0,
+                safe_mode_cooldown: 0
+            })
+        });
+
+        let mut tower = mock::structure("tower", StructureKind::Tower, Position::new(25, 25, "W1N1"));
+        tower.energy = 1000;
+        tower.energy_capacity = 1000;
+        world.add_structure(tower);
+        world
+    }
+
+    fn run(world: &MockWorld) {
+        let threats = Rc::new(ThreatAssessment::new());
+        threats.update(world);
+        Tower::new(Rc::new(Reservations::new()), threats).run(world).unwrap();
+    }
+
+    #[test]
+    fn discovers_towers() {
+        let world = world_with_tower();
+        world.memory().military.tower.towers.insert("gone".to_string(), TowerMemory::default());
+
+        run(&world);
+        assert_eq!(world.memory().military.tower.towers.keys().collect::<Vec<_>>(), vec!["tower"]);
+    }
+
+    #[test]
+    fn attacks_hostile() {
+        let world = world_with_tower();
+        let mut invader = mock::creep("invader", Position::new(25, 30, "W1N1"), &[Part::Attack, Part::Move]);
+        invader.owner = "Invader".to_string();
+        invader.my = false;
+        world.add_hostile(invader);
+
+        run(&world);
+        assert_eq!(world.structure_intents(), vec![("tower".to_string(), StructureIntent::TowerAttack("invader".to_string()))]);
+    }
+
+    #[test]
+    fn heals_before_repairing() {
+        let world = world_with_tower();
+        let mut spawn = mock::structure("spawn", StructureKind::Spawn, Position::new(20, 20, "W1N1"));
+        spawn.hits = 500;
+        world.add_structure(spawn);
+        let mut creep = mock::creep("worker", Position::new(24, 25, "W1N1"), &[Part::Work, Part::Move]);
+        creep.hits = 100;
+        world.add_creep(creep);
+
+        run(&world);
+        assert_eq!(world.structure_intents(), vec![("tower".to_string(), StructureIntent::TowerHeal("worker".to_string()))]);
+    }
+
+    #[test]
+    fn repairs_damaged_structure() {
+        let world = world_with_tower();
+        let mut spawn = mock::structure("spawn", StructureKind::Spawn, Position::new(20, 20, "W1N1"));
+        spawn.hits = 500;
+        world.add_structure(spawn);
+
+        run(&world);
+        assert_eq!(world.structure_intents(), vec![("tower".to_string(), StructureIntent::TowerRepair("spawn".to_string()))]);
+    }
+
+    #[test]
+    fn keeps_energy_reserve() {
+        let world = world_with_tower();
+        let mut tower = world.structure("tower").unwrap();
+        tower.energy = 400;
+        world.update_structure(tower);
+        let mut spawn = mock::structure("spawn", StructureKind::Spawn, Position::new(20, 20, "W1N1"));
+        spawn.hits = 500;
+        world.add_structure(spawn);
+
+        run(&world);
+        assert!(world.structure_intents().is_empty());
+    }
+}
@@ -0,0 +1,557 @@
+use std::{
+    cmp::{
+        self,
+        Reverse
+    },
+    collections::VecDeque,
+    error::Error
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    memory::{
+        PlannedStructure,
+        RoomPlan
+    },
+    world::{
+        RoomTerrain,
+        StructureKind
+    }
+};
+
+/// A tile of the room, signed so that neighbours of edge tiles can be computed.
+pub type Tile = (i32, i32);
+
+/// The base stays this far from the edges, to leave room for walls and ramparts.
+const MARGIN: i32 = 3;
+/// The anchor needs this much open space around it, so the core isn't squeezed between walls.
+const MIN_OPENNESS: u32 = 3;
+/// Roads are left out of the first levels, when energy is better spent on extensions.
+pub const ROAD_LEVEL: u32 = 3;
+const EXTENSIONS: u32 = 60;
+
+pub const DIRECTIONS: [Tile; 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+const ORTHOGONAL: [Tile; 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// Placed around the anchor, closest first.
+/// The first one always goes on the anchor itself.
+const CORE: &[StructureKind] = &[
+    StructureKind::Spawn,
+    StructureKind::Storage,
+    StructureKind::Link,
+    StructureKind::Terminal,
+    StructureKind::Spawn,
+    StructureKind::Spawn,
+    StructureKind::Tower,
+    StructureKind::Tower,
+    StructureKind::Tower,
+    StructureKind::Tower,
+    StructureKind::Tower,
+    StructureKind::Tower,
+    StructureKind::PowerSpawn,
+    StructureKind::Factory,
+    StructureKind::Nuker,
+    StructureKind::Observer
+];
+/// The storage and its link, placed right after the spawn on the anchor.
+const HUB: usize = 2;
+
+/// 10 labs around a diagonal road: `L` is a lab, `R` is a road.
+/// Every lab is within range 2 of the labs at (1, 1) and (2, 2), which hold the reagents.
+const LABS: [&str; 4] = [
+    " LLR",
+    "LLRL",
+    "LRLL",
+    "RLL "
+];
+
+/// What the layout is planned around.
+pub struct RoomFeatures {
+    pub controller: Tile,
+    pub sources: Vec<Tile>,
+    pub mineral: Option<Tile>,
+    /// The spawn placed by hand when the room was claimed, the base is built around it.
+    pub spawn: Option<Tile>
+}
+
+/// The layout of a room, while it's being planned.
+struct Layout<'a> {
+    terrain: &'a RoomTerrain,
+    /// Sources, the controller and the mineral, which can't be walked over.
+    obstacles: Vec<bool>,
+    /// What's planned on each tile.
+    planned: Vec<Option<StructureKind>>,
+    /// Tiles kept clear for creeps working sources, the controller and the mineral.
+    reserved: Vec<bool>,
+    /// Walking distance from the anchor, ignoring what's planned.
+    distance: Vec<u32>,
+    /// In the order it was planned, with the lowest level it may be built at.
+    structures: Vec<(StructureKind, Tile, u32)>
+}
+
+pub fn index(tile: Tile) -> usize {
+    (tile.1 * 50 + tile.0) as usize
+}
+
+pub fn offset(tile: Tile, direction: Tile) -> Tile {
+    (tile.0 + direction.0, tile.1 + direction.1)
+}
+
+pub fn range(a: Tile, b: Tile) -> u32 {
+    cmp::max((a.0 - b.0).abs(), (a.1 - b.1).abs()) as u32
+}
+
+/// Structures can be built anywhere but on the edges.
+pub fn in_room(tile: Tile) -> bool {
+    tile.0 >= 1 && tile.1 >= 1 && tile.0 <= 48 && tile.1 <= 48
+}
+
+fn in_base(tile: Tile) -> bool {
+    tile.0 >= MARGIN && tile.1 >= MARGIN && tile.0 < 50 - MARGIN && tile.1 < 50 - MARGIN
+}
+
+/// Marks the tiles of sources, the controller and the mineral.
+pub fn obstacles(features: &RoomFeatures) -> Vec<bool> {
+    let mut obstacles = vec![false; 2500];
+    for tile in features.sources.iter().chain(features.mineral.iter()).chain(Some(&features.controller)) {
+        if in_room(*tile) {
+            obstacles[index(*tile)] = true;
+        }
+    }
+    obstacles
+}
+
+impl<'a> Layout<'a> {
+    fn new(terrain: &'a RoomTerrain, features: &RoomFeatures) -> Layout<'a> {
+        let mut layout = Layout{
+            terrain: terrain,
+            obstacles: obstacles(features),
+            planned: vec![None; 2500],
+            reserved: vec![false; 2500],
+            distance: vec![u32::max_value(); 2500],
+            structures: Vec::new()
+        };
+
+        let keep_clear = features.sources.iter().chain(features.mineral.iter()).chain(Some(&features.controller));
+        for tile in keep_clear {
+            layout.reserve_around(*tile);
+        }
+        layout
+    }
+
+    fn reserve_around(&mut self, tile: Tile) {
+        for direction in DIRECTIONS.iter() {
+            let next = offset(tile, *direction);
+            if in_room(next) {
+                self.reserved[index(next)] = true;
+            }
+        }
+    }
+
+    fn is_wall(&self, tile: Tile) -> bool {
+        self.terrain.is_wall(tile.0, tile.1) || (in_room(tile) && self.obstacles[index(tile)])
+    }
+
+    /// Free to place something on, outside of the base.
+    fn is_free(&self, tile: Tile) -> bool {
+        in_room(tile) && !self.is_wall(tile) && self.planned[index(tile)].is_none() &&
+            self.distance[index(tile)] != u32::max_value()
+    }
+
+    /// Free to place part of the base on.
+    fn is_buildable(&self, tile: Tile) -> bool {
+        in_base(tile) && self.is_free(tile) && !self.reserved[index(tile)]
+    }
+
+    /// How far each tile is from anything the base can't be built on.
+    fn openness(&self) -> Vec<u32> {
+        let mut open = vec![0; 2500];
+        let at = |open: &[u32], tile: Tile| if in_room(tile) { open[index(tile)] } else { 0 };
+
+        for y in 0..50 {
+            for x in 0..50 {
+                let tile = (x, y);
+                if in_base(tile) && !self.is_wall(tile) && !self.reserved[index(tile)] {
+                    let before = [(-1, 0), (-1, -1), (0, -1), (1, -1)].iter()
+                        .map(|direction| at(&open, offset(tile, *direction)))
+                        .min()
+                        .unwrap_or(0);
+                    open[index(tile)] = before + 1;
+                }
+            }
+        }
+        for y in (0..50).rev() {
+            for x in (0..50).rev() {
+                let tile = (x, y);
+                if open[index(tile)] > 0 {
+                    let after = [(1, 0), (1, 1), (0, 1), (-1, 1)].iter()
+                        .map(|direction| at(&open, offset(tile, *direction)))
+                        .min()
+                        .unwrap_or(0);
+                    open[index(tile)] = cmp::min(open[index(tile)], after + 1);
+                }
+            }
+        }
+
+        open
+    }
+
+    /// The open tile closest to the sources and the controller, unless the base already has a spawn.
+    fn anchor(&self, features: &RoomFeatures) -> Option<Tile> {
+        if features.spawn.is_some() {
+            return features.spawn;
+        }
+
+        let open = self.openness();
+        let targets: Vec<Tile> = features.sources.iter().cloned().chain(Some(features.controller)).collect();
+        let needed = cmp::min(MIN_OPENNESS, open.iter().cloned().max().unwrap_or(0));
+        if needed == 0 {
+            return None;
+        }
+
+        (0..2500).map(|i| (i % 50, i / 50))
+            .filter(|tile| open[index(*tile)] >= needed)
+            .min_by_key(|tile| {
+                let spread: u32 = targets.iter().map(|target| range(*tile, *target)).sum();
+                (spread, Reverse(open[index(*tile)]), index(*tile))
+            })
+    }
+
+    /// Fills `distance` with a breadth-first search, and returns the tiles it reached, closest first.
+    fn walk_from(&mut self, anchor: Tile) -> Vec<Tile> {
+        let mut order = Vec::new();
+        let mut queue = VecDeque::new();
+        self.distance[index(anchor)] = 0;
+        queue.push_back(anchor);
+
+        while let Some(tile) = queue.pop_front() {
+            order.push(tile);
+            let next_distance = self.distance[index(tile)] + 1;
+
+            for direction in DIRECTIONS.iter() {
+                let next = offset(tile, *direction);
+                if in_room(next) && !self.is_wall(next) && self.distance[index(next)] == u32::max_value() {
+                    self.distance[index(next)] = next_distance;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        order
+    }
+
+    /// The free tile the closest to the anchor.
+    fn closest<I: Iterator<Item=Tile>>(&self, candidates: I) -> Option<Tile> {
+        candidates.filter(|tile| self.is_free(*tile))
+                  .min_by_key(|tile| (self.distance[index(*tile)], index(*tile)))
+    }
+
+    fn place(&mut self, kind: StructureKind, tile: Tile, min_level: u32) {
+        self.planned[index(tile)] = Some(kind);
+        self.structures.push((kind, tile, min_level));
+    }
+
+    /// Places a structure of the base, and roads next to it so it can be reached.
+    fn place_with_roads(&mut self, kind: StructureKind, tile: Tile) {
+        self.place(kind, tile, 0);
+
+        for direction in ORTHOGONAL.iter() {
+            let next = offset(tile, *direction);
+            if self.is_free(next) {
+                self.place(StructureKind::Road, next, 0);
+            }
+        }
+    }
+
+    /// Whether a slot of the base can hold a structure, and still be reached from a road.
+    fn is_slot(&self, tile: Tile, anchor: Tile) -> bool {
+        // a checkerboard, where the structures are reached from the roads between them
+        (tile.0 + tile.1 - anchor.0 - anchor.1) % 2 == 0 && self.is_buildable(tile) &&
+            ORTHOGONAL.iter().map(|direction| offset(tile, *direction)).any(|next| {
+                self.is_free(next) || self.planned[index(next)] == Some(StructureKind::Road)
+            })
+    }
+
+    /// Places a structure of the core on the next slot from `slots`.
+    fn place_core<'t, I: Iterator<Item=&'t Tile>>(&mut self, slots: &mut I, kind: StructureKind, anchor: Tile) -> Result<(), Box<dyn Error>> {
+        match slots.find(|tile| self.is_slot(**tile, anchor)) {
+            Some(tile) => {
+                self.place_with_roads(kind, *tile);
+                Ok(())
+            },
+            None => Err(Box::from(format!("no room for the core, at {:?}", kind)))
+        }
+    }
+
+    /// Containers and links next to the sources and the controller, and the extractor.
+    fn place_remote(&mut self, features: &RoomFeatures) {
+        let around = |tile: Tile| DIRECTIONS.iter().map(move |direction| offset(tile, *direction));
+
+        let mut mining = Vec::new();
+        for source in features.sources.iter() {
+            if let Some(tile) = self.closest(around(*source)) {
+                self.place(StructureKind::Container, tile, 0);
+                self.reserve_around(tile);
+                mining.push(tile);
+            }
+        }
+
+        let controller = features.controller;
+        let upgrade_tiles = (-2..3).flat_map(|dy| (-2..3).map(move |dx| (controller.0 + dx, controller.1 + dy)))
+                                   .filter(|tile| range(*tile, controller) == 2);
+        if let Some(container) = self.closest(upgrade_tiles) {
+            self.place(StructureKind::Container, container, 2);
+            self.reserve_around(container);
+
+            let sources = &features.sources;
+            let link = self.closest(around(container).filter(|tile| {
+                range(*tile, controller) <= 3 && sources.iter().all(|source| range(*tile, *source) > 2)
+            }));
+            if let Some(link) = link {
+                self.place(StructureKind::Link, link, 0);
+            }
+        }
+
+        for tile in mining {
+            if let Some(link) = self.closest(around(tile)) {
+                self.place(StructureKind::Link, link, 0);
+            }
+        }
+
+        if let Some(mineral) = features.mineral {
+            self.place(StructureKind::Extractor, mineral, 0);
+            if let Some(container) = self.closest(around(mineral)) {
+                self.place(StructureKind::Container, container, 6);
+            }
+        }
+    }
+
+    /// Places the lab stamp where its closest tile is the closest to the anchor.
+    fn place_labs(&mut self) -> bool {
+        let cells: Vec<(Tile, char)> = LABS.iter().enumerate()
+            .flat_map(|(y, row)| row.chars().enumerate().map(move |(x, cell)| ((x as i32, y as i32), cell)))
+            .filter(|&(_, cell)| cell != ' ')
+            .collect();
+
+        let best = (0..2500).map(|i| (i % 50, i / 50))
+            .filter(|corner| cells.iter().all(|&(cell, _)| self.is_buildable(offset(*corner, cell))))
+            .min_by_key(|corner| {
+                let closest = cells.iter().map(|&(cell, _)| self.distance[index(offset(*corner, cell))]).min();
+                (closest, index(*corner))
+            });
+
+        match best {
+            Some(corner) => {
+                for &(cell, kind) in cells.iter() {
+                    let kind = if kind == 'L' { StructureKind::Lab } else { StructureKind::Road };
+                    self.place(kind, offset(corner, cell), 0);
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Computes the level each structure unlocks at, and drops the ones over the limits.
+    fn into_plan(self, anchor: Tile) -> RoomPlan {
+        let mut counts: HashMap<StructureKind, u32> = HashMap::new();
+        let mut levels = vec![0; 2500];
+        let mut structures = Vec::new();
+
+        for &(kind, tile, min_level) in self.structures.iter().filter(|&&(kind, _, _)| kind != StructureKind::Road) {
+            let count = counts.entry(kind).or_insert(0);
+            let unlocked = (1..9).find(|level| kind.limit(*level) > *count);
+            *count += 1;
+
+            if let Some(level) = unlocked {
+                let level = cmp::max(level, min_level);
+                levels[index(tile)] = level;
+                structures.push(PlannedStructure{
+                    kind: kind,
+                    x: tile.0 as u32,
+                    y: tile.1 as u32,
+                    level: level
+                });
+            }
+        }
+
+        // a road is needed once what it leads to is
+        for &(_, tile, _) in self.structures.iter().filter(|&&(kind, _, _)| kind == StructureKind::Road) {
+            let needed_at = ORTHOGONAL.iter()
+                .map(|direction| offset(tile, *direction))
+                .filter(|next| in_room(*next) && levels[index(*next)] > 0)
+                .map(|next| levels[index(next)])
+                .min()
+                .unwrap_or(8);
+
+            structures.push(PlannedStructure{
+                kind: StructureKind::Road,
+                x: tile.0 as u32,
+                y: tile.1 as u32,
+                level: cmp::max(needed_at, ROAD_LEVEL)
+            });
+        }
+
+        // stable, so structures of the same level stay in order of importance
+        structures.sort_by_key(|structure| (structure.level, structure.kind == StructureKind::Road));
+
+        RoomPlan{
+            anchor: (anchor.0 as u32, anchor.1 as u32),
+            structures: structures
+        }
+    }
+}
+
+/// Plans the full layout of a room, for every controller level.
+///
+/// The base is a checkerboard of structures and roads, grown outwards from an anchor:
+/// the spawn placed by hand, or the open tile closest to the sources and the controller.
+/// The core (spawns, storage, terminal, towers, ...) is placed closest to the anchor, then the lab stamp,
+/// then extensions. The storage and its link are placed before the other links, so they're built first, at RCL5.
+/// Each source gets a container for its miner and a link, the controller gets a container and a link,
+/// and the mineral an extractor and a container.
+pub fn plan(terrain: &RoomTerrain, features: &RoomFeatures) -> Result<RoomPlan, Box<dyn Error>> {
+    let mut layout = Layout::new(terrain, features);
+    let anchor = layout.anchor(features).ok_or("no room for a base")?;
+    let order = layout.walk_from(anchor);
+
+    let mut core = CORE.iter();
+    if let Some(kind) = core.next() {
+        layout.place_with_roads(*kind, anchor);
+    }
+
+    // the storage and its link come before the links of the sources and the controller,
+    // so the link next to the storage is built first
+    let mut slots = order.iter().filter(|tile| **tile != anchor);
+    for kind in core.by_ref().take(HUB) {
+        layout.place_core(&mut slots, *kind, anchor)?;
+    }
+    layout.place_remote(features);
+    for kind in core {
+        layout.place_core(&mut slots, *kind, anchor)?;
+    }
+
+    if !layout.place_labs() {
+        warn!("no room for labs around {:?}", anchor);
+    }
+
+    let mut extensions = 0;
+    let mut slots = order.iter().filter(|tile| **tile != anchor);
+    while extensions < EXTENSIONS {
+        match slots.find(|tile| layout.is_slot(**tile, anchor)) {
+            Some(tile) => layout.place_with_roads(StructureKind::Extension, *tile),
+            None => break
+        }
+        extensions += 1;
+    }
+    if extensions < EXTENSIONS {
+        warn!("only room for {} extensions around {:?}", extensions, anchor);
+    }
+
+    Ok(layout.into_plan(anchor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::room::RoomDescription;
+
+    fn fixture() -> (RoomTerrain, RoomFeatures) {
+        let description = RoomDescription::load(concat!(env!("CARGO_MANIFEST_DIR"), "/rooms/W1N1.json")).unwrap();
+        let features = RoomFeatures{
+            controller: (description.controller.x as i32, description.controller.y as i32),
+            sources: description.sources.iter().map(|source| (source.x as i32, source.y as i32)).collect(),
+            mineral: Some((40, 40)),
+            spawn: Some((description.spawn.x as i32, description.spawn.y as i32))
+        };
+        (description.terrain(), features)
+    }
+
+    fn tile(planned: &PlannedStructure) -> Tile {
+        (planned.x as i32, planned.y as i32)
+    }
+
+    /// Checks what every plan must hold to.
+    fn check(plan: &RoomPlan, terrain: &RoomTerrain, features: &RoomFeatures) {
+        let mut taken = vec![false; 2500];
+        for planned in plan.structures.iter() {
+            let tile = tile(planned);
+            assert!(in_room(tile), "{:?} at {:?} is outside of the room", planned.kind, tile);
+            assert!(!terrain.is_wall(tile.0, tile.1), "{:?} at {:?} is on a wall", planned.kind, tile);
+            assert!(!taken[index(tile)], "{:?} at {:?} is on another structure", planned.kind, tile);
+            taken[index(tile)] = true;
+
+            let on_feature = features.sources.iter().chain(Some(&features.controller)).any(|feature| *feature == tile);
+            assert!(!on_feature, "{:?} at {:?} is on a source or the controller", planned.kind, tile);
+        }
+
+        for level in 1..9 {
+            let mut counts: HashMap<StructureKind, u32> = HashMap::new();
+            for planned in plan.structures.iter().filter(|planned| planned.level <= level) {
+                *counts.entry(planned.kind).or_insert(0) += 1;
+            }
+            for (kind, count) in counts {
+                assert!(count <= kind.limit(level), "{} {:?} at RCL{}", count, kind, level);
+            }
+        }
+    }
+
+    fn count(plan: &RoomPlan, kind: StructureKind, level: u32) -> u32 {
+        plan.structures.iter().filter(|planned| planned.kind == kind && planned.level <= level).count() as u32
+    }
+
+    #[test]
+    fn plans_fixture_room() {
+        let (terrain, features) = fixture();
+        let plan = plan(&terrain, &features).unwrap();
+
+        assert_eq!(plan.anchor, (25, 25));
+        check(&plan, &terrain, &features);
+        for level in 1..9 {
+            for kind in [StructureKind::Spawn, StructureKind::Extension, StructureKind::Tower, StructureKind::Lab].iter() {
+                assert_eq!(count(&plan, *kind, level), kind.limit(level), "{:?} at RCL{}", kind, level);
+            }
+        }
+        assert_eq!(count(&plan, StructureKind::Link, 8), 4);
+        assert_eq!(count(&plan, StructureKind::Extractor, 8), 1);
+    }
+
+    #[test]
+    fn builds_storage_link_first() {
+        let (terrain, features) = fixture();
+        let plan = plan(&terrain, &features).unwrap();
+
+        let storage = plan.structures.iter().find(|planned| planned.kind == StructureKind::Storage).unwrap();
+        let hub = plan.structures.iter()
+            .find(|planned| planned.kind == StructureKind::Link && range(tile(planned), tile(storage)) <= 2)
+            .unwrap();
+        assert_eq!(storage.level, 4);
+        assert_eq!(hub.level, 5);
+    }
+
+    #[test]
+    fn plans_around_walls() {
+        let (_, features) = fixture();
+        // a wall right next to the spawn, and one through the middle of where the extensions would go
+        let mut rows: Vec<String> = (0..50).map(|_| " ".repeat(50)).collect();
+        rows[24] = format!("{}xxxxxxxxxx{}", " ".repeat(26), " ".repeat(14));
+        rows[30] = format!("{}{}", " ".repeat(10), "x".repeat(30));
+        let terrain = RoomTerrain::parse(&rows);
+
+        let plan = plan(&terrain, &features).unwrap();
+        check(&plan, &terrain, &features);
+        assert_eq!(count(&plan, StructureKind::Extension, 8), 60);
+    }
+
+    #[test]
+    fn finds_anchor_without_spawn() {
+        let (terrain, mut features) = fixture();
+        features.spawn = None;
+
+        let plan = plan(&terrain, &features).unwrap();
+        check(&plan, &terrain, &features);
+        let anchor = (plan.anchor.0 as i32, plan.anchor.1 as i32);
+        assert_eq!(plan.structures.iter().find(|planned| tile(planned) == anchor).unwrap().kind, StructureKind::Spawn);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    cell::RefCell,
+    error::Error
+};
+
+use hashbrown::HashMap;
+use screeps::constants::ReturnCode;
+
+use crate::{
+    memory::{
+        PlannedStructure,
+        RoomPlan,
+        SourceMemory
+    },
+    world::{
+        Position,
+        StructureKind,
+        StructureState,
+        World
+    }
+};
+
+/// Computing the layout of a room.
+pub mod layout;
+/// Walls and ramparts around the base.
+pub mod ramparts;
+/// Roads between the base and what's outside of it.
+pub mod roads;
+
+use self::layout::{
+    RoomFeatures,
+    ROAD_LEVEL
+};
+
+/// Construction sites a room has at once, so builders finish what they start.
+const MAX_SITES: usize = 5;
+/// Ticks between looking for planned structures that aren't built yet.
+const PLACE_INTERVAL: u32 = 20;
+/// Ticks traffic is counted over, before roads are added where it was heavy.
+const TRAFFIC_PERIOD: u32 = 1500;
+/// Steps onto a tile during a period that make it worth a road.
+const TRAFFIC_THRESHOLD: u32 = 100;
+
+/// Plans the layout of every room we own, and places construction sites as the controller levels up.
+///
+/// The plan is computed once per room and kept in `Memory.plans.<room>`, see `layout::plan`.
+/// Deleting it from the console has the room replanned.
+/// Planning is expensive, so at most one room is planned per tick.
+/// 
+/// Steps creeps take are counted, and busy tiles get roads on top of the planned ones.  
+/// The counts are only kept on the heap, a reset just delays these roads.
+pub struct RoomPlanner {
+    /// Steps onto each tile in the current period, by room.
+    traffic: RefCell<HashMap<String, Vec<u32>>>,
+    /// Where each creep was in the previous tick.
+    last_pos: RefCell<HashMap<String, Position>>
+}
+
+impl RoomPlanner {
+    pub fn new() -> RoomPlanner {
+        RoomPlanner{
+            traffic: RefCell::new(HashMap::new()),
+            last_pos: RefCell::new(HashMap::new())
+        }
+    }
+
+    pub fn run(&self, world: &dyn World) -> Result<(), Box<dyn Error>> {
+        let mut planned = false;
+        self.count_traffic(world);
+
+        for room in world.rooms() {
+            let level = match room.controller {
+                Some(ref controller) if controller.my => controller.level,
+                _ => continue
+            };
+
+            if !world.memory().plans.contains_key(&room.name) {
+                if planned {
+                    continue;
+                }
+                let plan = self.plan(world, &room.name)?;
+                info!("planned {} structures in {}", plan.structures.len(), room.name);
+                world.memory().plans.insert(room.name.clone(), plan);
+                planned = true;
+            }
+
+            if world.time() % TRAFFIC_PERIOD == 0 {
+                self.add_traffic_roads(world, &room.name);
+            }
+            if world.time() % PLACE_INTERVAL == 0 {
+                self.place(world, &room.name, level);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn count_traffic(&self, world: &dyn World) {
+        let mut traffic = self.traffic.borrow_mut();
+        let mut last_pos = self.last_pos.borrow_mut();
+        let mut positions = HashMap::new();
+
+        for creep in world.my_creeps().into_iter().filter(|creep| !creep.spawning) {
+            if last_pos.get(&creep.name).map_or(false, |pos| *pos != creep.pos) {
+                let counts = traffic.entry(creep.pos.room.clone()).or_insert_with(|| vec![0; 2500]);
+                counts[(creep.pos.y * 50 + creep.pos.x) as usize] += 1;
+            }
+            positions.insert(creep.name, creep.pos);
+        }
+
+        *last_pos = positions;
+    }
+
+    /// Plans roads on the tiles that were busy during the last period, and starts a new one.
+    fn add_traffic_roads(&self, world: &dyn World, room: &str) {
+        let counts = match self.traffic.borrow_mut().remove(room) {
+            Some(counts) => counts,
+            None => return
+        };
+        let mut memory = world.memory();
+        let plan = match memory.plans.get_mut(room) {
+            Some(plan) => plan,
+            None => return
+        };
+
+        let mut added = 0;
+        for (index, count) in counts.into_iter().enumerate() {
+            let (x, y) = ((index % 50) as u32, (index / 50) as u32);
+            let taken = plan.structures.iter().any(|planned| planned.x == x && planned.y == y);
+            if count < TRAFFIC_THRESHOLD || taken || x == 0 || y == 0 || x == 49 || y == 49 {
+                continue;
+            }
+
+            plan.structures.push(PlannedStructure{
+                kind: StructureKind::Road,
+                x: x,
+                y: y,
+                level: ROAD_LEVEL
+            });
+            added += 1;
+        }
+
+        if added > 0 {
+            plan.structures.sort_by_key(|structure| (structure.level, structure.kind == StructureKind::Road));
+            info!("planned {} roads in {} where traffic was heavy", added, room);
+        }
+    }
+
+    fn plan(&self, world: &dyn World, room: &str) -> Result<RoomPlan, Box<dyn Error>> {
+        let tile = |pos: &Position| (pos.x as i32, pos.y as i32);
+
+        let controller = world.room(room).and_then(|room| room.controller).ok_or("no controller")?;
+        let sources = world.sources(room);
+        let spawn = world.spawns().into_iter()
+                         .filter(|spawn| spawn.pos.room == room)
+                         .min_by(|a, b| a.name.cmp(&b.name));
+        let features = RoomFeatures{
+            controller: tile(&controller.pos),
+            sources: sources.iter().map(|source| tile(&source.pos)).collect(),
+            mineral: world.minerals(room).first().map(|mineral| tile(&mineral.pos)),
+            spawn: spawn.map(|spawn| tile(&spawn.pos))
+        };
+
+        let terrain = world.terrain(room);
+        let mut plan = layout::plan(&terrain, &features)?;
+        roads::plan_roads(&terrain, &mut plan, &features);
+        ramparts::plan_ramparts(&terrain, &mut plan, &features);
+
+        // miners park on the planned containers, instead of wherever they arrive first
+        let mut memory = world.memory();
+        for source in sources.iter() {
+            let container = plan.structures.iter().find(|planned| {
+                planned.kind == StructureKind::Container &&
+                    source.pos.is_near_to(&Position::new(planned.x, planned.y, room))
+            });
+            let source_memory = memory.tasks.harvest.sources.entry(source.id.clone()).or_insert_with(SourceMemory::default);
+            if source_memory.container.is_none() {
+                source_memory.container = container.map(|container| Position::new(container.x, container.y, room));
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Places sites for what the plan has up to the level, in the plan's order.
+    fn place(&self, world: &dyn World, room: &str, level: u32) {
+        let plan = match world.memory().plans.get(room) {
+            Some(plan) => plan.clone(),
+            None => return
+        };
+        let structures = world.structures(room);
+        let sites = world.construction_sites(room);
+        let mut site_count = sites.len();
+
+        for planned in plan.structures.iter().filter(|planned| planned.level <= level) {
+            if site_count >= MAX_SITES {
+                break;
+            }
+
+            let pos = Position::new(planned.x, planned.y, room);
+            if sites.iter().any(|site| site.pos == pos) ||
+               structures.iter().any(|structure| structure.pos == pos && blocks(structure, planned.kind)) {
+                continue;
+            }
+
+            match world.create_construction_site(&pos, planned.kind) {
+                ReturnCode::Ok => site_count += 1,
+                // too many sites in the world
+                ReturnCode::Full => break,
+                // the limit is taken by structures built outside of the plan
+                ReturnCode::RclNotEnough => (),
+                code => debug!("can't place {:?} at {:?}: {:?}", planned.kind, pos, code)
+            }
+        }
+    }
+}
+
+/// Whether a structure already there stops a planned one from being built.
+fn blocks(structure: &StructureState, kind: StructureKind) -> bool {
+    structure.kind == kind || match (structure.kind, kind) {
+        (StructureKind::Road, _) | (_, StructureKind::Road) => false,
+        (StructureKind::Rampart, _) | (_, StructureKind::Rampart) => false,
+        _ => true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use screeps::constants::Part;
+
+    use super::*;
+    use crate::world::mock::{
+        self,
+        MockWorld
+    };
+
+    /// A creep stepping back and forth between (10, 10) and (11, 10), which get 99 and 100 steps.
+    fn world_with_traffic(planner: &RoomPlanner) -> MockWorld {
+        let world = MockWorld::new();
+        world.memory().plans.insert("W1N1".to_string(), RoomPlan::default());
+
+        for step in 0..200 {
+            let x = if step % 2 == 0 { 10 } else { 11 };
+            world.update_creep(mock::creep("walker", Position::new(x, 10, "W1N1"), &[Part::Move]));
+            planner.count_traffic(&world);
+        }
+        world
+    }
+
+    fn roads(world: &MockWorld) -> Vec<(u32, u32)> {
+        world.memory().plans["W1N1"].structures.iter()
+            .filter(|planned| planned.kind == StructureKind::Road)
+            .map(|planned| (planned.x, planned.y))
+            .collect()
+    }
+
+    #[test]
+    fn adds_roads_where_traffic_is_heavy() {
+        let planner = RoomPlanner::new();
+        let world = world_with_traffic(&planner);
+        planner.add_traffic_roads(&world, "W1N1");
+
+        assert_eq!(roads(&world), vec![(11, 10)]);
+        assert_eq!(world.memory().plans["W1N1"].structures[0].level, ROAD_LEVEL);
+
+        // a new period starts from scratch
+        planner.add_traffic_roads(&world, "W1N1");
+        assert_eq!(roads(&world).len(), 1);
+    }
+
+    #[test]
+    fn skips_planned_tiles() {
+        let planner = RoomPlanner::new();
+        let world = world_with_traffic(&planner);
+        world.memory().plans.get_mut("W1N1").unwrap().structures.push(PlannedStructure{
+            kind: StructureKind::Extension,
+            x: 11,
+            y: 10,
+            level: 2
+        });
+        planner.add_traffic_roads(&world, "W1N1");
+
+        assert!(roads(&world).is_empty());
+    }
+}
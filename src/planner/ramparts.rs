@@ -0,0 +1,383 @@
+use std::{
+    cmp,
+    collections::VecDeque
+};
+
+use crate::{
+    memory::{
+        PlannedStructure,
+        RoomPlan
+    },
+    world::{
+        RoomTerrain,
+        StructureKind
+    }
+};
+
+use super::layout::{
+    self,
+    RoomFeatures,
+    Tile,
+    DIRECTIONS
+};
+
+/// Tiles this close to the base are kept inside the walls, so ranged attackers can't reach it from outside.
+const PROTECT_RANGE: i32 = 3;
+/// Walls and ramparts are built once the first tower can keep them up.
+const RAMPART_LEVEL: u32 = 4;
+/// More than any cut can cost, a tile with this capacity can't be cut.
+const INFINITE: u32 = 1 << 20;
+
+/// Hits walls and ramparts are repaired up to at each controller level, so repair energy isn't sunk into them early.
+pub fn target_hits(level: u32) -> u32 {
+    const HITS: [u32; 9] = [0, 0, 10_000, 30_000, 100_000, 300_000, 1_000_000, 3_000_000, 10_000_000];
+    HITS[cmp::min(level, 8) as usize]
+}
+
+struct Edge {
+    to: usize,
+    capacity: u32
+}
+
+/// A flow network, for Dinic's max flow.
+/// Every edge is followed by its reverse, so the reverse of edge `i` is `i ^ 1`.
+struct Network {
+    edges: Vec<Edge>,
+    adjacency: Vec<Vec<usize>>,
+    level: Vec<u32>,
+    next: Vec<usize>
+}
+
+impl Network {
+    fn new(nodes: usize) -> Network {
+        Network{
+            edges: Vec::new(),
+            adjacency: vec![Vec::new(); nodes],
+            level: vec![0; nodes],
+            next: vec![0; nodes]
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: u32) {
+        self.adjacency[from].push(self.edges.len());
+        self.edges.push(Edge{ to: to, capacity: capacity });
+        self.adjacency[to].push(self.edges.len());
+        self.edges.push(Edge{ to: from, capacity: 0 });
+    }
+
+    /// Nodes reachable from `source` over edges with capacity left.
+    fn reachable(&self, source: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.adjacency.len()];
+        let mut queue = VecDeque::new();
+        reached[source] = true;
+        queue.push_back(source);
+
+        while let Some(node) = queue.pop_front() {
+            for &id in self.adjacency[node].iter() {
+                let edge = &self.edges[id];
+                if edge.capacity > 0 && !reached[edge.to] {
+                    reached[edge.to] = true;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        reached
+    }
+
+    /// Levels the nodes by distance from `source`, returns whether `sink` can still be reached.
+    fn level_from(&mut self, source: usize, sink: usize) -> bool {
+        for level in self.level.iter_mut() {
+            *level = u32::max_value();
+        }
+        let mut queue = VecDeque::new();
+        self.level[source] = 0;
+        queue.push_back(source);
+
+        while let Some(node) = queue.pop_front() {
+            for &id in self.adjacency[node].iter() {
+                let edge = &self.edges[id];
+                if edge.capacity > 0 && self.level[edge.to] == u32::max_value() {
+                    self.level[edge.to] = self.level[node] + 1;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        self.level[sink] != u32::max_value()
+    }
+
+    /// Pushes flow along a single path of increasing levels, from `source` to `sink`.  
+    /// Walks the path with a stack, since it can be as long as the room has tiles.
+    fn push(&mut self, source: usize, sink: usize) -> u32 {
+        let mut path: Vec<usize> = Vec::new();
+        let mut node = source;
+
+        loop {
+            if node == sink {
+                let pushed = path.iter().map(|id| self.edges[*id].capacity).min().unwrap_or(0);
+                for &id in path.iter() {
+                    self.edges[id].capacity -= pushed;
+                    self.edges[id ^ 1].capacity += pushed;
+                }
+                return pushed;
+            }
+
+            let mut advanced = false;
+            while self.next[node] < self.adjacency[node].len() {
+                let id = self.adjacency[node][self.next[node]];
+                let to = self.edges[id].to;
+
+                if self.edges[id].capacity > 0 && self.level[to] == self.level[node] + 1 {
+                    path.push(id);
+                    node = to;
+                    advanced = true;
+                    break;
+                }
+                self.next[node] += 1;
+            }
+
+            if !advanced {
+                // a dead end, go back and skip the edge that led here
+                match path.pop() {
+                    Some(id) => {
+                        node = self.edges[id ^ 1].to;
+                        self.next[node] += 1;
+                    },
+                    None => return 0
+                }
+            }
+        }
+    }
+
+    fn max_flow(&mut self, source: usize, sink: usize) -> u32 {
+        let mut flow = 0;
+
+        while self.level_from(source, sink) {
+            for next in self.next.iter_mut() {
+                *next = 0;
+            }
+            loop {
+                let pushed = self.push(source, sink);
+                if pushed == 0 {
+                    break;
+                }
+                flow += pushed;
+                if flow >= INFINITE {
+                    return flow;
+                }
+            }
+        }
+
+        flow
+    }
+}
+
+/// Whether the structure is part of the base, rather than next to a source, the controller or the mineral.
+fn is_base(planned: &PlannedStructure, features: &RoomFeatures) -> bool {
+    let tile = (planned.x as i32, planned.y as i32);
+    match planned.kind {
+        StructureKind::Road | StructureKind::Container | StructureKind::Extractor => false,
+        StructureKind::Link => {
+            features.sources.iter().all(|source| layout::range(tile, *source) > 2) &&
+                layout::range(tile, features.controller) > 3
+        },
+        _ => true
+    }
+}
+
+/// Adds walls and ramparts on the fewest tiles that seal the base off from the exits.
+///
+/// The tiles within `PROTECT_RANGE` of the base are the source of a flow network and the tiles next to exits
+/// the sink, every other tile can carry one unit of flow. The tiles of the minimum cut are where the
+/// attackers have to break through.
+/// Tiles on planned roads get ramparts so our creeps can pass, the others get walls, which don't decay,
+/// except for a gate in every stretch of wall without a road through it.
+pub fn plan_ramparts(terrain: &RoomTerrain, plan: &mut RoomPlan, features: &RoomFeatures) {
+    let obstacles = layout::obstacles(features);
+    let passable = |tile: Tile| {
+        tile.0 >= 0 && tile.1 >= 0 && tile.0 <= 49 && tile.1 <= 49 &&
+            !terrain.is_wall(tile.0, tile.1) && !obstacles[layout::index(tile)]
+    };
+    let tiles: Vec<Tile> = (0..2500).map(|i| (i % 50, i / 50)).filter(|tile| passable(*tile)).collect();
+
+    let mut protected = vec![false; 2500];
+    for planned in plan.structures.iter().filter(|planned| is_base(planned, features)) {
+        for dy in -PROTECT_RANGE..PROTECT_RANGE + 1 {
+            for dx in -PROTECT_RANGE..PROTECT_RANGE + 1 {
+                let tile = (planned.x as i32 + dx, planned.y as i32 + dy);
+                // the cut has to fit between the protected tiles and the exits
+                let inside = tile.0 >= 3 && tile.1 >= 3 && tile.0 <= 46 && tile.1 <= 46;
+                if inside && passable(tile) {
+                    protected[layout::index(tile)] = true;
+                }
+            }
+        }
+    }
+
+    let mut exits = vec![false; 2500];
+    for &tile in tiles.iter().filter(|tile| tile.0 == 0 || tile.1 == 0 || tile.0 == 49 || tile.1 == 49) {
+        exits[layout::index(tile)] = true;
+        for direction in DIRECTIONS.iter() {
+            let next = layout::offset(tile, *direction);
+            if passable(next) {
+                exits[layout::index(next)] = true;
+            }
+        }
+    }
+
+    // every tile is split into an entry and an exit node, joined by the tile's capacity
+    let (source, sink) = (5000, 5001);
+    let mut network = Network::new(5002);
+    for &tile in tiles.iter() {
+        let index = layout::index(tile);
+        let capacity = if protected[index] || exits[index] { INFINITE } else { 1 };
+        network.add_edge(index * 2, index * 2 + 1, capacity);
+
+        if protected[index] {
+            network.add_edge(source, index * 2, INFINITE);
+        }
+        if exits[index] {
+            network.add_edge(index * 2 + 1, sink, INFINITE);
+        }
+        for direction in DIRECTIONS.iter() {
+            let next = layout::offset(tile, *direction);
+            if passable(next) {
+                network.add_edge(index * 2 + 1, layout::index(next) * 2, INFINITE);
+            }
+        }
+    }
+
+    if network.max_flow(source, sink) >= INFINITE {
+        warn!("the base can't be walled off from the exits");
+        return;
+    }
+
+    let reached = network.reachable(source);
+    let cut: Vec<Tile> = tiles.into_iter()
+        .filter(|tile| reached[layout::index(*tile) * 2] && !reached[layout::index(*tile) * 2 + 1])
+        .collect();
+
+    let on_plan = |tile: &Tile| plan.structures.iter().any(|planned| planned.x == tile.0 as u32 && planned.y == tile.1 as u32);
+    let mut ramparts: Vec<bool> = cut.iter().map(|tile| on_plan(tile)).collect();
+
+    // every stretch of wall gets a gate in the middle, for creeps leaving the room
+    let mut seen = vec![false; cut.len()];
+    for start in 0..cut.len() {
+        if seen[start] {
+            continue;
+        }
+        let mut stretch = vec![start];
+        seen[start] = true;
+        let mut i = 0;
+        while i < stretch.len() {
+            let tile = cut[stretch[i]];
+            for (other, next) in cut.iter().enumerate() {
+                if !seen[other] && layout::range(tile, *next) == 1 {
+                    seen[other] = true;
+                    stretch.push(other);
+                }
+            }
+            i += 1;
+        }
+
+        if stretch.iter().all(|index| !ramparts[*index]) {
+            stretch.sort_by_key(|index| layout::index(cut[*index]));
+            ramparts[stretch[stretch.len() / 2]] = true;
+        }
+    }
+
+    for (tile, rampart) in cut.into_iter().zip(ramparts) {
+        plan.structures.push(PlannedStructure{
+            kind: if rampart { StructureKind::Rampart } else { StructureKind::Wall },
+            x: tile.0 as u32,
+            y: tile.1 as u32,
+            level: RAMPART_LEVEL
+        });
+    }
+    plan.structures.sort_by_key(|structure| (structure.level, structure.kind == StructureKind::Road));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A base at (25, 25), inside a room with the given rows.
+    fn plan_with(rows: &[String]) -> RoomPlan {
+        let features = RoomFeatures{
+            controller: (30, 30),
+            sources: vec![(20, 20)],
+            mineral: None,
+            spawn: Some((25, 25))
+        };
+        let mut plan = RoomPlan{
+            anchor: (25, 25),
+            structures: vec![PlannedStructure{
+                kind: StructureKind::Spawn,
+                x: 25,
+                y: 25,
+                level: 1
+            }]
+        };
+        plan_ramparts(&RoomTerrain::parse(rows), &mut plan, &features);
+        plan
+    }
+
+    fn cut(plan: &RoomPlan) -> Vec<&PlannedStructure> {
+        plan.structures.iter()
+            .filter(|planned| planned.kind == StructureKind::Rampart || planned.kind == StructureKind::Wall)
+            .collect()
+    }
+
+    #[test]
+    fn target_hits_by_level() {
+        let hits: Vec<u32> = (0..10).map(target_hits).collect();
+        assert_eq!(hits, vec![0, 0, 10_000, 30_000, 100_000, 300_000, 1_000_000, 3_000_000, 10_000_000, 10_000_000]);
+    }
+
+    #[test]
+    fn cuts_corridor_to_single_exit() {
+        // an open cave from (10, 10) to (40, 40), with a corridor up to the top exit
+        let rows: Vec<String> = (0..50).map(|y| (0..50).map(|x| {
+            let cave = x >= 10 && x <= 40 && y >= 10 && y <= 40;
+            let corridor = x == 25 && y < 10;
+            if cave || corridor { ' ' } else { 'x' }
+        }).collect()).collect();
+        let plan = plan_with(&rows);
+
+        let cut = cut(&plan);
+        assert_eq!(cut.len(), 1);
+        assert_eq!(cut[0].kind, StructureKind::Rampart);
+        assert_eq!(cut[0].x, 25);
+        assert!(cut[0].y >= 2 && cut[0].y < 10);
+        assert_eq!(cut[0].level, RAMPART_LEVEL);
+    }
+
+    #[test]
+    fn leaves_enclosed_base_alone() {
+        // a ring of natural walls from (15, 15) to (35, 35)
+        let rows: Vec<String> = (0..50).map(|y| (0..50).map(|x| {
+            let ring = ((x == 15 || x == 35) && y >= 15 && y <= 35) || ((y == 15 || y == 35) && x >= 15 && x <= 35);
+            if ring { 'x' } else { ' ' }
+        }).collect()).collect();
+        let plan = plan_with(&rows);
+
+        assert!(cut(&plan).is_empty());
+        assert_eq!(plan.structures.len(), 1);
+    }
+
+    #[test]
+    fn walls_off_open_room() {
+        let rows: Vec<String> = (0..50).map(|_| " ".repeat(50)).collect();
+        let plan = plan_with(&rows);
+
+        let cut = cut(&plan);
+        assert!(!cut.is_empty());
+        // every stretch has a gate
+        assert!(cut.iter().any(|planned| planned.kind == StructureKind::Rampart));
+        for planned in cut {
+            let range = layout::range((planned.x as i32, planned.y as i32), (25, 25));
+            assert!(range > PROTECT_RANGE as u32, "{:?} at {}, {} is inside the base", planned.kind, planned.x, planned.y);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    cmp::{
+        self,
+        Reverse
+    },
+    collections::BinaryHeap
+};
+
+use crate::{
+    memory::{
+        PlannedStructure,
+        RoomPlan
+    },
+    world::{
+        RoomTerrain,
+        StructureKind,
+        Terrain
+    }
+};
+
+use super::layout::{
+    self,
+    RoomFeatures,
+    Tile,
+    DIRECTIONS,
+    ROAD_LEVEL
+};
+
+/// The same costs the game's pathfinder uses, so planned roads are where creeps would walk anyway.
+const ROAD_COST: u32 = 1;
+const PLAIN_COST: u32 = 2;
+const SWAMP_COST: u32 = 10;
+
+/// The cheapest path from next to `from` to next to `to`, over tiles that aren't blocked.
+/// Doesn't include `from`, ends on a tile next to `to`.
+fn find_path(terrain: &RoomTerrain, blocked: &[bool], roads: &[bool], from: Tile, to: Tile) -> Option<Vec<Tile>> {
+    let mut cost = vec![u32::max_value(); 2500];
+    let mut came_from = vec![None; 2500];
+    let mut open = BinaryHeap::new();
+
+    cost[layout::index(from)] = 0;
+    open.push(Reverse((layout::range(from, to), from)));
+
+    while let Some(Reverse((_, tile))) = open.pop() {
+        if tile != from && layout::range(tile, to) <= 1 {
+            let mut path = vec![tile];
+            let mut step = tile;
+            while let Some(previous) = came_from[layout::index(step)] {
+                if previous == from {
+                    break;
+                }
+                path.push(previous);
+                step = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for direction in DIRECTIONS.iter() {
+            let next = layout::offset(tile, *direction);
+            if !layout::in_room(next) || blocked[layout::index(next)] {
+                continue;
+            }
+
+            let step_cost = match terrain.get(next.0, next.1) {
+                Terrain::Wall => continue,
+                _ if roads[layout::index(next)] => ROAD_COST,
+                Terrain::Swamp => SWAMP_COST,
+                Terrain::Plain => PLAIN_COST
+            };
+            let next_cost = cost[layout::index(tile)] + step_cost;
+            if next_cost < cost[layout::index(next)] {
+                cost[layout::index(next)] = next_cost;
+                came_from[layout::index(next)] = Some(tile);
+                open.push(Reverse((next_cost + layout::range(next, to) * ROAD_COST, next)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Adds roads from the storage to every container outside of the base:
+/// the ones next to the sources, the controller and the mineral.
+///
+/// Each road is needed at the level of the container it leads to.
+/// Roads planned earlier are cheaper to walk, so later roads join them instead of running alongside.
+pub fn plan_roads(terrain: &RoomTerrain, plan: &mut RoomPlan, features: &RoomFeatures) {
+    let mut blocked = layout::obstacles(features);
+    let mut roads = vec![false; 2500];
+    for planned in plan.structures.iter() {
+        let index = layout::index((planned.x as i32, planned.y as i32));
+        match planned.kind {
+            StructureKind::Road => roads[index] = true,
+            kind if !kind.is_walkable() => blocked[index] = true,
+            _ => ()
+        }
+    }
+
+    let from = plan.structures.iter()
+                   .find(|planned| planned.kind == StructureKind::Storage)
+                   .map(|storage| (storage.x as i32, storage.y as i32))
+                   .unwrap_or((plan.anchor.0 as i32, plan.anchor.1 as i32));
+    let mut targets: Vec<(Tile, u32)> = plan.structures.iter()
+        .filter(|planned| planned.kind == StructureKind::Container)
+        .map(|container| ((container.x as i32, container.y as i32), container.level))
+        .collect();
+    targets.sort_by_key(|&(tile, level)| (level, layout::range(from, tile)));
+
+    // the lowest level each tile of a road is needed at
+    let mut needed = vec![u32::max_value(); 2500];
+    for (target, level) in targets {
+        let path = match find_path(terrain, &blocked, &roads, from, target) {
+            Some(path) => path,
+            None => {
+                warn!("no path for a road from {:?} to {:?}", from, target);
+                continue;
+            }
+        };
+
+        for tile in path {
+            let index = layout::index(tile);
+            roads[index] = true;
+            needed[index] = cmp::min(needed[index], cmp::max(level, ROAD_LEVEL));
+        }
+    }
+
+    // roads of the base that are on the way are needed sooner
+    for planned in plan.structures.iter_mut().filter(|planned| planned.kind == StructureKind::Road) {
+        let index = layout::index((planned.x as i32, planned.y as i32));
+        planned.level = cmp::min(planned.level, needed[index]);
+        needed[index] = u32::max_value();
+    }
+
+    for (index, level) in needed.into_iter().enumerate().filter(|&(_, level)| level != u32::max_value()) {
+        plan.structures.push(PlannedStructure{
+            kind: StructureKind::Road,
+            x: (index % 50) as u32,
+            y: (index / 50) as u32,
+            level: level
+        });
+    }
+    plan.structures.sort_by_key(|structure| (structure.level, structure.kind == StructureKind::Road));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A wall down the middle of the room, with gaps at the top and bottom.
+    fn walled() -> RoomTerrain {
+        let rows: Vec<String> = (0..50).map(|y| if y >= 5 && y < 45 {
+            format!("{}x{}", " ".repeat(20), " ".repeat(29))
+        } else {
+            " ".repeat(50)
+        }).collect();
+        RoomTerrain::parse(&rows)
+    }
+
+    fn planned(kind: StructureKind, x: u32, y: u32, level: u32) -> PlannedStructure {
+        PlannedStructure{
+            kind: kind,
+            x: x,
+            y: y,
+            level: level
+        }
+    }
+
+    /// Checks that a path is walkable step by step, from next to `from` to next to `to`.
+    fn check_path(path: &[Tile], terrain: &RoomTerrain, from: Tile, to: Tile) {
+        assert_eq!(layout::range(path[0], from), 1);
+        assert!(layout::range(*path.last().unwrap(), to) <= 1);
+        for step in path.windows(2) {
+            assert_eq!(layout::range(step[0], step[1]), 1);
+        }
+        for tile in path {
+            assert!(!terrain.is_wall(tile.0, tile.1), "{:?} is a wall", tile);
+        }
+    }
+
+    #[test]
+    fn finds_path_around_wall() {
+        let terrain = walled();
+        let path = find_path(&terrain, &[false; 2500], &[false; 2500], (10, 25), (30, 25)).unwrap();
+
+        check_path(&path, &terrain, (10, 25), (30, 25));
+        assert!(path.iter().any(|tile| tile.0 == 20 && (tile.1 < 5 || tile.1 >= 45)));
+    }
+
+    #[test]
+    fn finds_no_path_through_blocked_tiles() {
+        let mut blocked = vec![false; 2500];
+        for y in 1..49 {
+            blocked[layout::index((20, y))] = true;
+        }
+
+        assert_eq!(find_path(&RoomTerrain::plain(), &blocked, &[false; 2500], (10, 25), (30, 25)), None);
+    }
+
+    #[test]
+    fn roads_avoid_planned_structures() {
+        let terrain = walled();
+        let features = RoomFeatures{
+            controller: (45, 45),
+            sources: vec![(31, 25)],
+            mineral: None,
+            spawn: None
+        };
+        let mut plan = RoomPlan{
+            anchor: (10, 25),
+            structures: vec![
+                planned(StructureKind::Storage, 10, 25, 4),
+                planned(StructureKind::Container, 30, 25, 0)
+            ]
+        };
+        // extensions closing the bottom gap, so the road has to take the top one
+        for y in 45..49 {
+            plan.structures.push(planned(StructureKind::Extension, 20, y, 2));
+        }
+        plan_roads(&terrain, &mut plan, &features);
+
+        let roads: Vec<Tile> = plan.structures.iter()
+            .filter(|planned| planned.kind == StructureKind::Road)
+            .map(|planned| (planned.x as i32, planned.y as i32))
+            .collect();
+        assert!(roads.iter().any(|tile| layout::range(*tile, (10, 25)) == 1));
+        assert!(roads.iter().any(|tile| layout::range(*tile, (30, 25)) == 1));
+        assert!(roads.iter().any(|tile| tile.0 == 20 && tile.1 < 5));
+        for structure in plan.structures.iter().filter(|planned| planned.kind != StructureKind::Road) {
+            assert!(!roads.contains(&(structure.x as i32, structure.y as i32)), "road on {:?}", structure.kind);
+        }
+        assert!(plan.structures.iter()
+                    .filter(|planned| planned.kind == StructureKind::Road)
+                    .all(|road| road.level == ROAD_LEVEL));
+    }
+}
@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+
+use hashbrown::HashMap;
+
+use crate::memory::CreepTaskMemory;
+
+/// What a reservation counts towards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReservationKind {
+    /// Energy brought to the object.
+    EnergyIn,
+    /// Energy taken from the object.
+    EnergyOut,
+    /// Work done on the object, such as build progress or repaired hits.
+    Work
+}
+
+/// Everything reserved on a single object.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reservation {
+    pub energy_in: u32,
+    pub energy_out: u32,
+    pub work: u32
+}
+
+impl Reservation {
+    pub fn amount(&self, kind: ReservationKind) -> u32 {
+        match kind {
+            ReservationKind::EnergyIn => self.energy_in,
+            ReservationKind::EnergyOut => self.energy_out,
+            ReservationKind::Work => self.work
+        }
+    }
+
+    fn amount_mut(&mut self, kind: ReservationKind) -> &mut u32 {
+        match kind {
+            ReservationKind::EnergyIn => &mut self.energy_in,
+            ReservationKind::EnergyOut => &mut self.energy_out,
+            ReservationKind::Work => &mut self.work
+        }
+    }
+}
+
+/// What creeps and towers are about to do with each object, keyed by id.
+/// 
+/// Tasks consult it when picking a target, so creeps spread out instead of converging on one job.  
+/// It's cleared at the start of every tick, and rebuilt from the targets creeps keep in `Memory`, see `Task::restore`.
+pub struct Reservations {
+    ledger: RefCell<HashMap<String, Reservation>>
+}
+
+impl Reservations {
+    pub fn new() -> Reservations {
+        Reservations{
+            ledger: RefCell::new(HashMap::new())
+        }
+    }
+
+    pub fn clear(&self) {
+        self.ledger.borrow_mut().clear();
+    }
+
+    pub fn get(&self, id: &str) -> Reservation {
+        self.ledger.borrow().get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn amount(&self, id: &str, kind: ReservationKind) -> u32 {
+        self.ledger.borrow().get(id).map_or(0, |reservation| reservation.amount(kind))
+    }
+
+    pub fn reserve(&self, id: &str, kind: ReservationKind, amount: u32) {
+        let mut ledger = self.ledger.borrow_mut();
+        let reserved = ledger.entry(id.to_string()).or_insert_with(Reservation::default).amount_mut(kind);
+        *reserved += amount;
+    }
+
+    pub fn release(&self, id: &str, kind: ReservationKind, amount: u32) {
+        let mut ledger = self.ledger.borrow_mut();
+        let empty = match ledger.get_mut(id) {
+            Some(reservation) => {
+                let reserved = reservation.amount_mut(kind);
+                *reserved = reserved.saturating_sub(amount);
+
+                *reservation == Reservation::default()
+            },
+            None => false
+        };
+
+        if empty {
+            ledger.remove(id);
+        }
+    }
+
+    /// Points a creep's task at `target`, moving its reservation there.
+    pub fn lock(&self, memory: &mut CreepTaskMemory, kind: ReservationKind, target: &str, amount: u32) {
+        self.unlock(memory, kind);
+        self.reserve(target, kind, amount);
+
+        memory.target = Some(target.to_string());
+        memory.reserved = amount;
+    }
+
+    /// Frees a creep's reservation, returns the target it had.
+    pub fn unlock(&self, memory: &mut CreepTaskMemory, kind: ReservationKind) -> Option<String> {
+        let target = memory.target.take();
+        if let Some(ref id) = target {
+            self.release(id, kind, memory.reserved);
+        }
+        memory.reserved = 0;
+
+        target
+    }
+
+    /// The creep's intent went through, and the next tick's snapshot will show it.
+    /// Keeps the target and this tick's reservation, but stops `restore` from reserving it again.
+    pub fn settle(&self, memory: &mut CreepTaskMemory) {
+        memory.reserved = 0;
+    }
+
+    /// Frees the reservation of memory that was already removed from the creep.
+    pub fn free(&self, memory: &CreepTaskMemory, kind: ReservationKind) {
+        if let Some(ref id) = memory.target {
+            self.release(id, kind, memory.reserved);
+        }
+    }
+
+    /// Re-reserves a creep's target from its memory.
+    pub fn restore(&self, memory: &CreepTaskMemory, kind: ReservationKind) {
+        if let Some(ref id) = memory.target {
+            if memory.reserved > 0 {
+                self.reserve(id, kind, memory.reserved);
+            }
+        }
+    }
+}
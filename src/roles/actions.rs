@@ -0,0 +1,43 @@
+use std::error::Error;
+
+use screeps::constants::ReturnCode;
+
+use crate::world::{
+    CreepIntent,
+    CreepState,
+    StructureIntent,
+    World
+};
+
+/// Issues an intent, failing unless the creep only has to wait or can't take or give any more.
+/// Returns the code, so callers can tell those apart.
+pub fn intent(world: &dyn World, creep: &CreepState, intent: CreepIntent) -> Result<ReturnCode, Box<dyn Error>> {
+    let code = world.creep_intent(&creep.name, intent.clone());
+    match code {
+        ReturnCode::Ok | ReturnCode::NotInRange | ReturnCode::Tired | ReturnCode::Full | ReturnCode::NotEnough => Ok(code),
+        _ => Err(Box::from(format!("{} failed {:?} with {:?}", creep.name, intent, code)))
+    }
+}
+
+/// Walks to the closest spawn in the home room, which recycles the creep.
+pub fn recycle_at_home(world: &dyn World, creep: &CreepState, home: &str, reason: &str) -> Result<(), Box<dyn Error>> {
+    let spawn = world.spawns().into_iter()
+        .filter(|spawn| spawn.pos.room == home)
+        .min_by_key(|spawn| spawn.pos.range_to(&creep.pos));
+    let spawn = match spawn {
+        Some(spawn) => spawn,
+        None => return Ok(())
+    };
+
+    if creep.pos.range_to(&spawn.pos) > 1 {
+        intent(world, creep, CreepIntent::MoveTo(spawn.pos.clone()))?;
+        return Ok(());
+    }
+    match world.structure_intent(&spawn.id, StructureIntent::RecycleCreep(creep.id.clone())) {
+        ReturnCode::Ok => {
+            info!("recycling {} in {}, {}", creep.name, spawn.name, reason);
+            Ok(())
+        },
+        code => Err(Box::from(format!("failed to recycle in {}: {:?}", spawn.name, code)))
+    }
+}
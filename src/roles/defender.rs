@@ -0,0 +1,333 @@
+use std::{
+    cmp,
+    error::Error,
+    rc::Rc
+};
+
+use screeps::constants::Part;
+
+use crate::{
+    body::BodyTemplate,
+    military::{
+        defense,
+        threat::{
+            self,
+            Hostile,
+            RoomThreat,
+            ThreatAssessment
+        }
+    },
+    roles::actions::{
+        intent,
+        recycle_at_home
+    },
+    spawning::SpawnRequest,
+    traits::{
+        Role,
+        FlagProcessor
+    },
+    world::{
+        CreepIntent,
+        CreepState,
+        ObjectId,
+        Position,
+        RoomState,
+        StructureKind,
+        World
+    }
+};
+
+/// A room never gets more defenders of a kind than this.
+const MAX_DEFENDERS: u32 = 3;
+
+/// What a defender fights with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefenderKind {
+    Melee,
+    Ranged,
+    Healer
+}
+
+/// Defends its home room from hostiles the towers can't handle.
+///
+/// Melee and ranged defenders each take on half of the room's threat, healers half of the hostiles' damage,
+/// so bigger attacks get more of them, up to `MAX_DEFENDERS` of each.
+/// None are spawned while the towers out-damage every hostile, or against hostiles that can't hurt us.
+///
+/// Fighters stand on our ramparts in range of their target where they can, and chase it otherwise.
+/// Healers heal the most damaged creep in the room, and follow the fighters when nobody is hurt.
+/// Once the room is clear, defenders go back to a spawn to be recycled.
+pub struct RoleDefender {
+    kind: DefenderKind,
+    threats: Rc<ThreatAssessment>
+}
+
+impl RoleDefender {
+    pub fn new(kind: DefenderKind, threats: Rc<ThreatAssessment>) -> RoleDefender {
+        RoleDefender{
+            kind: kind,
+            threats: threats
+        }
+    }
+
+    /// How many defenders of this kind the room needs against the threat.
+    fn needed(&self, body: &[Part], threat: &RoomThreat) -> u32 {
+        let dangerous = threat.hostiles.iter().filter(|hostile| !hostile.kind.is_harmless());
+        let strength = cmp::max(threat::body_threat(body), 1);
+
+        let share = match self.kind {
+            DefenderKind::Melee | DefenderKind::Ranged => dangerous.map(|hostile| hostile.threat).sum::<u32>() / 2,
+            DefenderKind::Healer => dangerous.map(|hostile| hostile.damage).sum::<u32>() / 2
+        };
+        cmp::min((share + strength - 1) / strength, MAX_DEFENDERS)
+    }
+
+    /// Attacks the closest hostile, from a rampart in range of it if there's a free one.
+    fn fight(&self, world: &dyn World, creep: &CreepState, hostiles: &[&Hostile]) -> Result<(), Box<dyn Error>> {
+        let (range, attack): (u32, fn(ObjectId) -> CreepIntent) = match self.kind {
+            DefenderKind::Melee => (1, CreepIntent::Attack),
+            _ => (3, CreepIntent::RangedAttack)
+        };
+        let target = match hostiles.iter().min_by_key(|hostile| hostile.creep.pos.range_to(&creep.pos)) {
+            Some(target) => &target.creep,
+            None => return Ok(())
+        };
+
+        let others: Vec<Position> = world.my_creeps().into_iter()
+            .filter(|other| other.name != creep.name)
+            .map(|other| other.pos)
+            .collect();
+        let rampart = world.structures(&target.pos.room).into_iter()
+            .filter(|structure| structure.kind == StructureKind::Rampart && structure.my)
+            .filter(|rampart| rampart.pos.range_to(&target.pos) <= range && !others.contains(&rampart.pos))
+            .min_by_key(|rampart| rampart.pos.range_to(&creep.pos));
+
+        let goal = match rampart {
+            Some(ref rampart) if rampart.pos != creep.pos => Some(rampart.pos.clone()),
+            Some(_) => None,
+            None if creep.pos.range_to(&target.pos) > range => Some(target.pos.clone()),
+            None => None
+        };
+        if let Some(goal) = goal {
+            intent(world, creep, CreepIntent::MoveTo(goal))?;
+        }
+
+        if creep.pos.range_to(&target.pos) <= range {
+            intent(world, creep, attack(target.id.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Heals the most damaged of our creeps in the room, or follows the closest fighter.
+    fn heal(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        let ours: Vec<CreepState> = world.my_creeps().into_iter()
+            .filter(|other| other.pos.room == creep.pos.room && !other.spawning)
+            .collect();
+
+        let patient = ours.iter()
+            .filter(|other| other.hits < other.hits_max)
+            .min_by_key(|other| (other.hits * 100 / cmp::max(other.hits_max, 1), other.pos.range_to(&creep.pos)));
+        if let Some(patient) = patient {
+            let range = patient.pos.range_to(&creep.pos);
+            if range > 1 {
+                intent(world, creep, CreepIntent::MoveTo(patient.pos.clone()))?;
+            }
+            let heal = match range {
+                0..=1 => CreepIntent::Heal(patient.id.clone()),
+                2..=3 => CreepIntent::RangedHeal(patient.id.clone()),
+                _ => return Ok(())
+            };
+            intent(world, creep, heal)?;
+            return Ok(());
+        }
+
+        let fighter = ours.iter()
+            .filter(|other| other.active_parts(Part::Attack) > 0 || other.active_parts(Part::RangedAttack) > 0)
+            .min_by_key(|other| other.pos.range_to(&creep.pos));
+        if let Some(fighter) = fighter {
+            if fighter.pos.range_to(&creep.pos) > 1 {
+                intent(world, creep, CreepIntent::MoveTo(fighter.pos.clone()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FlagProcessor for RoleDefender {}
+
+impl Role for RoleDefender {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            DefenderKind::Melee => "melee",
+            DefenderKind::Ranged => "ranged",
+            DefenderKind::Healer => "healer"
+        }
+    }
+
+    fn limit(&self) -> i32 {
+        MAX_DEFENDERS as i32
+    }
+
+    fn body(&self) -> BodyTemplate {
+        let part = match self.kind {
+            DefenderKind::Melee => Part::Attack,
+            DefenderKind::Ranged => Part::RangedAttack,
+            DefenderKind::Healer => Part::Heal
+        };
+        BodyTemplate::new(&[(part, 1), (Part::Move, 1)])
+            .size(1, 25)
+    }
+
+    fn run_count(&self, world: &dyn World) -> i32 {
+        world.memory().role(self.name()).run_count as i32
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        world.memory().role(self.name()).run_count += 1;
+        if creep.spawning {
+            return Ok(());
+        }
+
+        let home = world.memory().creep(&creep.name).home.clone();
+        let home = if home.is_empty() { creep.pos.room.clone() } else { home };
+        let threat = self.threats.room(&home);
+        let hostiles: Vec<&Hostile> = threat.hostiles.iter().filter(|hostile| !hostile.kind.is_harmless()).collect();
+
+        if hostiles.is_empty() {
+            return recycle_at_home(world, creep, &home, &format!("{} is clear", home));
+        }
+        match self.kind {
+            DefenderKind::Healer => self.heal(world, creep),
+            _ => self.fight(world, creep, &hostiles)
+        }
+    }
+
+    /// Defenders jump ahead of the economy while the room is under attack,
+    /// even the last one of a kind is more urgent than a miner.
+    fn spawn_priority(&self) -> i32 {
+        match self.kind {
+            DefenderKind::Healer => 6,
+            _ => 5
+        }
+    }
+
+    fn spawn_request(&self, world: &dyn World, room: &RoomState, count: u32) -> Option<SpawnRequest> {
+        let threat = self.threats.room(&room.name);
+        if threat.hostiles.iter().all(|hostile| hostile.kind.is_harmless()) ||
+           defense::towers_win(&world.structures(&room.name), &threat) {
+            return None;
+        }
+
+        let body = self.next_creep(room.energy_capacity_available);
+        if count >= self.needed(&body, &threat) {
+            return None;
+        }
+
+        Some(SpawnRequest{
+            role: self.name(),
+            room: room.name.clone(),
+            body: body,
+            priority: self.spawn_priority() + count as i32,
+            bootstrap: false
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        ControllerState
+    };
+
+    fn hostile(name: &str, body: &[Part]) -> CreepState {
+        let mut creep = mock::creep(name, Position::new(25, 25, "W1N1"), body);
+        creep.owner = "player".to_string();
+        creep.my = false;
+        creep
+    }
+
+    fn threat(hostiles: &[CreepState]) -> RoomThreat {
+        RoomThreat{
+            hostiles: hostiles.iter().map(threat::assess).collect(),
+            ..RoomThreat::default()
+        }
+    }
+
+    fn defender(kind: DefenderKind) -> RoleDefender {
+        RoleDefender::new(kind, Rc::new(ThreatAssessment::new()))
+    }
+
+    #[test]
+    fn needs_half_the_threat() {
+        // 150 damage per defender
+        let body = [Part::Attack; 5];
+        let melee = defender(DefenderKind::Melee);
+
+        assert_eq!(melee.needed(&body, &threat(&[])), 0);
+        assert_eq!(melee.needed(&body, &threat(&[hostile("small", &[Part::Attack])])), 1);
+        assert_eq!(melee.needed(&body, &threat(&[hostile("even", &[Part::Attack; 10])])), 1);
+        assert_eq!(melee.needed(&body, &threat(&[hostile("more", &[Part::Attack; 11])])), 2);
+        assert_eq!(melee.needed(&body, &threat(&[hostile("huge", &[Part::Attack; 50])])), MAX_DEFENDERS);
+    }
+
+    #[test]
+    fn ignores_harmless_hostiles() {
+        let melee = defender(DefenderKind::Melee);
+        let hostiles = [hostile("scout", &[Part::Move]), hostile("harvester", &[Part::Work, Part::Carry])];
+
+        assert_eq!(melee.needed(&[Part::Attack], &threat(&hostiles)), 0);
+    }
+
+    #[test]
+    fn healers_match_damage() {
+        let healer = defender(DefenderKind::Healer);
+        // 60 heal per healer
+        let body = [Part::Heal; 5];
+
+        assert_eq!(healer.needed(&body, &threat(&[hostile("healer", &[Part::Heal; 10])])), 0);
+        assert_eq!(healer.needed(&body, &threat(&[hostile("attacker", &[Part::Attack; 4])])), 1);
+        assert_eq!(healer.needed(&body, &threat(&[hostile("attacker", &[Part::Attack; 5])])), 2);
+    }
+
+    #[test]
+    fn spawns_ahead_of_economy() {
+        let world = MockWorld::new();
+        let room = RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 1300,
+            energy_capacity_available: 1300,
+            controller: Some(ControllerState{
+                id: "controller".to_string(),
+                pos: Position::new(40, 40, "W1N1"),
+                my: true,
+                level: 4,
+                progress: 0,
+                progress_total: 405000,
+                safe_mode: 0,
+                safe_mode_available: 0,
+                safe_mode_cooldown: 0
+            })
+        };
+        world.add_room(room.clone());
+        world.add_hostile(hostile("attacker", &[Part::Attack; 50]));
+        let threats = Rc::new(ThreatAssessment::new());
+        threats.update(&world);
+
+        for &kind in [DefenderKind::Melee, DefenderKind::Ranged, DefenderKind::Healer].iter() {
+            let defender = RoleDefender::new(kind, threats.clone());
+            let priorities: Vec<i32> = (0..MAX_DEFENDERS)
+                .map(|count| defender.spawn_request(&world, &room, count).unwrap().priority)
+                .collect();
+
+            assert_eq!(priorities[0], defender.spawn_priority());
+            // the miner is 12, the hauler 11
+            assert!(priorities.iter().all(|priority| *priority < 11), "{:?}: {:?}", kind, priorities);
+            assert!(defender.spawn_request(&world, &room, MAX_DEFENDERS).is_none());
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use screeps::constants::Part;
+
+use crate::body::BodyTemplate;
+use super::priority::RoleDefinition;
+
+/// Every role built from a definition.  
+/// Add a new entry here to add a role.
+pub fn definitions() -> Vec<RoleDefinition> {
+    vec![
+        // Prioritizes refilling empty structures.
+        RoleDefinition{
+            name: "harvester",
+            refill: vec!["pickup", "withdraw", "harvest"],
+            tasks: vec!["refill", "build", "upgrade"],
+            body: BodyTemplate::new(&[(Part::Work, 1), (Part::Carry, 1), (Part::Move, 1)])
+                      .size(1, 5),
+            limit: 2,
+            spawn_priority: 10,
+            essential: true
+        },
+        // Prioritizes building construction sites.
+        RoleDefinition{
+            name: "builder",
+            refill: vec!["pickup", "withdraw", "harvest"],
+            tasks: vec!["build", "refill", "upgrade"],
+            body: BodyTemplate::new(&[(Part::Work, 1), (Part::Carry, 1), (Part::Move, 1)])
+                      .size(1, 5),
+            limit: 2,
+            spawn_priority: 15,
+            essential: false
+        },
+        // Prioritizes upgrading the Room Controller.
+        RoleDefinition{
+            name: "upgrader",
+            refill: vec!["supply", "pickup", "withdraw", "harvest"],
+            tasks: vec!["upgrade"],
+            body: BodyTemplate::new(&[(Part::Work, 1), (Part::Carry, 1), (Part::Move, 1)])
+                      .size(1, 6),
+            limit: 2,
+            spawn_priority: 15,
+            essential: false
+        }
+    ]
+}
@@ -0,0 +1,143 @@
+use std::{
+    cmp,
+    error::Error,
+    rc::Rc
+};
+
+use screeps::constants::Part;
+
+use crate::{
+    body::BodyTemplate,
+    logistics::is_upgrade_container,
+    spawning::SpawnRequest,
+    tasks::registry::TaskRegistry,
+    traits::{
+        Role,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepState,
+        RoomState,
+        StructureKind,
+        World
+    }
+};
+
+/// Moves energy from providers to requesters, see `logistics::Logistics`.
+/// 
+/// A room gets a hauler for every container that's being filled, up to `limit`, so none
+/// are spawned before the miners have placed theirs.
+pub struct RoleHauler {
+    tasks: Rc<TaskRegistry>
+}
+
+impl RoleHauler {
+    pub fn new(tasks: Rc<TaskRegistry>) -> RoleHauler {
+        RoleHauler{
+            tasks: tasks
+        }
+    }
+}
+
+impl FlagProcessor for RoleHauler {}
+
+impl Role for RoleHauler {
+    fn name(&self) -> &'static str {
+        "hauler"
+    }
+
+    /// At most one for each source container.
+    fn limit(&self) -> i32 {
+        2
+    }
+
+    fn body(&self) -> BodyTemplate {
+        BodyTemplate::new(&[(Part::Carry, 2), (Part::Move, 1)])
+            .size(1, 10)
+    }
+
+    fn run_count(&self, world: &dyn World) -> i32 {
+        world.memory().role(self.name()).run_count as i32
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        world.memory().role(self.name()).run_count += 1;
+
+        match self.tasks.run("haul", world, creep)? {
+            TaskStatus::Failed(reason) => Err(Box::from(reason)),
+            _ => Ok(())
+        }
+    }
+
+    fn spawn_priority(&self) -> i32 {
+        11
+    }
+
+    fn spawn_request(&self, world: &dyn World, room: &RoomState, count: u32) -> Option<SpawnRequest> {
+        let containers = world.structures(&room.name).iter()
+            .filter(|structure| structure.kind == StructureKind::Container &&
+                                !is_upgrade_container(structure, room.controller.as_ref()))
+            .count() as i32;
+
+        if (count as i32) < cmp::min(containers, self.limit()) {
+            Some(SpawnRequest{
+                role: self.name(),
+                room: room.name.clone(),
+                body: self.next_creep(room.energy_capacity_available),
+                priority: (count as i32 + 1) * self.spawn_priority(),
+                bootstrap: false
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        Position
+    };
+
+    fn world_with_containers(containers: u32) -> (MockWorld, RoomState) {
+        let world = MockWorld::new();
+        let room = RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: None
+        };
+        world.add_room(room.clone());
+        for i in 0..containers {
+            world.add_structure(mock::structure(&format!("container{}", i), StructureKind::Container, Position::new(10 + i * 5, 10, "W1N1")));
+        }
+        (world, room)
+    }
+
+    #[test]
+    fn one_per_container() {
+        let hauler = RoleHauler::new(Rc::new(TaskRegistry::new()));
+
+        let (world, room) = world_with_containers(0);
+        assert!(hauler.spawn_request(&world, &room, 0).is_none());
+
+        let (world, room) = world_with_containers(1);
+        assert_eq!(hauler.spawn_request(&world, &room, 0).unwrap().priority, 11);
+        assert!(hauler.spawn_request(&world, &room, 1).is_none());
+    }
+
+    #[test]
+    fn caps_at_limit() {
+        let hauler = RoleHauler::new(Rc::new(TaskRegistry::new()));
+        let (world, room) = world_with_containers(3);
+
+        assert!(hauler.spawn_request(&world, &room, 1).is_some());
+        assert!(hauler.spawn_request(&world, &room, 2).is_none());
+    }
+}
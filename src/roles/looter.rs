@@ -0,0 +1,154 @@
+use std::error::Error;
+
+use screeps::constants::{
+    Part,
+    ReturnCode
+};
+
+use crate::{
+    body::BodyTemplate,
+    roles::actions::{
+        intent,
+        recycle_at_home
+    },
+    spawning::SpawnRequest,
+    traits::{
+        Role,
+        FlagProcessor
+    },
+    world::{
+        CreepIntent,
+        CreepState,
+        Position,
+        RemainsKind,
+        RoomState,
+        StructureKind,
+        StructureState,
+        World
+    }
+};
+
+/// Empties the ruins of invader cores destroyed by strike groups from its room, see `military::invaders`.
+///
+/// One is spawned in a room with a storage while any of those rooms has loot left.
+/// It fills up from the fullest ruin with whatever it holds, brings it back to the storage,
+/// and is recycled once there's nothing left to loot.
+pub struct RoleLooter;
+
+impl RoleLooter {
+    pub fn new() -> RoleLooter {
+        RoleLooter{}
+    }
+
+    /// Where the core was, in a room cleared by strike groups from `home` that still has loot.
+    fn loot(&self, world: &dyn World, home: &str) -> Option<Position> {
+        world.memory().military.invaders.values()
+            .filter(|invader| invader.loot && invader.home.as_ref().map_or(false, |room| room == home))
+            .filter_map(|invader| invader.pos.clone())
+            .next()
+    }
+
+    fn storage(&self, world: &dyn World, room: &str) -> Option<StructureState> {
+        world.structures(room).into_iter()
+            .find(|structure| structure.kind == StructureKind::Storage && structure.my)
+    }
+
+    /// Fills up from the fullest ruin in the room, returns whether the looter should keep looting.
+    fn fill(&self, world: &dyn World, creep: &CreepState, loot: &Position) -> Result<bool, Box<dyn Error>> {
+        if creep.pos.room != loot.room {
+            intent(world, creep, CreepIntent::MoveTo(loot.clone()))?;
+            return Ok(true);
+        }
+
+        let ruin = world.remains(&loot.room).into_iter()
+            .filter(|remains| remains.kind == RemainsKind::Ruin && remains.store > 0)
+            .max_by_key(|remains| remains.store);
+        let ruin = match ruin {
+            Some(ruin) => ruin,
+            None => return Ok(false)
+        };
+
+        if creep.pos.range_to(&ruin.pos) > 1 {
+            intent(world, creep, CreepIntent::MoveTo(ruin.pos.clone()))?;
+            return Ok(true);
+        }
+        Ok(intent(world, creep, CreepIntent::Loot(ruin.id.clone()))? != ReturnCode::Full)
+    }
+}
+
+impl FlagProcessor for RoleLooter {}
+
+impl Role for RoleLooter {
+    fn name(&self) -> &'static str {
+        "looter"
+    }
+
+    fn limit(&self) -> i32 {
+        1
+    }
+
+    fn body(&self) -> BodyTemplate {
+        BodyTemplate::new(&[(Part::Carry, 1), (Part::Move, 1)])
+            .size(1, 16)
+    }
+
+    fn run_count(&self, world: &dyn World) -> i32 {
+        world.memory().role(self.name()).run_count as i32
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        world.memory().role(self.name()).run_count += 1;
+        if creep.spawning {
+            return Ok(());
+        }
+
+        let (home, looting) = {
+            let mut memory = world.memory();
+            let creep_memory = memory.creep(&creep.name);
+            (creep_memory.home.clone(), creep_memory.harvesting)
+        };
+        let home = if home.is_empty() { creep.pos.room.clone() } else { home };
+        let loot = self.loot(world, &home);
+
+        let looting = match loot {
+            Some(ref loot) if looting => self.fill(world, creep, loot)?,
+            _ => {
+                let storage = match self.storage(world, &home) {
+                    Some(storage) => storage,
+                    None => return recycle_at_home(world, creep, &home, "nothing left to loot")
+                };
+                if creep.pos.range_to(&storage.pos) > 1 {
+                    intent(world, creep, CreepIntent::MoveTo(storage.pos.clone()))?;
+                    false
+                } else if intent(world, creep, CreepIntent::Unload(storage.id.clone()))? != ReturnCode::NotEnough {
+                    false
+                } else if loot.is_none() {
+                    return recycle_at_home(world, creep, &home, "nothing left to loot");
+                } else {
+                    true
+                }
+            }
+        };
+        world.memory().creep(&creep.name).harvesting = looting;
+        Ok(())
+    }
+
+    /// Loot waits until the economy is taken care of.
+    fn spawn_priority(&self) -> i32 {
+        30
+    }
+
+    fn spawn_request(&self, world: &dyn World, room: &RoomState, count: u32) -> Option<SpawnRequest> {
+        if count > 0 || self.loot(world, &room.name).is_none() || self.storage(world, &room.name).is_none() {
+            return None;
+        }
+
+        Some(SpawnRequest{
+            role: self.name(),
+            room: room.name.clone(),
+            body: self.next_creep(room.energy_capacity_available),
+            priority: self.spawn_priority(),
+            bootstrap: false
+        })
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    cmp,
+    error::Error,
+    rc::Rc
+};
+
+use screeps::constants::Part;
+
+use crate::{
+    body::BodyTemplate,
+    spawning::SpawnRequest,
+    tasks::{
+        mine,
+        registry::TaskRegistry
+    },
+    traits::{
+        Role,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepState,
+        RoomState,
+        World
+    }
+};
+
+/// Ticks a source takes to regenerate.
+const SOURCE_REGEN_TIME: u32 = 300;
+/// Energy a WORK part harvests per tick.
+const HARVEST_POWER: u32 = 2;
+/// Ticks it takes to spawn a single part.
+const CREEP_SPAWN_TIME: u32 = 3;
+
+/// WORK parts needed to empty a source right before it regenerates.
+fn work_needed(energy_capacity: u32) -> u32 {
+    (energy_capacity + SOURCE_REGEN_TIME * HARVEST_POWER - 1) / (SOURCE_REGEN_TIME * HARVEST_POWER)
+}
+
+/// Ticks per tile on plain terrain, for a creep carrying nothing.
+fn ticks_per_tile(body: &[Part]) -> u32 {
+    let moves = body.iter().filter(|part| **part == Part::Move).count() as u32;
+    let weight = body.iter().filter(|part| **part != Part::Move && **part != Part::Carry).count() as u32;
+
+    match moves {
+        0 => u32::max_value(),
+        moves => cmp::max(1, (weight + moves - 1) / moves)
+    }
+}
+
+/// Parks a creep on every source, which drops what it harvests into a container.
+/// 
+/// A miner is sized to drain its source exactly once per regeneration cycle.  
+/// Its replacement is requested ahead of its death, to arrive just as it dies.
+pub struct RoleMiner {
+    tasks: Rc<TaskRegistry>
+}
+
+impl RoleMiner {
+    pub fn new(tasks: Rc<TaskRegistry>) -> RoleMiner {
+        RoleMiner{
+            tasks: tasks
+        }
+    }
+
+    fn template(&self, energy_capacity: u32) -> BodyTemplate {
+        BodyTemplate::new(&[(Part::Work, 1)])
+            .fixed(&[(Part::Carry, 1), (Part::Move, 1)])
+            .size(1, work_needed(energy_capacity))
+    }
+}
+
+impl FlagProcessor for RoleMiner {}
+
+impl Role for RoleMiner {
+    fn name(&self) -> &'static str {
+        "miner"
+    }
+
+    /// Per source: the one mining it, and its replacement on the way.
+    fn limit(&self) -> i32 {
+        2
+    }
+
+    fn body(&self) -> BodyTemplate {
+        self.template(3000)
+    }
+
+    fn run_count(&self, world: &dyn World) -> i32 {
+        world.memory().role(self.name()).run_count as i32
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        world.memory().role(self.name()).run_count += 1;
+
+        match self.tasks.run("mine", world, creep)? {
+            TaskStatus::Failed(reason) => Err(Box::from(reason)),
+            _ => Ok(())
+        }
+    }
+
+    fn spawn_priority(&self) -> i32 {
+        12
+    }
+
+    /// Requests a miner for the first source whose miners all die before a replacement could get there.  
+    /// Waits until the room can afford a miner that drains the source,
+    /// and never has more than `limit` miners per source.
+    fn spawn_request(&self, world: &dyn World, room: &RoomState, count: u32) -> Option<SpawnRequest> {
+        let sources = world.sources(&room.name);
+        if count as i32 >= self.limit() * sources.len() as i32 {
+            return None;
+        }
+
+        let spawns: Vec<_> = world.spawns().into_iter().filter(|spawn| spawn.pos.room == room.name).collect();
+        let mut covered = 0;
+
+        for source in sources {
+            let template = self.template(source.energy_capacity);
+            let body = match template.repeats_for(room.energy_capacity_available) {
+                Some(repeats) if repeats >= work_needed(source.energy_capacity) => template.generate(room.energy_capacity_available)?,
+                _ => continue
+            };
+
+            let distance = spawns.iter().map(|spawn| spawn.pos.range_to(&source.pos)).min().unwrap_or(0);
+            let lead_time = body.len() as u32 * CREEP_SPAWN_TIME + distance.saturating_mul(ticks_per_tile(&body));
+
+            let healthy = mine::miners(world, &source.id).iter()
+                .any(|miner| miner.spawning || miner.ticks_to_live > lead_time);
+            if healthy {
+                covered += 1;
+                continue;
+            }
+
+            return Some(SpawnRequest{
+                role: self.name(),
+                room: room.name.clone(),
+                body: body,
+                priority: (covered + 1) * self.spawn_priority(),
+                bootstrap: false
+            });
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::MockWorld,
+        Position,
+        SourceState,
+        SpawnState
+    };
+
+    fn world_with_source() -> (MockWorld, RoomState) {
+        let world = MockWorld::new();
+        let room = RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 800,
+            energy_capacity_available: 800,
+            controller: None
+        };
+        world.add_room(room.clone());
+        world.add_spawn(SpawnState{
+            id: "spawn".to_string(),
+            name: "Spawn1".to_string(),
+            pos: Position::new(25, 25, "W1N1"),
+            spawning: false
+        });
+        world.add_source(SourceState{
+            id: "source".to_string(),
+            pos: Position::new(10, 10, "W1N1"),
+            energy: 3000,
+            energy_capacity: 3000,
+            ticks_to_regeneration: 300
+        });
+        (world, room)
+    }
+
+    #[test]
+    fn requests_miner_for_uncovered_source() {
+        let (world, room) = world_with_source();
+        let request = RoleMiner::new(Rc::new(TaskRegistry::new())).spawn_request(&world, &room, 0).unwrap();
+
+        assert_eq!(request.body.iter().filter(|part| **part == Part::Work).count(), 5);
+        assert_eq!(request.priority, 12);
+    }
+
+    #[test]
+    fn caps_miners_per_source() {
+        let (world, room) = world_with_source();
+        let miner = RoleMiner::new(Rc::new(TaskRegistry::new()));
+
+        // none of them is mining the source, but the room has enough already
+        assert!(miner.spawn_request(&world, &room, 1).is_some());
+        assert!(miner.spawn_request(&world, &room, 2).is_none());
+    }
+}
@@ -1,6 +1,18 @@
-/// Prioritizes building construction sites.
-pub mod builder;
-/// Prioritizes refilling empty structures.
-pub mod harvester;
-/// Prioritizes upgrading the Room Controller.
-pub mod upgrader;
\ No newline at end of file
+/// Creep actions shared by the roles.
+pub mod actions;
+/// The roles built into the bot.
+pub mod definitions;
+/// Defenders, fighting hostiles in their home room.
+pub mod defender;
+/// Haulers, moving energy around the room.
+pub mod hauler;
+/// Static miners, one for each source.
+pub mod miner;
+/// Looters, emptying the ruins of invader cores.
+pub mod looter;
+/// A role that tries a list of tasks in order.
+pub mod priority;
+/// Looking up roles by name.
+pub mod registry;
+/// Members of squads.
+pub mod squad;
@@ -0,0 +1,133 @@
+use std::{
+    error::Error,
+    rc::Rc
+};
+
+use crate::{
+    body::BodyTemplate,
+    tasks::registry::TaskRegistry,
+    traits::{
+        Role,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepState,
+        World
+    }
+};
+
+/// Declares a role that refills itself with one list of tasks, then spends its energy with another.
+#[derive(Clone)]
+pub struct RoleDefinition {
+    pub name: &'static str,
+    /// Tried in order when the creep is empty, until it's full.
+    pub refill: Vec<&'static str>,
+    /// Tried in order when the creep is full, until one of them runs.
+    pub tasks: Vec<&'static str>,
+    pub body: BodyTemplate,
+    /// How many creeps of this role each room has.
+    pub limit: i32,
+    /// The lower this number, the more creeps there will be overall
+    pub spawn_priority: i32,
+    /// Whether a room can't function without this role.
+    pub essential: bool
+}
+
+/// A role driven by a `RoleDefinition`.
+/// 
+/// A creep refills itself when empty, otherwise tries the definition's tasks in order.  
+/// Each tick, the first task that's in progress wins.
+pub struct PriorityRole {
+    definition: RoleDefinition,
+    tasks: Rc<TaskRegistry>
+}
+
+impl PriorityRole {
+    /// Checks that every task the definition uses is registered.
+    pub fn new(definition: RoleDefinition, tasks: Rc<TaskRegistry>) -> Result<PriorityRole, Box<dyn Error>> {
+        if let Some(name) = definition.refill.iter()
+                                             .chain(definition.tasks.iter())
+                                             .find(|name| !tasks.contains(name)) {
+            return Err(Box::from(format!("role {} uses unknown task {}", definition.name, name)));
+        }
+
+        Ok(PriorityRole{
+            definition: definition,
+            tasks: tasks
+        })
+    }
+
+    /// Runs tasks until one of them is in progress.
+    fn run_tasks(&self, world: &dyn World, creep: &CreepState, tasks: &[&'static str]) -> Result<(), Box<dyn Error>> {
+        let mut reasons = Vec::new();
+        for task in tasks.iter() {
+            match self.tasks.run(task, world, creep)? {
+                TaskStatus::InProgress => return Ok(()),
+                TaskStatus::Failed(reason) => reasons.push(format!("{}: {}", task, reason)),
+                TaskStatus::Done | TaskStatus::Blocked => ()
+            }
+        }
+
+        match reasons.len() {
+            0 => Ok(()), // idle, nothing to do
+            _ => Err(Box::from(reasons.join(", ")))
+        }
+    }
+}
+
+impl FlagProcessor for PriorityRole {}
+
+impl Role for PriorityRole {
+    fn name(&self) -> &'static str {
+        self.definition.name
+    }
+
+    fn limit(&self) -> i32 {
+        self.definition.limit
+    }
+
+    fn body(&self) -> BodyTemplate {
+        self.definition.body.clone()
+    }
+
+    fn run_count(&self, world: &dyn World) -> i32 {
+        world.memory().role(self.name()).run_count as i32
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        world.memory().role(self.name()).run_count += 1;
+
+        let (harvesting, switched) = {
+            let mut memory = world.memory();
+            let creep_memory = memory.creep(&creep.name);
+            let was_harvesting = creep_memory.harvesting;
+            creep_memory.harvesting = match creep.energy {
+                0 => true,
+                carry if carry >= creep.carry_capacity => false,
+                _ => creep_memory.harvesting
+            };
+
+            (creep_memory.harvesting, creep_memory.harvesting != was_harvesting)
+        };
+
+        if switched {
+            let left = if harvesting { &self.definition.tasks } else { &self.definition.refill };
+            self.tasks.leave(world, creep, left);
+        }
+
+        if harvesting {
+            self.run_tasks(world, creep, &self.definition.refill)
+        } else {
+            self.run_tasks(world, creep, &self.definition.tasks)
+        }
+    }
+
+    fn spawn_priority(&self) -> i32 {
+        self.definition.spawn_priority
+    }
+
+    fn essential(&self) -> bool {
+        self.definition.essential
+    }
+}
@@ -0,0 +1,58 @@
+use std::rc::Rc;
+
+use hashbrown::HashMap;
+
+use crate::{
+    tasks::registry::TaskRegistry,
+    traits::Role
+};
+use super::{
+    definitions::definitions,
+    priority::PriorityRole
+};
+
+/// Every role the bot knows, keyed by `Role::name`.
+pub struct RoleRegistry {
+    roles: HashMap<&'static str, Box<dyn Role>>
+}
+
+impl RoleRegistry {
+    pub fn new() -> RoleRegistry {
+        RoleRegistry{
+            roles: HashMap::new()
+        }
+    }
+
+    /// Builds every role in `definitions`, skipping the ones that use unknown tasks.
+    pub fn from_definitions(tasks: &Rc<TaskRegistry>) -> RoleRegistry {
+        let mut registry = RoleRegistry::new();
+
+        for definition in definitions() {
+            match PriorityRole::new(definition, tasks.clone()) {
+                Ok(role) => registry.register(Box::new(role)),
+                Err(err) => error!("failed to create role: {}", err)
+            }
+        }
+
+        registry
+    }
+
+    /// Adds a role, replacing any other with the same name.
+    pub fn register(&mut self, role: Box<dyn Role>) {
+        if self.roles.insert(role.name(), role).is_some() {
+            warn!("a role was registered twice");
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Box<dyn Role>> {
+        self.roles.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&&'static str, &Box<dyn Role>)> {
+        self.roles.iter()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Box<dyn Role>> {
+        self.roles.values()
+    }
+}
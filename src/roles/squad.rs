@@ -0,0 +1,88 @@
+use std::error::Error;
+
+use crate::{
+    body::BodyTemplate,
+    military::squad::{
+        self,
+        Slot
+    },
+    roles::actions::recycle_at_home,
+    spawning::SpawnRequest,
+    traits::{
+        Role,
+        FlagProcessor
+    },
+    world::{
+        CreepState,
+        RoomState,
+        World
+    }
+};
+
+/// Members of squads, see `military::squad::Squads`, which moves them.
+///
+/// Spawned one at a time for the squads forming in the room, with the body of the slot they fill.
+/// Members left without a squad, because it was removed while they were spawning, are recycled.
+pub struct RoleSquad;
+
+impl RoleSquad {
+    pub fn new() -> RoleSquad {
+        RoleSquad{}
+    }
+}
+
+impl FlagProcessor for RoleSquad {}
+
+impl Role for RoleSquad {
+    fn name(&self) -> &'static str {
+        squad::ROLE
+    }
+
+    /// Up to a quad at a time, more squads only wait longer.
+    fn limit(&self) -> i32 {
+        4
+    }
+
+    fn body(&self) -> BodyTemplate {
+        Slot::Attacker.body()
+    }
+
+    fn run_count(&self, world: &dyn World) -> i32 {
+        world.memory().role(self.name()).run_count as i32
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>> {
+        world.memory().role(self.name()).run_count += 1;
+        if creep.spawning {
+            return Ok(());
+        }
+
+        let (home, member) = {
+            let mut memory = world.memory();
+            let member = memory.military.squads.values().any(|squad| squad.members.contains(&creep.name));
+            (memory.creep(&creep.name).home.clone(), member)
+        };
+        if member {
+            return Ok(());
+        }
+        let home = if home.is_empty() { creep.pos.room.clone() } else { home };
+        recycle_at_home(world, creep, &home, "it has no squad")
+    }
+
+    fn spawn_priority(&self) -> i32 {
+        20
+    }
+
+    fn spawn_request(&self, world: &dyn World, room: &RoomState, count: u32) -> Option<SpawnRequest> {
+        let slot = squad::next_member(world, &room.name)?;
+        let body = slot.body();
+
+        Some(SpawnRequest{
+            role: self.name(),
+            room: room.name.clone(),
+            body: body.generate(room.energy_capacity_available).unwrap_or_else(|| body.minimal()),
+            priority: (count as i32 + 1) * self.spawn_priority(),
+            bootstrap: false
+        })
+    }
+}
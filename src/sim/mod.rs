@@ -0,0 +1,738 @@
+use std::{
+    cmp,
+    env,
+    process
+};
+
+use hashbrown::HashMap;
+use screeps::constants::Part;
+
+use crate::bot::Bot;
+use crate::world::{
+    mock,
+    part_cost,
+    CreepIntent,
+    CreepState,
+    MockWorld,
+    Position,
+    RemainsKind,
+    RemainsState,
+    ResourceState,
+    RoomTerrain,
+    SpawnState,
+    StructureIntent,
+    StructureKind,
+    Terrain,
+    World
+};
+
+/// Loading a room to simulate.
+pub mod room;
+
+use self::room::RoomDescription;
+
+/// Progress needed to reach the next controller level.
+pub fn progress_total(level: u32) -> u32 {
+    match level {
+        1 => 200,
+        2 => 45_000,
+        3 => 135_000,
+        4 => 405_000,
+        5 => 1_215_000,
+        6 => 3_645_000,
+        7 => 10_935_000,
+        _ => 0
+    }
+}
+
+const SOURCE_REGEN_TIME: u32 = 300;
+const TOMBSTONE_DECAY_PER_PART: u32 = 5;
+const SPAWN_REGEN_LIMIT: u32 = 300;
+const CREEP_SPAWN_TIME: u32 = 3;
+const SAFE_MODE_DURATION: u32 = 20_000;
+const SAFE_MODE_COOLDOWN: u32 = 50_000;
+
+/// Runs the bot against a single simulated room.
+/// 
+/// Intents are resolved at the end of every tick, at a simplified fidelity:
+///   * creeps step one tile towards their target, with fatigue but no pathfinding
+///   * harvesting, building, upgrading and transferring move energy the way the game does
+///   * spawns refill themselves up to 300 energy, and creeps age and die
+///   * towers spend energy, but only repairing has an effect
+pub struct Simulator {
+    world: MockWorld,
+    room: String,
+    terrain: RoomTerrain,
+    /// Creeps being spawned: (spawn name, ticks left)
+    spawning: HashMap<String, u32>,
+    idle: HashMap<String, u32>,
+    report: Report
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub ticks: u32,
+    pub level: u32,
+    /// The tick each controller level was reached at.
+    pub level_reached: HashMap<u32, u32>,
+    /// The longest time a creep did nothing, and the creep.
+    pub max_idle: (u32, String),
+    pub creeps_spawned: u32,
+    pub creeps_alive: u32
+}
+
+impl Report {
+    /// The expectations that weren't met, as messages.
+    /// `expect_rcl` is a controller level and the tick it must be reached by,
+    /// `max_idle` how long a creep may do nothing.
+    pub fn failures(&self, expect_rcl: Option<(u32, u32)>, max_idle: Option<u32>) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some((level, tick)) = expect_rcl {
+            match self.level_reached.get(&level) {
+                Some(reached) if *reached <= tick => (),
+                _ if self.level >= level && !self.level_reached.contains_key(&level) => (),
+                _ => failures.push(format!("RCL{} was not reached within {} ticks", level, tick))
+            }
+        }
+        if let Some(limit) = max_idle {
+            if self.max_idle.0 >= limit {
+                failures.push(format!("{} idled for {} ticks", self.max_idle.1, self.max_idle.0));
+            }
+        }
+        failures
+    }
+}
+
+impl Simulator {
+    pub fn new(description: &RoomDescription) -> Simulator {
+        let world = description.build();
+        let level = world.room(&description.room).and_then(|room| room.controller).map(|c| c.level).unwrap_or(0);
+
+        let simulator = Simulator{
+            world: world,
+            room: description.room.clone(),
+            terrain: description.terrain(),
+            spawning: HashMap::new(),
+            idle: HashMap::new(),
+            report: Report{
+                level: level,
+                ..Report::default()
+            }
+        };
+        simulator.update_room_energy();
+        simulator
+    }
+
+    pub fn world(&self) -> &MockWorld {
+        &self.world
+    }
+
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Runs the bot for one tick, then resolves its intents.
+    pub fn tick(&mut self, bot: &Bot) {
+        self.world.clear_intents();
+        bot.run(&self.world);
+        self.world.commit_memory();
+
+        self.resolve_spawns();
+        self.resolve_creep_intents();
+        self.resolve_structure_intents();
+        self.age_creeps();
+        self.decay();
+        self.regenerate();
+        self.update_room_energy();
+
+        self.report.ticks += 1;
+        self.world.set_time(self.world.time() + 1);
+    }
+
+    fn terrain_at(&self, x: u32, y: u32) -> Terrain {
+        self.terrain.get(x as i32, y as i32)
+    }
+
+    fn is_walkable(&self, pos: &Position) -> bool {
+        if pos.x > 49 || pos.y > 49 || self.terrain_at(pos.x, pos.y) == Terrain::Wall {
+            return false;
+        }
+
+        let blocked_by_structure = self.world.structures(&pos.room).iter().any(|structure| {
+            structure.pos == *pos && match structure.kind {
+                StructureKind::Road | StructureKind::Container | StructureKind::Rampart => false,
+                _ => true
+            }
+        });
+        let blocked_by_creep = self.world.my_creeps().iter().any(|creep| creep.pos == *pos);
+
+        !blocked_by_structure && !blocked_by_creep
+    }
+
+    fn resolve_spawns(&mut self) {
+        for (spawn_name, body, name) in self.world.spawned() {
+            let spawn = match self.world.spawns().into_iter().find(|spawn| spawn.name == spawn_name) {
+                Some(spawn) => spawn,
+                None => continue
+            };
+
+            let mut cost: u32 = body.iter().map(|part| part_cost(*part)).sum();
+            let mut structures = self.world.structures(&self.room);
+            structures.sort_by_key(|structure| structure.kind != StructureKind::Spawn);
+            for mut structure in structures {
+                if cost == 0 {
+                    break;
+                }
+                if structure.kind == StructureKind::Spawn || structure.kind == StructureKind::Extension {
+                    let used = cmp::min(cost, structure.energy);
+                    structure.energy -= used;
+                    cost -= used;
+                    self.world.update_structure(structure);
+                }
+            }
+
+            let mut creep = mock::creep(&name, spawn.pos.clone(), &body);
+            creep.spawning = true;
+            self.world.add_creep(creep);
+            self.spawning.insert(name, body.len() as u32 * CREEP_SPAWN_TIME);
+            self.report.creeps_spawned += 1;
+        }
+
+        let mut done = Vec::new();
+        for (name, ticks) in self.spawning.iter_mut() {
+            *ticks -= 1;
+            if *ticks == 0 {
+                done.push(name.clone());
+            }
+        }
+
+        for name in done {
+            self.spawning.remove(&name);
+
+            if let Some(mut creep) = self.world.creep(&name) {
+                let spawn_pos = creep.pos.clone();
+                creep.spawning = false;
+                creep.pos = self.free_tile_near(&spawn_pos).unwrap_or(spawn_pos.clone());
+                self.world.update_creep(creep);
+
+                if let Some(mut spawn) = self.world.spawns().into_iter().find(|spawn| spawn.pos == spawn_pos) {
+                    spawn.spawning = false;
+                    self.world.update_spawn(spawn);
+                }
+            }
+        }
+    }
+
+    fn free_tile_near(&self, pos: &Position) -> Option<Position> {
+        neighbours(pos).into_iter().find(|tile| self.is_walkable(tile))
+    }
+
+    fn resolve_creep_intents(&mut self) {
+        let intents = self.world.creep_intents();
+
+        for creep in self.world.my_creeps() {
+            if creep.spawning {
+                continue;
+            }
+
+            let idle = self.idle.entry(creep.name.clone()).or_insert(0);
+            if intents.iter().any(|(name, _)| *name == creep.name) {
+                *idle = 0;
+            } else {
+                *idle += 1;
+                if *idle > self.report.max_idle.0 {
+                    self.report.max_idle = (*idle, creep.name.clone());
+                }
+            }
+        }
+
+        for (name, intent) in intents {
+            let creep = match self.world.creep(&name) {
+                Some(creep) => creep,
+                None => continue
+            };
+
+            match intent {
+                CreepIntent::MoveTo(target) => self.move_creep(creep, &target),
+                CreepIntent::Move(direction) => {
+                    if let Some(target) = creep.pos.step(direction) {
+                        self.move_creep(creep, &target);
+                    }
+                },
+                CreepIntent::Harvest(id) => self.harvest(creep, &id),
+                CreepIntent::Transfer(id) => self.transfer(creep, &id),
+                CreepIntent::Withdraw(id) => self.withdraw(creep, &id),
+                CreepIntent::Pickup(id) => self.pickup(creep, &id),
+                // energy is the only resource in the simulator
+                CreepIntent::Loot(id) => self.withdraw(creep, &id),
+                CreepIntent::Unload(id) => self.transfer(creep, &id),
+                CreepIntent::Build(id) => self.build(creep, &id),
+                CreepIntent::Repair(id) => self.repair(creep, &id),
+                CreepIntent::UpgradeController(_) => self.upgrade(creep),
+                // there's nothing to fight in the simulator
+                CreepIntent::Attack(_) | CreepIntent::RangedAttack(_) |
+                CreepIntent::Heal(_) | CreepIntent::RangedHeal(_) => ()
+            }
+        }
+    }
+
+    fn move_creep(&mut self, mut creep: CreepState, target: &Position) {
+        if creep.fatigue > 0 || creep.pos.room != target.room {
+            return;
+        }
+
+        let current = creep.pos.range_to(target);
+        let step = neighbours(&creep.pos).into_iter()
+            .filter(|tile| self.is_walkable(tile) || tile == target)
+            .filter(|tile| tile.range_to(target) < current)
+            .min_by_key(|tile| {
+                let dx = tile.x as i32 - target.x as i32;
+                let dy = tile.y as i32 - target.y as i32;
+                (tile.range_to(target), dx * dx + dy * dy)
+            });
+
+        if let Some(step) = step {
+            // creeps can't step onto a blocked target, only next to it
+            if !self.is_walkable(&step) {
+                return;
+            }
+
+            let on_road = self.world.structures(&step.room).iter()
+                              .any(|structure| structure.pos == step && structure.kind == StructureKind::Road);
+            let weight = creep.body.iter().filter(|part| part.part != Part::Move && part.part != Part::Carry).count() as u32
+                       + (creep.energy + 49) / 50;
+            let multiplier = match self.terrain_at(step.x, step.y) {
+                _ if on_road => 1,
+                Terrain::Swamp => 10,
+                _ => 2
+            };
+
+            creep.fatigue = weight * multiplier;
+            creep.pos = step;
+            self.world.update_creep(creep);
+        }
+    }
+
+    fn harvest(&mut self, mut creep: CreepState, id: &str) {
+        if let Some(mut source) = self.world.source(id) {
+            let amount = cmp::min(creep.active_parts(Part::Work) * 2, source.energy);
+            let carried = cmp::min(amount, creep.carry_capacity - creep.energy);
+            if source.energy == source.energy_capacity {
+                source.ticks_to_regeneration = SOURCE_REGEN_TIME;
+            }
+
+            self.drop_energy(&creep.pos, amount - carried);
+
+            source.energy -= amount;
+            creep.energy += carried;
+            self.world.update_source(source);
+            self.world.update_creep(creep);
+        }
+    }
+
+    fn transfer(&mut self, mut creep: CreepState, id: &str) {
+        if let Some(mut structure) = self.world.structure(id) {
+            let amount = cmp::min(creep.energy, structure.energy_capacity - structure.energy);
+
+            structure.energy += amount;
+            creep.energy -= amount;
+            self.world.update_structure(structure);
+            self.world.update_creep(creep);
+        }
+    }
+
+    /// Drops energy into a container on the tile, or onto the ground.
+    fn drop_energy(&mut self, pos: &Position, amount: u32) {
+        if amount == 0 {
+            return;
+        }
+
+        let container = self.world.structures(&pos.room).into_iter()
+            .find(|structure| structure.kind == StructureKind::Container && structure.pos == *pos);
+        let overflow = match container {
+            Some(mut container) => {
+                let stored = cmp::min(container.energy_capacity - container.energy, amount);
+                container.energy += stored;
+                self.world.update_structure(container);
+                amount - stored
+            },
+            None => amount
+        };
+        if overflow == 0 {
+            return;
+        }
+
+        let pile = self.world.dropped_resources(&pos.room).into_iter().find(|resource| resource.pos == *pos);
+        match pile {
+            Some(mut pile) => {
+                pile.amount += overflow;
+                self.world.update_dropped(pile);
+            },
+            None => self.world.add_dropped(ResourceState{
+                id: format!("energy{}-{}-{}", pos.x, pos.y, self.world.time()),
+                pos: pos.clone(),
+                amount: overflow
+            })
+        }
+    }
+
+    fn withdraw(&mut self, mut creep: CreepState, id: &str) {
+        if let Some(mut structure) = self.world.structure(id) {
+            let amount = cmp::min(structure.energy, creep.carry_capacity - creep.energy);
+
+            structure.energy -= amount;
+            creep.energy += amount;
+            self.world.update_structure(structure);
+            self.world.update_creep(creep);
+        } else if let Some(mut remains) = self.world.remains_by_id(id) {
+            let amount = cmp::min(remains.energy, creep.carry_capacity - creep.energy);
+
+            remains.energy -= amount;
+            remains.store -= amount;
+            creep.energy += amount;
+            self.world.update_remains(remains);
+            self.world.update_creep(creep);
+        }
+    }
+
+    fn pickup(&mut self, mut creep: CreepState, id: &str) {
+        if let Some(mut resource) = self.world.dropped(id) {
+            let amount = cmp::min(resource.amount, creep.carry_capacity - creep.energy);
+
+            resource.amount -= amount;
+            creep.energy += amount;
+            self.world.update_dropped(resource);
+            self.world.update_creep(creep);
+        }
+    }
+
+    fn build(&mut self, mut creep: CreepState, id: &str) {
+        if let Some(mut site) = self.world.site(id) {
+            let amount = cmp::min(
+                cmp::min(creep.active_parts(Part::Work) * 5, creep.energy),
+                site.progress_total - site.progress
+            );
+
+            site.progress += amount;
+            creep.energy -= amount;
+            self.world.update_creep(creep);
+
+            if site.progress >= site.progress_total {
+                self.world.remove_site(&site.id);
+
+                let mut structure = mock::structure(&format!("{}{}", site.kind.as_str(), site.id), site.kind, site.pos.clone());
+                match site.kind {
+                    StructureKind::Extension => structure.energy_capacity = 50,
+                    StructureKind::Container => structure.energy_capacity = 2000,
+                    StructureKind::Link => structure.energy_capacity = 800,
+                    StructureKind::Tower => structure.energy_capacity = 1000,
+                    StructureKind::Spawn => {
+                        structure.energy_capacity = 300;
+                        self.world.add_spawn(SpawnState{
+                            id: structure.id.clone(),
+                            name: format!("Spawn{}", self.world.spawns().len() + 1),
+                            pos: structure.pos.clone(),
+                            spawning: false
+                        });
+                    },
+                    _ => ()
+                }
+                self.world.add_structure(structure);
+            } else {
+                self.world.update_site(site);
+            }
+        }
+    }
+
+    fn repair(&mut self, mut creep: CreepState, id: &str) {
+        if let Some(mut structure) = self.world.structure(id) {
+            let work = cmp::min(creep.active_parts(Part::Work), creep.energy);
+
+            structure.hits = cmp::min(structure.hits_max, structure.hits + work * 100);
+            creep.energy -= work;
+            self.world.update_structure(structure);
+            self.world.update_creep(creep);
+        }
+    }
+
+    fn upgrade(&mut self, mut creep: CreepState) {
+        let mut room = match self.world.room(&creep.pos.room) {
+            Some(room) => room,
+            None => return
+        };
+
+        if let Some(ref mut controller) = room.controller {
+            let amount = cmp::min(creep.active_parts(Part::Work), creep.energy);
+            creep.energy -= amount;
+            controller.progress += amount;
+
+            if controller.progress_total > 0 && controller.progress >= controller.progress_total {
+                controller.progress -= controller.progress_total;
+                controller.level += 1;
+                controller.progress_total = progress_total(controller.level);
+
+                self.report.level = controller.level;
+                self.report.level_reached.insert(controller.level, self.report.ticks);
+                info!("[sim] reached RCL{} at tick {}", controller.level, self.report.ticks);
+            }
+        }
+
+        self.world.update_room(room);
+        self.world.update_creep(creep);
+    }
+
+    fn resolve_structure_intents(&mut self) {
+        for (id, intent) in self.world.structure_intents() {
+            if let StructureIntent::LinkTransfer(target) = intent {
+                self.link_transfer(&id, &target);
+                continue;
+            }
+            if let StructureIntent::ActivateSafeMode = intent {
+                self.activate_safe_mode();
+                continue;
+            }
+            if let StructureIntent::RecycleCreep(target) = intent {
+                if let Some(creep) = self.world.creep_by_id(&target) {
+                    self.world.remove_creep(&creep.name);
+                    self.idle.remove(&creep.name);
+                }
+                continue;
+            }
+
+            let mut tower = match self.world.structure(&id) {
+                Some(tower) => tower,
+                None => continue
+            };
+            tower.energy -= cmp::min(10, tower.energy);
+            self.world.update_structure(tower);
+
+            if let StructureIntent::TowerRepair(target) = intent {
+                if let Some(mut structure) = self.world.structure(&target) {
+                    structure.hits = cmp::min(structure.hits_max, structure.hits + 800);
+                    self.world.update_structure(structure);
+                }
+            }
+        }
+    }
+
+    fn activate_safe_mode(&mut self) {
+        if let Some(mut room) = self.world.room(&self.room) {
+            if let Some(controller) = room.controller.as_mut() {
+                controller.safe_mode = SAFE_MODE_DURATION;
+                controller.safe_mode_available -= 1;
+                controller.safe_mode_cooldown = SAFE_MODE_COOLDOWN;
+                info!("[sim] activated safe mode at tick {}", self.report.ticks);
+            }
+            self.world.update_room(room);
+        }
+    }
+
+    /// Links lose 3% of what they send, and cool down for a tick per tile.
+    fn link_transfer(&mut self, id: &str, target: &str) {
+        if let (Some(mut link), Some(mut target)) = (self.world.structure(id), self.world.structure(target)) {
+            let amount = cmp::min(link.energy, target.energy_capacity - target.energy);
+            let lost = (amount * 3 + 99) / 100;
+
+            link.energy -= amount;
+            link.cooldown = link.pos.range_to(&target.pos);
+            target.energy += amount - lost;
+            self.world.update_structure(link);
+            self.world.update_structure(target);
+        }
+    }
+
+    fn age_creeps(&mut self) {
+        for mut creep in self.world.my_creeps() {
+            if creep.spawning {
+                continue;
+            }
+
+            if creep.ticks_to_live <= 1 {
+                self.world.remove_creep(&creep.name);
+                self.world.add_remains(RemainsState{
+                    id: format!("tombstone-{}", creep.name),
+                    kind: RemainsKind::Tombstone,
+                    pos: creep.pos.clone(),
+                    energy: creep.energy,
+                    store: creep.energy,
+                    ticks_to_decay: TOMBSTONE_DECAY_PER_PART * creep.body.len() as u32
+                });
+                self.idle.remove(&creep.name);
+                continue;
+            }
+
+            creep.ticks_to_live -= 1;
+            let move_parts = creep.active_parts(Part::Move);
+            creep.fatigue -= cmp::min(creep.fatigue, move_parts * 2);
+            self.world.update_creep(creep);
+        }
+
+        self.report.creeps_alive = self.world.my_creeps().len() as u32;
+    }
+
+    /// Dropped energy loses a thousandth per tick, remains disappear when they run out of time.
+    fn decay(&mut self) {
+        for mut resource in self.world.dropped_resources(&self.room) {
+            resource.amount -= cmp::min(resource.amount, (resource.amount + 999) / 1000);
+            self.world.update_dropped(resource);
+        }
+
+        for mut remains in self.world.remains(&self.room) {
+            remains.ticks_to_decay -= 1;
+            if remains.ticks_to_decay == 0 {
+                self.drop_energy(&remains.pos, remains.energy);
+            }
+            self.world.update_remains(remains);
+        }
+    }
+
+    fn regenerate(&mut self) {
+        if let Some(mut room) = self.world.room(&self.room) {
+            if let Some(controller) = room.controller.as_mut() {
+                controller.safe_mode = controller.safe_mode.saturating_sub(1);
+                controller.safe_mode_cooldown = controller.safe_mode_cooldown.saturating_sub(1);
+            }
+            self.world.update_room(room);
+        }
+
+        for mut source in self.world.sources(&self.room) {
+            if source.ticks_to_regeneration > 0 {
+                source.ticks_to_regeneration -= 1;
+                if source.ticks_to_regeneration == 0 {
+                    source.energy = source.energy_capacity;
+                }
+                self.world.update_source(source);
+            }
+        }
+
+        for mut structure in self.world.structures(&self.room) {
+            if structure.cooldown > 0 {
+                structure.cooldown -= 1;
+                self.world.update_structure(structure.clone());
+            }
+            if structure.kind == StructureKind::Spawn && structure.energy < SPAWN_REGEN_LIMIT {
+                structure.energy += 1;
+                self.world.update_structure(structure);
+            }
+        }
+    }
+
+    fn update_room_energy(&self) {
+        if let Some(mut room) = self.world.room(&self.room) {
+            let (available, capacity) = self.world.structures(&self.room).iter()
+                .filter(|structure| structure.kind == StructureKind::Spawn || structure.kind == StructureKind::Extension)
+                .fold((0, 0), |(available, capacity), structure| {
+                    (available + structure.energy, capacity + structure.energy_capacity)
+                });
+
+            room.energy_available = available;
+            room.energy_capacity_available = capacity;
+            self.world.update_room(room);
+        }
+    }
+}
+
+fn neighbours(pos: &Position) -> Vec<Position> {
+    let mut tiles = Vec::with_capacity(8);
+    for dy in -1..2 {
+        for dx in -1..2 {
+            let x = pos.x as i32 + dx;
+            let y = pos.y as i32 + dy;
+            if (dx, dy) != (0, 0) && x >= 0 && x < 50 && y >= 0 && y < 50 {
+                tiles.push(Position::new(x as u32, y as u32, &pos.room));
+            }
+        }
+    }
+    tiles
+}
+
+/// Runs a fresh bot against the room for the given number of ticks.
+pub fn simulate(description: &RoomDescription, ticks: u32) -> Report {
+    // the bot keeps its state between ticks, the same way it does on the server
+    let bot = Bot::new();
+    let mut simulator = Simulator::new(description);
+    for _ in 0..ticks {
+        simulator.tick(&bot);
+    }
+    simulator.report
+}
+
+const USAGE: &'static str = "usage: screeps-script <room.json> [--ticks N] [--expect-rcl LEVEL:TICK] [--max-idle TICKS]";
+
+/// The entry point of the native binary.
+/// 
+/// Simulates the room for the given number of ticks, prints a report,
+/// and exits with an error if any of the expectations failed.
+pub fn run_from_args() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut path = None;
+    let mut ticks = 1500;
+    let mut expect_rcl: Option<(u32, u32)> = None;
+    let mut max_idle: Option<u32> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--ticks" => iter.next().and_then(|x| x.parse().ok()).map(|x| ticks = x),
+            "--expect-rcl" => iter.next().and_then(|x| {
+                let mut split = x.splitn(2, ':');
+                let level = split.next()?.parse().ok()?;
+                let tick = split.next()?.parse().ok()?;
+                Some(expect_rcl = Some((level, tick)))
+            }),
+            "--max-idle" => iter.next().and_then(|x| x.parse().ok()).map(|x| max_idle = Some(x)),
+            _ if path.is_none() => Some(path = Some(arg.clone())),
+            _ => None
+        };
+
+        if parsed.is_none() {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+
+    let description = match path.as_ref().map(|path| RoomDescription::load(path)) {
+        Some(Ok(description)) => description,
+        Some(Err(err)) => {
+            eprintln!("failed to load room: {}", err);
+            process::exit(2);
+        },
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let report = simulate(&description, ticks);
+    println!("ticks: {}", report.ticks);
+    println!("controller level: {}", report.level);
+    let mut levels: Vec<_> = report.level_reached.iter().collect();
+    levels.sort();
+    for (level, tick) in levels {
+        println!("  RCL{} at tick {}", level, tick);
+    }
+    println!("creeps spawned: {}, alive: {}", report.creeps_spawned, report.creeps_alive);
+    println!("longest idle: {} ticks ({})", report.max_idle.0, report.max_idle.1);
+
+    let failures = report.failures(expect_rcl, max_idle);
+    for failure in failures.iter() {
+        println!("FAILED: {}", failure);
+    }
+    if !failures.is_empty() {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The same run as the example in the README.
+    #[test]
+    fn w1n1_reaches_rcl2() {
+        let description = RoomDescription::load(concat!(env!("CARGO_MANIFEST_DIR"), "/rooms/W1N1.json")).unwrap();
+        let report = simulate(&description, 3000);
+
+        assert_eq!(report.failures(Some((2, 2000)), Some(50)), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::Read
+};
+
+use serde_json::{
+    self,
+    Value
+};
+
+use crate::memory::Memory;
+use crate::world::{
+    mock,
+    ControllerState,
+    MineralState,
+    MockWorld,
+    Position,
+    RoomState,
+    RoomTerrain,
+    SourceState,
+    SpawnState,
+    StructureKind
+};
+
+#[derive(Deserialize)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32
+}
+
+#[derive(Deserialize)]
+pub struct ControllerDescription {
+    pub x: u32,
+    pub y: u32,
+    #[serde(default = "default_level")]
+    pub level: u32
+}
+
+fn default_level() -> u32 {
+    1
+}
+
+/// A single room to simulate, loaded from JSON.
+/// 
+/// `terrain` is 50 rows of 50 characters: `x` is a wall, `~` is a swamp, anything else is plain.
+/// If it's missing, the whole room is plain.  
+/// `mineral` is optional.  
+/// `memory` is the initial value of `Memory`.
+#[derive(Deserialize)]
+pub struct RoomDescription {
+    pub room: String,
+    #[serde(default)]
+    pub terrain: Vec<String>,
+    pub sources: Vec<Tile>,
+    #[serde(default)]
+    pub mineral: Option<Tile>,
+    pub controller: ControllerDescription,
+    pub spawn: Tile,
+    #[serde(default)]
+    pub extensions: Vec<Tile>,
+    #[serde(default)]
+    pub memory: Value
+}
+
+impl RoomDescription {
+    pub fn load(path: &str) -> Result<RoomDescription, Box<dyn Error>> {
+        let mut raw = String::new();
+        File::open(path)?.read_to_string(&mut raw)?;
+        Ok(serde_json::from_str(&raw)?)
+    }
+
+    pub fn terrain(&self) -> RoomTerrain {
+        RoomTerrain::parse(&self.terrain)
+    }
+
+    /// Builds the starting state of the room.
+    pub fn build(&self) -> MockWorld {
+        let world = MockWorld::new();
+        let room = &self.room;
+
+        world.set_terrain(room, self.terrain());
+
+        world.add_room(RoomState{
+            name: room.clone(),
+            energy_available: 0,
+            energy_capacity_available: 0,
+            controller: Some(ControllerState{
+                id: "controller".to_string(),
+                pos: Position::new(self.controller.x, self.controller.y, room),
+                my: true,
+                level: self.controller.level,
+                progress: 0,
+                progress_total: super::progress_total(self.controller.level),
+                safe_mode: 0,
+                safe_mode_available: 1,
+                safe_mode_cooldown: 0
+            })
+        });
+
+        for (index, source) in self.sources.iter().enumerate() {
+            world.add_source(SourceState{
+                id: format!("source{}", index),
+                pos: Position::new(source.x, source.y, room),
+                energy: 3000,
+                energy_capacity: 3000,
+                ticks_to_regeneration: 0
+            });
+        }
+
+        if let Some(ref mineral) = self.mineral {
+            world.add_mineral(MineralState{
+                id: "mineral".to_string(),
+                pos: Position::new(mineral.x, mineral.y, room)
+            });
+        }
+
+        let spawn_pos = Position::new(self.spawn.x, self.spawn.y, room);
+        world.add_spawn(SpawnState{
+            id: "spawn0".to_string(),
+            name: "Spawn1".to_string(),
+            pos: spawn_pos.clone(),
+            spawning: false
+        });
+
+        let mut spawn = mock::structure("spawn0", StructureKind::Spawn, spawn_pos);
+        spawn.hits = 5000;
+        spawn.hits_max = 5000;
+        spawn.energy = 300;
+        spawn.energy_capacity = 300;
+        world.add_structure(spawn);
+
+        for (index, extension) in self.extensions.iter().enumerate() {
+            let mut structure = mock::structure(&format!("extension{}", index), StructureKind::Extension,
+                                                Position::new(extension.x, extension.y, room));
+            structure.energy_capacity = 50;
+            world.add_structure(structure);
+        }
+
+        match Memory::from_value(self.memory.clone()) {
+            Ok(memory) => *world.memory() = memory,
+            Err(err) => warn!("ignoring the room's memory: {}", err)
+        }
+
+        world
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap
+};
+
+use hashbrown::HashMap;
+use screeps::constants::{
+    Part,
+    ReturnCode
+};
+
+use crate::{
+    body::body_cost,
+    memory::{
+        CreepMemory,
+        QueuedSpawn
+    },
+    roles::registry::RoleRegistry,
+    traits::Role,
+    world::{
+        RoomState,
+        SpawnState,
+        World
+    }
+};
+
+/// Failed spawns are retried for this many times before the request is put on hold.
+const MAX_ATTEMPTS: u32 = 10;
+/// How long a request is put on hold for.
+const RETRY_DELAY: u32 = 100;
+/// Requests the room can't afford are put on hold this long, so they're only warned about this often.
+const WARN_INTERVAL: u32 = 1000;
+
+/// A creep a role wants spawned.
+#[derive(Clone, Debug)]
+pub struct SpawnRequest {
+    pub role: &'static str,
+    pub room: String,
+    pub body: Vec<Part>,
+    /// Lower is more urgent.
+    pub priority: i32,
+    /// Spawned as soon as possible, without waiting for the room's full energy.
+    pub bootstrap: bool
+}
+
+impl SpawnRequest {
+    pub fn cost(&self) -> u32 {
+        body_cost(&self.body)
+    }
+}
+
+/// Collects spawn requests from all roles, and spawns them in every room that has a spawn.
+/// 
+/// Requests are handled in order of priority in each room:
+///   * every idle spawn in the room takes the next request
+///   * if the next request can't be afforded yet, its energy is reserved,
+///     and nothing below it is spawned until it is
+///   * if a room has no creeps left of an essential role, a small creep of it jumps the queue
+/// 
+/// The queue is written to `Memory.spawn_queue` every tick, for inspection.
+pub struct SpawnQueue;
+
+impl SpawnQueue {
+    pub fn new() -> SpawnQueue {
+        SpawnQueue{}
+    }
+
+    /// Counts living creeps by home room, then role.
+    fn census(&self, world: &dyn World) -> HashMap<(String, String), u32> {
+        let mut counts = HashMap::new();
+        let mut memory = world.memory();
+
+        for creep in world.my_creeps() {
+            let creep_memory = memory.creep(&creep.name);
+            if creep_memory.home.is_empty() {
+                creep_memory.home = creep.pos.room.clone();
+            }
+
+            *counts.entry((creep_memory.home.clone(), creep_memory.role.clone())).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Asks every role what it wants spawned in the room, most urgent first.
+    fn collect(&self, world: &dyn World, room: &RoomState, roles: &RoleRegistry,
+               census: &HashMap<(String, String), u32>) -> Vec<SpawnRequest> {
+        let mut requests: Vec<SpawnRequest> = roles.values().filter_map(|role| {
+            let count = census.get(&(room.name.clone(), role.name().to_string())).cloned().unwrap_or(0);
+
+            if count == 0 && role.essential() {
+                Some(SpawnRequest{
+                    role: role.name(),
+                    room: room.name.clone(),
+                    // as big as the energy in the room allows right now, or the smallest body the role has
+                    body: role.next_creep(room.energy_available),
+                    priority: i32::min_value(),
+                    bootstrap: true
+                })
+            } else {
+                role.spawn_request(world, room, count)
+            }
+        }).collect();
+
+        requests.sort_by(|a, b| match a.priority.cmp(&b.priority) {
+            Ordering::Equal => a.role.cmp(b.role),
+            ordering => ordering
+        });
+        requests
+    }
+
+    fn next_name(&self, world: &dyn World, role: &str) -> (String, u32) {
+        let mut memory = world.memory();
+        loop {
+            let id = memory.id;
+            memory.id += 1;
+
+            let name = format!("{}-{}", role, id);
+            if world.creep(&name).is_none() {
+                return (name, id);
+            }
+        }
+    }
+
+    pub fn run(&self, world: &dyn World, roles: &RoleRegistry) {
+        let census = self.census(world);
+
+        let mut spawns_by_room: HashMap<String, Vec<SpawnState>> = HashMap::new();
+        for spawn in world.spawns() {
+            spawns_by_room.entry(spawn.pos.room.clone()).or_insert_with(Vec::new).push(spawn);
+        }
+
+        let mut queues = BTreeMap::new();
+        for (room_name, spawns) in spawns_by_room {
+            let room = match world.room(&room_name) {
+                Some(room) => room,
+                None => continue
+            };
+
+            let requests = self.collect(world, &room, roles, &census);
+            let queue = self.spawn_room(world, &room, spawns, requests);
+            queues.insert(room_name, queue);
+        }
+
+        world.memory().spawn_queue = queues;
+    }
+
+    /// Spawns what the room can afford, and returns what's left in the queue.
+    fn spawn_room(&self, world: &dyn World, room: &RoomState, mut spawns: Vec<SpawnState>,
+                  requests: Vec<SpawnRequest>) -> Vec<QueuedSpawn> {
+        let previous: Vec<QueuedSpawn> = world.memory().spawn_queue.get(&room.name).cloned().unwrap_or_default();
+        spawns.retain(|spawn| !spawn.spawning);
+
+        let mut energy = room.energy_available;
+        let mut reserved = false;
+        let mut queue = Vec::new();
+
+        for request in requests {
+            let cost = request.cost();
+            let mut queued = previous.iter()
+                .find(|queued| queued.role == request.role)
+                .cloned()
+                .unwrap_or(QueuedSpawn{
+                    role: request.role.to_string(),
+                    since: world.time(),
+                    ..QueuedSpawn::default()
+                });
+            queued.priority = request.priority;
+            queued.cost = cost;
+            queued.bootstrap = request.bootstrap;
+
+            if world.time() < queued.retry_at {
+                // on hold, give other requests a chance
+                queue.push(queued);
+                continue;
+            }
+
+            if cost > room.energy_capacity_available {
+                warn!("{} in {} costs {}, but the room can only hold {}", request.role, room.name, cost, room.energy_capacity_available);
+                queued.retry_at = world.time() + WARN_INTERVAL;
+                queue.push(queued);
+                continue;
+            }
+
+            if reserved || spawns.is_empty() || cost > energy {
+                // the energy stays reserved for this one
+                reserved = true;
+                queue.push(queued);
+                continue;
+            }
+
+            let spawn = spawns.remove(0);
+            let (name, id) = self.next_name(world, request.role);
+
+            match world.spawn_creep(&spawn.name, &request.body, &name) {
+                ReturnCode::Ok => {
+                    energy -= cost;
+                    world.memory().creeps.insert(name.clone(), CreepMemory{
+                        role: request.role.to_string(),
+                        id: id,
+                        home: room.name.clone(),
+                        ..CreepMemory::default()
+                    });
+
+                    if request.bootstrap {
+                        warn!("bootstrapping {} with {}", room.name, name);
+                    } else {
+                        info!("spawning {} in {}", name, spawn.name);
+                    }
+                },
+                code => {
+                    queued.attempts += 1;
+                    warn!("failed to spawn {} in {}: {:?} (attempt {})", name, spawn.name, code, queued.attempts);
+
+                    if queued.attempts >= MAX_ATTEMPTS {
+                        queued.attempts = 0;
+                        queued.retry_at = world.time() + RETRY_DELAY;
+                    }
+                    queue.push(queued);
+                }
+            }
+        }
+
+        queue
+    }
+
+    /// A short summary of all queues, for logging.
+    pub fn describe(&self, world: &dyn World) -> String {
+        world.memory().spawn_queue.iter().fold(String::new(), |acc, (room, queue)| {
+            let entries = queue.iter().fold(String::new(), |acc, queued| {
+                format!("{acc}<li>{role} ({cost}, waiting {wait})</li>", acc=acc, role=queued.role,
+                        cost=queued.cost, wait=world.time() - queued.since)
+            });
+            format!("{acc}<li>{room}: <ul>{entries}</ul></li>", acc=acc, room=room, entries=entries)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::{
+        body::BodyTemplate,
+        traits::FlagProcessor,
+        world::{
+            mock::{
+                self,
+                MockWorld
+            },
+            CreepState,
+            Position
+        }
+    };
+
+    struct TestRole {
+        name: &'static str,
+        limit: i32,
+        priority: i32,
+        essential: bool,
+        body: BodyTemplate
+    }
+
+    impl FlagProcessor for TestRole {}
+
+    impl Role for TestRole {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn limit(&self) -> i32 {
+            self.limit
+        }
+
+        fn body(&self) -> BodyTemplate {
+            self.body.clone()
+        }
+
+        fn run_count(&self, _world: &dyn World) -> i32 {
+            0
+        }
+
+        fn run(&self, _world: &dyn World, _creep: &CreepState) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn spawn_priority(&self) -> i32 {
+            self.priority
+        }
+
+        fn essential(&self) -> bool {
+            self.essential
+        }
+    }
+
+    /// A role wanting one creep of WORK, CARRY and MOVE, costing 200.
+    fn role(name: &'static str, priority: i32) -> TestRole {
+        TestRole{
+            name: name,
+            limit: 1,
+            priority: priority,
+            essential: false,
+            body: BodyTemplate::new(&[(Part::Work, 1), (Part::Carry, 1), (Part::Move, 1)]).size(1, 1)
+        }
+    }
+
+    fn registry(roles: Vec<TestRole>) -> RoleRegistry {
+        let mut registry = RoleRegistry::new();
+        for role in roles {
+            registry.register(Box::new(role));
+        }
+        registry
+    }
+
+    fn world_with_spawns(spawns: u32, energy: u32, capacity: u32) -> MockWorld {
+        let world = MockWorld::new();
+        world.add_room(RoomState{
+            name: "W1N1".to_string(),
+            energy_available: energy,
+            energy_capacity_available: capacity,
+            controller: None
+        });
+        for i in 0..spawns {
+            world.add_spawn(SpawnState{
+                id: format!("spawn{}", i),
+                name: format!("Spawn{}", i),
+                pos: Position::new(20 + i, 25, "W1N1"),
+                spawning: false
+            });
+        }
+        world
+    }
+
+    fn spawned_roles(world: &MockWorld) -> Vec<String> {
+        let memory = world.memory();
+        world.spawned().iter().map(|(_, _, name)| memory.creeps[name].role.clone()).collect()
+    }
+
+    fn queued(world: &MockWorld) -> Vec<QueuedSpawn> {
+        world.memory().spawn_queue.get("W1N1").cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn spawns_most_urgent_first() {
+        let world = world_with_spawns(1, 1000, 1000);
+        SpawnQueue::new().run(&world, &registry(vec![role("later", 20), role("first", 10)]));
+
+        assert_eq!(spawned_roles(&world), vec!["first".to_string()]);
+        let queue = queued(&world);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].role, "later");
+        assert_eq!(queue[0].since, 1);
+    }
+
+    #[test]
+    fn reserves_energy_for_next_request() {
+        let world = world_with_spawns(1, 250, 1000);
+        let mut expensive = role("expensive", 10);
+        expensive.body = BodyTemplate::new(&[(Part::Work, 1), (Part::Move, 1)]).size(4, 4);
+
+        SpawnQueue::new().run(&world, &registry(vec![expensive, role("cheap", 20)]));
+
+        // the cheap one fits, but would delay the more urgent one
+        assert!(world.spawned().is_empty());
+        let roles: Vec<String> = queued(&world).into_iter().map(|queued| queued.role).collect();
+        assert_eq!(roles, vec!["expensive".to_string(), "cheap".to_string()]);
+    }
+
+    #[test]
+    fn fills_every_spawn_in_a_room() {
+        let world = world_with_spawns(2, 400, 400);
+        SpawnQueue::new().run(&world, &registry(vec![role("a", 10), role("b", 20), role("c", 30)]));
+
+        assert_eq!(spawned_roles(&world), vec!["a".to_string(), "b".to_string()]);
+        let spawns: Vec<String> = world.spawned().into_iter().map(|(spawn, _, _)| spawn).collect();
+        assert_eq!(spawns, vec!["Spawn0".to_string(), "Spawn1".to_string()]);
+        assert_eq!(world.room("W1N1").unwrap().energy_available, 0);
+        assert_eq!(queued(&world).len(), 1);
+    }
+
+    #[test]
+    fn holds_requests_the_room_cannot_hold() {
+        let world = world_with_spawns(1, 300, 300);
+        let mut big = role("big", 10);
+        big.body = BodyTemplate::new(&[(Part::Work, 1), (Part::Move, 1)]).size(4, 4);
+
+        let roles = registry(vec![big, role("small", 20)]);
+        SpawnQueue::new().run(&world, &roles);
+
+        // it can never be afforded, so it doesn't block the rest
+        assert_eq!(spawned_roles(&world), vec!["small".to_string()]);
+        assert_eq!(queued(&world)[0].retry_at, 1 + WARN_INTERVAL);
+    }
+
+    #[test]
+    fn holds_failing_requests() {
+        let world = world_with_spawns(1, 3000, 3000);
+        let mut broken = role("broken", 10);
+        // more parts than a creep can have, so spawning always fails
+        broken.body = BodyTemplate::new(&[(Part::Move, 1)]).fixed(&[(Part::Move, 50)]);
+        let roles = registry(vec![broken]);
+        let queue = SpawnQueue::new();
+
+        for tick in 1..MAX_ATTEMPTS {
+            world.set_time(tick);
+            queue.run(&world, &roles);
+            assert_eq!(queued(&world)[0].attempts, tick);
+        }
+
+        world.set_time(MAX_ATTEMPTS);
+        queue.run(&world, &roles);
+        assert_eq!(queued(&world)[0].attempts, 0);
+        assert_eq!(queued(&world)[0].retry_at, MAX_ATTEMPTS + RETRY_DELAY);
+
+        world.set_time(MAX_ATTEMPTS + 1);
+        queue.run(&world, &roles);
+        assert_eq!(queued(&world)[0].attempts, 0);
+
+        world.set_time(MAX_ATTEMPTS + RETRY_DELAY);
+        queue.run(&world, &roles);
+        assert_eq!(queued(&world)[0].attempts, 1);
+        assert!(world.spawned().is_empty());
+    }
+
+    #[test]
+    fn bootstraps_with_available_energy() {
+        let world = world_with_spawns(1, 250, 1000);
+        let mut hauler = role("hauler", 10);
+        hauler.essential = true;
+        hauler.body = BodyTemplate::new(&[(Part::Carry, 1), (Part::Move, 1)]).size(1, 10);
+
+        SpawnQueue::new().run(&world, &registry(vec![role("other", 5), hauler]));
+
+        // jumps the queue, with a body it can afford right away
+        assert_eq!(spawned_roles(&world), vec!["hauler".to_string()]);
+        assert_eq!(world.spawned()[0].1, vec![Part::Carry, Part::Carry, Part::Move, Part::Move]);
+    }
+
+    #[test]
+    fn bootstrap_waits_for_minimal_body() {
+        let world = world_with_spawns(1, 150, 1000);
+        let mut miner = role("miner", 10);
+        miner.essential = true;
+
+        SpawnQueue::new().run(&world, &registry(vec![miner]));
+
+        assert!(world.spawned().is_empty());
+        let queue = queued(&world);
+        assert!(queue[0].bootstrap);
+        assert_eq!(queue[0].cost, 200);
+    }
+
+    #[test]
+    fn no_bootstrap_while_role_lives() {
+        let world = world_with_spawns(1, 250, 1000);
+        world.add_creep(mock::creep("hauler-0", Position::new(10, 10, "W1N1"), &[Part::Carry, Part::Move]));
+        world.memory().creep("hauler-0").role = "hauler".to_string();
+
+        let mut hauler = role("hauler", 10);
+        hauler.essential = true;
+        hauler.limit = 2;
+        hauler.body = BodyTemplate::new(&[(Part::Carry, 1), (Part::Move, 1)]).size(1, 10);
+
+        SpawnQueue::new().run(&world, &registry(vec![hauler]));
+
+        // a full sized creep is waited for instead
+        assert!(world.spawned().is_empty());
+        let queue = queued(&world);
+        assert!(!queue[0].bootstrap);
+        assert_eq!(queue[0].cost, 1000);
+    }
+}
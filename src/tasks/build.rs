@@ -1,42 +1,177 @@
-use std::error::Error;
-use screeps::{
-    prelude::*,
-    objects::Creep,
-    constants::*
+use std::{
+    error::Error,
+    rc::Rc
 };
+use screeps::constants::*;
 
-use crate::traits::{
-    Task,
-    FlagProcessor
+use crate::{
+    memory::CreepTaskMemory,
+    reservations::{
+        ReservationKind,
+        Reservations
+    },
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        find_closest_by_range,
+        CreepIntent,
+        CreepState,
+        SiteState,
+        World
+    }
 };
 
-/// A creep moves to the closest construction site, and attempts to build it.
-pub struct TaskBuild;
+/// Progress a work part adds per tick, for as much energy.
+const BUILD_POWER: u32 = 5;
+
+/// A creep moves to the closest construction site, and builds it until it's finished.
+/// 
+/// The site is kept in `Memory.creeps.<creep>.tasks.build.target`,  
+/// and the progress the creep's energy is worth is reserved on it.
+pub struct TaskBuild {
+    reservations: Rc<Reservations>
+}
 
 impl TaskBuild {
-    pub fn new() -> TaskBuild {
-        TaskBuild{}
+    pub fn new(reservations: Rc<Reservations>) -> TaskBuild {
+        TaskBuild{
+            reservations: reservations
+        }
+    }
+
+    /// Progress left on the site that no other creep reserved.
+    fn unreserved(&self, site: &SiteState) -> u32 {
+        site.progress_total.saturating_sub(site.progress)
+                           .saturating_sub(self.reservations.amount(&site.id, ReservationKind::Work))
     }
 }
 
 impl FlagProcessor for TaskBuild {}
 
 impl Task for TaskBuild {
-    fn run(&self, creep: &Creep) -> Result<bool, Box<dyn Error>> {
-        let sites = creep.room().find(find::CONSTRUCTION_SITES);
-        let target_site = match sites.get(0) {
-            Some(s) => s,
-            None => return Ok(false)
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let sites = world.construction_sites(&creep.pos.room);
+        let locked = self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::Work);
+
+        // a locked site that's gone was either finished, or removed
+        let (locked_site, finished) = match locked {
+            Some(id) => match sites.iter().find(|site| site.id == id) {
+                Some(site) => (Some(site.clone()), false),
+                None => (None, true)
+            },
+            None => (None, false)
         };
 
-        if creep.build(target_site) == ReturnCode::NotInRange {
-            creep.move_to(target_site);
-        }
+        let target_site = locked_site.filter(|site| self.unreserved(site) > 0)
+                                     .or_else(|| {
+                                         let open = sites.into_iter()
+                                                         .filter(|site| self.unreserved(site) > 0)
+                                                         .collect();
+                                         find_closest_by_range(&creep.pos, open)
+                                     });
+        let target_site = match target_site {
+            Some(site) => site,
+            None => return Ok(if finished { TaskStatus::Done } else { TaskStatus::Blocked })
+        };
+
+        let amount = creep.energy.min(self.unreserved(&target_site));
+        self.reservations.lock(self.creep_memory(&mut world.memory(), creep), ReservationKind::Work, &target_site.id, amount);
 
-        Ok(true)
+        match world.creep_intent(&creep.name, CreepIntent::Build(target_site.id.clone())) {
+            ReturnCode::Ok => {
+                // the creep runs out of energy, or the site is finished, this tick
+                if amount <= creep.active_parts(Part::Work) * BUILD_POWER {
+                    self.reservations.settle(self.creep_memory(&mut world.memory(), creep));
+                }
+                Ok(TaskStatus::InProgress)
+            },
+            ReturnCode::NotInRange => {
+                world.creep_intent(&creep.name, CreepIntent::MoveTo(target_site.pos.clone()));
+                Ok(TaskStatus::InProgress)
+            },
+            code => {
+                self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::Work);
+                Ok(TaskStatus::Failed(format!("can't build {}: {:?}", target_site.id, code)))
+            }
+        }
     }
 
     fn name(&self) -> &'static str {
         "build"
     }
-}
\ No newline at end of file
+
+    fn restore(&self, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.restore(memory, ReservationKind::Work);
+    }
+
+    fn release(&self, _world: &dyn World, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.free(memory, ReservationKind::Work);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        Position,
+        SiteState,
+        StructureKind
+    };
+
+    fn world_with_site() -> MockWorld {
+        let world = MockWorld::new();
+        world.add_site(SiteState{
+            id: "site".to_string(),
+            kind: StructureKind::Extension,
+            pos: Position::new(10, 10, "W1N1"),
+            progress: 0,
+            progress_total: 100
+        });
+        world
+    }
+
+    fn builder(world: &MockWorld, pos: Position, energy: u32) -> CreepState {
+        let mut creep = mock::creep("builder", pos, &[Part::Work, Part::Carry, Part::Move]);
+        creep.energy = energy;
+        world.add_creep(creep.clone());
+        creep
+    }
+
+    #[test]
+    fn builds_site_in_range() {
+        let world = world_with_site();
+        let creep = builder(&world, Position::new(13, 10, "W1N1"), 50);
+        let reservations = Rc::new(Reservations::new());
+
+        assert_eq!(TaskBuild::new(reservations.clone()).run(&world, &creep).unwrap(), TaskStatus::InProgress);
+        assert_eq!(world.creep_intents(), vec![("builder".to_string(), CreepIntent::Build("site".to_string()))]);
+        assert_eq!(reservations.amount("site", ReservationKind::Work), 50);
+    }
+
+    #[test]
+    fn moves_to_distant_site() {
+        let world = world_with_site();
+        let creep = builder(&world, Position::new(30, 30, "W1N1"), 50);
+
+        assert_eq!(TaskBuild::new(Rc::new(Reservations::new())).run(&world, &creep).unwrap(), TaskStatus::InProgress);
+        assert_eq!(world.creep_intents(), vec![("builder".to_string(), CreepIntent::MoveTo(Position::new(10, 10, "W1N1")))]);
+    }
+
+    #[test]
+    fn settles_last_build() {
+        let world = world_with_site();
+        let creep = builder(&world, Position::new(13, 10, "W1N1"), BUILD_POWER);
+        let task = TaskBuild::new(Rc::new(Reservations::new()));
+
+        task.run(&world, &creep).unwrap();
+        assert_eq!(world.creep_intents(), vec![("builder".to_string(), CreepIntent::Build("site".to_string()))]);
+        assert_eq!(task.creep_memory(&mut world.memory(), &creep).reserved, 0);
+    }
+}
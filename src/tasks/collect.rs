@@ -0,0 +1,145 @@
+use std::{
+    cmp,
+    error::Error,
+    rc::Rc
+};
+use screeps::constants::*;
+
+use crate::{
+    logistics::{
+        best_provider,
+        Logistics
+    },
+    memory::{
+        CreepTaskMemory,
+        StopAction
+    },
+    reservations::{
+        ReservationKind,
+        Reservations
+    },
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepIntent,
+        CreepState,
+        World
+    }
+};
+
+/// A creep fills up from a provider, see `logistics::Logistics`:
+///   * `pickup` takes dropped energy
+///   * `withdraw` takes energy from tombstones, ruins, containers, storage and links
+///   * `supply` takes energy from the containers and links next to the controller, for upgraders
+/// 
+/// Energy that's about to decay is taken first. Roles list these before `harvest`,
+/// so creeps only harvest when there's nothing to take.  
+/// The provider is kept in `Memory.creeps.<creep>.tasks.<task>.target`, and the energy taken is reserved on it.
+pub struct TaskCollect {
+    name: &'static str,
+    action: StopAction,
+    /// Takes from `Logistics::upgrade_supply` instead of the providers.
+    upgrade: bool,
+    logistics: Rc<Logistics>,
+    reservations: Rc<Reservations>
+}
+
+impl TaskCollect {
+    pub fn pickup(logistics: Rc<Logistics>, reservations: Rc<Reservations>) -> TaskCollect {
+        TaskCollect{
+            name: "pickup",
+            action: StopAction::Pickup,
+            upgrade: false,
+            logistics: logistics,
+            reservations: reservations
+        }
+    }
+
+    pub fn withdraw(logistics: Rc<Logistics>, reservations: Rc<Reservations>) -> TaskCollect {
+        TaskCollect{
+            name: "withdraw",
+            action: StopAction::Withdraw,
+            upgrade: false,
+            logistics: logistics,
+            reservations: reservations
+        }
+    }
+
+    pub fn supply(logistics: Rc<Logistics>, reservations: Rc<Reservations>) -> TaskCollect {
+        TaskCollect{
+            name: "supply",
+            action: StopAction::Withdraw,
+            upgrade: true,
+            logistics: logistics,
+            reservations: reservations
+        }
+    }
+}
+
+impl FlagProcessor for TaskCollect {}
+
+impl Task for TaskCollect {
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let locked = self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyOut);
+        let had_target = locked.is_some();
+
+        let free = creep.carry_capacity.saturating_sub(creep.energy);
+        if free == 0 {
+            return Ok(if had_target { TaskStatus::Done } else { TaskStatus::Blocked });
+        }
+
+        let providers: Vec<_> = if self.upgrade {
+            self.logistics.upgrade_supply(world, &creep.pos.room)
+        } else {
+            self.logistics.providers(world, &creep.pos.room).into_iter()
+                .filter(|offer| offer.action == self.action)
+                .collect()
+        };
+        let target = locked.and_then(|id| providers.iter().find(|offer| offer.id == id))
+                           .or_else(|| best_provider(&providers, &creep.pos));
+
+        let target = match target {
+            Some(target) => target,
+            None => return Ok(if had_target { TaskStatus::Done } else { TaskStatus::Blocked })
+        };
+
+        let amount = cmp::min(free, target.amount);
+        self.reservations.lock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyOut, &target.id, amount);
+
+        let intent = match self.action {
+            StopAction::Pickup => CreepIntent::Pickup(target.id.clone()),
+            _ => CreepIntent::Withdraw(target.id.clone())
+        };
+
+        match world.creep_intent(&creep.name, intent) {
+            ReturnCode::Ok => {
+                // the creep takes all it can, so it's full or the provider is empty
+                self.reservations.settle(self.creep_memory(&mut world.memory(), creep));
+                Ok(TaskStatus::InProgress)
+            },
+            ReturnCode::NotInRange => {
+                world.creep_intent(&creep.name, CreepIntent::MoveTo(target.pos.clone()));
+                Ok(TaskStatus::InProgress)
+            },
+            code => {
+                self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyOut);
+                Ok(TaskStatus::Failed(format!("can't {} from {}: {:?}", self.name, target.id, code)))
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn restore(&self, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.restore(memory, ReservationKind::EnergyOut);
+    }
+
+    fn release(&self, _world: &dyn World, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.free(memory, ReservationKind::EnergyOut);
+    }
+}
@@ -2,106 +2,61 @@ use std::{
     convert::From,
     error::Error
 };
-use screeps::{
-    constants::*,
-    game::get_object_typed,
+
+use crate::{
     memory::{
-        MemoryReference,
-        root
+        Memory,
+        SourceMemory
     },
-    objects::{
-        Creep,
-        RoomPosition,
-        Source
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
     },
-    prelude::*
-};
-
-use crate::traits::{
-    Task,
-    FlagProcessor
+    world::{
+        CreepIntent,
+        CreepState,
+        World
+    }
 };
 
 /// A creep moves to its assigned source, and begins harvesting.
 /// 
-/// Sources are stored in `memory.sources`.  
-/// What's stored inside a source:
-///   * `creep_limit` - how many creeps can harvest at the same time
-///   * `counter` - how many creeps harvested in this tick
-///   * `prev_counter` - how many creeps harvested in the previous tick
-///   * `pos` - an {x, y, room} object that stores the source's position
-///     * Populates when the first creep begins harvesting there.
+/// Sources are stored in `memory.sources`, see `memory::SourceMemory`.
 /// 
 /// A source cannot be removed, but its limit can be set to 0.
-pub struct TaskHarvest {
-    memory: MemoryReference
-}
+pub struct TaskHarvest;
 
 impl TaskHarvest {
     pub fn new() -> TaskHarvest {
-        let memory = 
-            root().dict_or_create("tasks")
-                  .and_then(|mem| mem.dict_or_create("harvest"))
-              .expect("failed to create task memory");
-        let task = TaskHarvest{memory};
-
-        // Copy last tick's counter
-        // 
-        let sources = task.memory.dict_or_create("sources").expect("failed to create task memory");
-
-        for source_id in sources.keys() {
-            let source = sources.dict(&source_id).unwrap().unwrap();
-            source.set("prev_counter", source.i32("counter").unwrap_or(None).unwrap_or(0));
-            source.set("counter", 0);
-        }
-
-        task
+        TaskHarvest{}
     }
 
     #[inline]
-    fn add_to_counter(&self, id: &str) {
-        if let Ok(source) = self.memory.dict_or_create("sources").and_then(|src| src.dict_or_create(id)) {
-            source.set("counter", 1 + source.i32("counter").unwrap_or(None).unwrap_or(0));
-        }
-    }
-
-    #[inline]
-    fn add_to_prev_counter(&self, id: &str) {
-        if let Ok(source) = self.memory.dict_or_create("sources").and_then(|src| src.dict_or_create(id)) {
-            source.set("prev_counter", 1 + source.i32("prev_counter").unwrap_or(None).unwrap_or(0));
-        }
-    }
-
-    #[inline]
-    fn get_counter(&self, id: &str) -> i32 {
-        if let Ok(source) = self.memory.dict_or_create("sources").and_then(|src| src.dict_or_create(id)) {
-            source.i32("prev_counter").unwrap_or(None).unwrap_or(0)
-        } else {
-            0
-        }
-    }
-
-    #[inline]
-    fn get_limit(&self, id: &str) -> i32 {
-        if let Ok(source) = self.memory.dict_or_create("sources").and_then(|src| src.dict_or_create(id)) {
-            source.i32("creep_limit").unwrap_or(None).unwrap_or(4)
-        } else {
-            4
-        }
+    fn source_memory<'a>(&self, memory: &'a mut Memory, id: &str) -> &'a mut SourceMemory {
+        memory.tasks.harvest.sources.entry(id.to_string()).or_insert_with(SourceMemory::default)
     }
 }
 
 impl FlagProcessor for TaskHarvest {}
 
 impl Task for TaskHarvest {
-    fn run(&self, creep: &Creep) -> Result<bool, Box<dyn Error>> {
-        let memory = self.creep_memory(creep)?;
-        let sources = self.memory.dict_or_create("sources")?;
+    fn begin_tick(&self, world: &dyn World) {
+        // Copy last tick's counter
+        // 
+        for source in world.memory().tasks.harvest.sources.values_mut() {
+            source.prev_counter = source.counter;
+            source.counter = 0;
+        }
+    }
+
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let mut memory = world.memory();
 
         let mut source_opt = { // reading stored target from memory
-            match memory.string("source") {
-                Ok(Some(id)) => match get_object_typed::<Source>(&id) {
-                    Ok(Some(ref source)) if source.energy() == 0 => None,
+            match self.creep_memory(&mut memory, creep).target.clone() {
+                Some(id) => match world.source(&id) {
+                    Some(ref source) if source.energy == 0 => None,
                     _ => Some(id)
                 },
                 _ => None
@@ -110,24 +65,26 @@ impl Task for TaskHarvest {
 
         source_opt = match source_opt {
             None => { // selecting a new target
-                let mut source_ids = sources.keys();
-                source_ids.retain(|source_id| {
-                    (match get_object_typed::<Source>(&source_id) {
-                        Ok(Some(ref source)) if source.energy() == 0 => false,
-                        _ => true
-                    }) && self.get_counter(&source_id) < self.get_limit(&source_id)
-                });
+                let source_ids: Vec<String> = memory.tasks.harvest.sources.iter()
+                    .filter(|(source_id, source)| {
+                        (match world.source(&source_id) {
+                            Some(ref source) if source.energy == 0 => false,
+                            _ => true
+                        }) && source.prev_counter < source.creep_limit
+                    })
+                    .map(|(source_id, _)| source_id.clone())
+                    .collect();
 
                 match source_ids.len() {
                     0 => None,
                     len => {
                         let source_id = {
-                            let id = creep.memory().i32("id").unwrap_or(None).unwrap_or(0) as usize;
+                            let id = memory.creep(&creep.name).id as usize;
                             source_ids[id % len].to_owned()
                         };
 
-                        memory.set("source", &source_id);
-                        self.add_to_prev_counter(&source_id);
+                        self.creep_memory(&mut memory, creep).target = Some(source_id.clone());
+                        self.source_memory(&mut memory, &source_id).prev_counter += 1;
 
                         Some(source_id)
                     }
@@ -137,46 +94,33 @@ impl Task for TaskHarvest {
         };
         
         if let Some(source_id) = source_opt {
-            self.add_to_counter(&source_id);
-            memory.set("source", &source_id);
-
-            if let Some(pos) = 
-                sources.dict_or_create(&source_id)?.dict_or_create("pos").ok()
-                .and_then(|pos_root| {
-                    let x = pos_root.get("x").ok()?;
-                    let y = pos_root.get("y").ok()?;
-                    let room: String = pos_root.get("room").ok()?;
-
-                    Some(RoomPosition::new(x, y, &room))
-                }) 
-            { // we have a stored position
-                if creep.pos().is_near_to(&pos) {
-                    match get_object_typed::<Source>(&source_id)? {
+            self.source_memory(&mut memory, &source_id).counter += 1;
+            self.creep_memory(&mut memory, creep).target = Some(source_id.clone());
+
+            if let Some(pos) = self.source_memory(&mut memory, &source_id).pos.clone() { // we have a stored position
+                if creep.pos.is_near_to(&pos) {
+                    match world.source(&source_id) {
                         Some(source) => {
-                            creep.harvest(&source);
+                            world.creep_intent(&creep.name, CreepIntent::Harvest(source.id));
                         },
                         None => {
                             warn!("Invalid source: {} - bad pos, in a different room", source_id);
                         }
                     }
                 } else {
-                    creep.move_to(&pos);
+                    world.creep_intent(&creep.name, CreepIntent::MoveTo(pos));
                 }
             } else { // we do not have a stored position
-                match get_object_typed::<Source>(&source_id)? {
+                match world.source(&source_id) {
                     Some(source) => {
-                        if creep.pos().is_near_to(&source) {
-                            creep.harvest(&source);
+                        if creep.pos.is_near_to(&source.pos) {
+                            world.creep_intent(&creep.name, CreepIntent::Harvest(source.id.clone()));
                         } else {
-                            creep.move_to(&source);
+                            world.creep_intent(&creep.name, CreepIntent::MoveTo(source.pos.clone()));
                         }
                         
                         // write the position
-                        let source_pos = source.pos();
-                        let pos_root = sources.dict_or_create(&source_id)?.dict_or_create("pos")?;
-                        pos_root.set("x", source_pos.x());
-                        pos_root.set("y", source_pos.y());
-                        pos_root.set("room", source_pos.room_name());
+                        self.source_memory(&mut memory, &source_id).pos = Some(source.pos);
                     },
                     None => {
                         warn!("Invalid source: {} - missing pos", source_id);
@@ -184,17 +128,75 @@ impl Task for TaskHarvest {
                 }
             }
 
-            Ok(true)
+            Ok(TaskStatus::InProgress)
         } else {
-            Ok(false)
+            Ok(TaskStatus::Blocked)
         }
     }
 
     fn name(&self) -> &'static str {
         "harvest"
     }
+}
 
-    fn memory(&self) -> Result<MemoryReference, Box<dyn Error>> {
-        Ok(self.memory.clone())
+#[cfg(test)]
+mod tests {
+    use screeps::constants::Part;
+
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        Position,
+        SourceState
+    };
+
+    fn world_with_source() -> MockWorld {
+        let world = MockWorld::new();
+        world.add_source(SourceState{
+            id: "source".to_string(),
+            pos: Position::new(10, 10, "W1N1"),
+            energy: 3000,
+            energy_capacity: 3000,
+            ticks_to_regeneration: 300
+        });
+        world.memory().tasks.harvest.sources.insert("source".to_string(), SourceMemory{
+            creep_limit: 1,
+            ..SourceMemory::default()
+        });
+        world
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn harvests_next_to_source() {
+        let world = world_with_source();
+        let creep = mock::creep("harvester", Position::new(11, 10, "W1N1"), &[Part::Work, Part::Carry, Part::Move]);
+        world.add_creep(creep.clone());
+
+        assert_eq!(TaskHarvest::new().run(&world, &creep).unwrap(), TaskStatus::InProgress);
+        assert_eq!(world.creep_intents(), vec![("harvester".to_string(), CreepIntent::Harvest("source".to_string()))]);
+    }
+
+    #[test]
+    fn moves_to_distant_source() {
+        let world = world_with_source();
+        let creep = mock::creep("harvester", Position::new(30, 30, "W1N1"), &[Part::Work, Part::Carry, Part::Move]);
+        world.add_creep(creep.clone());
+
+        TaskHarvest::new().run(&world, &creep).unwrap();
+        assert_eq!(world.creep_intents(), vec![("harvester".to_string(), CreepIntent::MoveTo(Position::new(10, 10, "W1N1")))]);
+    }
+
+    #[test]
+    fn skips_full_source() {
+        let world = world_with_source();
+        world.memory().tasks.harvest.sources.get_mut("source").unwrap().creep_limit = 0;
+        let creep = mock::creep("harvester", Position::new(11, 10, "W1N1"), &[Part::Work, Part::Carry, Part::Move]);
+        world.add_creep(creep.clone());
+
+        assert_eq!(TaskHarvest::new().run(&world, &creep).unwrap(), TaskStatus::Blocked);
+        assert!(world.creep_intents().is_empty());
+    }
+}
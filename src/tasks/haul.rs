@@ -0,0 +1,185 @@
+use std::{
+    cmp,
+    error::Error,
+    rc::Rc
+};
+use screeps::constants::*;
+
+use crate::{
+    logistics::{
+        best_offer,
+        best_provider,
+        reservation_kind,
+        Logistics
+    },
+    memory::{
+        CreepTaskMemory,
+        RouteStop,
+        StopAction
+    },
+    reservations::Reservations,
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepIntent,
+        CreepState,
+        StructureKind,
+        World
+    }
+};
+
+/// A route has at most this many stops.
+const MAX_STOPS: usize = 4;
+
+/// A creep collects energy from a provider, and delivers it to one or more requesters, see `logistics::Logistics`.
+/// 
+/// The route is planned when the creep has none, and kept in `Memory.creeps.<creep>.tasks.haul.route`.  
+/// Every stop reserves its amount, so haulers split the work between them.
+pub struct TaskHaul {
+    logistics: Rc<Logistics>,
+    reservations: Rc<Reservations>
+}
+
+impl TaskHaul {
+    pub fn new(logistics: Rc<Logistics>, reservations: Rc<Reservations>) -> TaskHaul {
+        TaskHaul{
+            logistics: logistics,
+            reservations: reservations
+        }
+    }
+
+    /// Collects if the creep is less than half full, then delivers to the most urgent requesters.
+    fn plan(&self, world: &dyn World, creep: &CreepState) -> Vec<RouteStop> {
+        let mut requesters = self.logistics.requesters(world, &creep.pos.room);
+        if requesters.is_empty() {
+            return Vec::new();
+        }
+
+        let mut route = Vec::new();
+        let mut carried = creep.energy;
+        let mut from = creep.pos.clone();
+
+        if carried * 2 < creep.carry_capacity {
+            let providers = self.logistics.providers(world, &creep.pos.room);
+
+            if let Some(provider) = best_provider(&providers, &from) {
+                let amount = cmp::min(provider.amount, creep.carry_capacity - carried);
+                route.push(provider.stop(amount));
+                carried += amount;
+                from = provider.pos.clone();
+
+                // don't carry energy around in circles
+                requesters.retain(|requester| requester.id != provider.id &&
+                    !(provider.kind == Some(StructureKind::Storage) && requester.kind == Some(StructureKind::Storage)));
+            }
+        }
+
+        while carried > 0 && route.len() < MAX_STOPS {
+            let next = match best_offer(&requesters, &from) {
+                Some(requester) => requester.clone(),
+                None => break
+            };
+            requesters.retain(|requester| requester.id != next.id);
+
+            let amount = cmp::min(carried, next.amount);
+            route.push(next.stop(amount));
+            carried -= amount;
+            from = next.pos;
+        }
+
+        // nowhere to deliver to, so don't collect either
+        if route.iter().all(|stop| stop.action != StopAction::Transfer) {
+            route.clear();
+        }
+
+        route
+    }
+
+    fn release_stop(&self, stop: &RouteStop) {
+        self.reservations.release(&stop.id, reservation_kind(stop.action), stop.amount);
+    }
+}
+
+impl FlagProcessor for TaskHaul {}
+
+impl Task for TaskHaul {
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let mut route = self.creep_memory(&mut world.memory(), creep).route.clone();
+
+        // skip stops that can't be done with what the creep carries
+        while let Some(stop) = route.first().cloned() {
+            let skip = match stop.action {
+                StopAction::Transfer => creep.energy == 0,
+                _ => creep.energy >= creep.carry_capacity
+            };
+            if !skip {
+                break;
+            }
+
+            self.release_stop(&stop);
+            route.remove(0);
+        }
+
+        if route.is_empty() {
+            route = self.plan(world, creep);
+            for stop in route.iter() {
+                self.reservations.reserve(&stop.id, reservation_kind(stop.action), stop.amount);
+            }
+        }
+
+        let stop = match route.first().cloned() {
+            Some(stop) => stop,
+            None => {
+                self.creep_memory(&mut world.memory(), creep).route = route;
+                return Ok(TaskStatus::Blocked);
+            }
+        };
+
+        let intent = match stop.action {
+            StopAction::Withdraw => CreepIntent::Withdraw(stop.id.clone()),
+            StopAction::Pickup => CreepIntent::Pickup(stop.id.clone()),
+            StopAction::Transfer => CreepIntent::Transfer(stop.id.clone())
+        };
+
+        let status = match world.creep_intent(&creep.name, intent) {
+            ReturnCode::NotInRange => {
+                world.creep_intent(&creep.name, CreepIntent::MoveTo(stop.pos.clone()));
+                TaskStatus::InProgress
+            },
+            code => {
+                self.release_stop(&stop);
+                route.remove(0);
+
+                if code != ReturnCode::Ok {
+                    debug!("{} skipped a stop at {}: {:?}", creep.name, stop.id, code);
+                }
+
+                match route.first() {
+                    Some(next) => {
+                        // moving doesn't interfere with the transfer
+                        world.creep_intent(&creep.name, CreepIntent::MoveTo(next.pos.clone()));
+                        TaskStatus::InProgress
+                    },
+                    None if code == ReturnCode::Ok => TaskStatus::InProgress,
+                    None => TaskStatus::Done
+                }
+            }
+        };
+
+        self.creep_memory(&mut world.memory(), creep).route = route;
+        Ok(status)
+    }
+
+    fn name(&self) -> &'static str {
+        "haul"
+    }
+
+    fn restore(&self, _creep: &str, memory: &CreepTaskMemory) {
+        for stop in memory.route.iter() {
+            self.reservations.reserve(&stop.id, reservation_kind(stop.action), stop.amount);
+        }
+    }
+}
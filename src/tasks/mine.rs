@@ -0,0 +1,144 @@
+use std::error::Error;
+use screeps::constants::*;
+
+use crate::{
+    memory::{
+        Memory,
+        SourceMemory
+    },
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepIntent,
+        CreepState,
+        Position,
+        StructureKind,
+        World
+    }
+};
+
+const NAME: &'static str = "mine";
+
+/// Every creep mining the source.
+pub fn miners(world: &dyn World, source_id: &str) -> Vec<CreepState> {
+    let names: Vec<String> = world.memory().creeps.iter()
+        .filter(|(_, creep_memory)| {
+            creep_memory.tasks.get(NAME)
+                              .and_then(|task| task.target.as_ref())
+                              .map_or(false, |target| target == source_id)
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    names.iter().filter_map(|name| world.creep(name)).collect()
+}
+
+/// A creep parks next to its source for the rest of its life, and harvests it.
+/// 
+/// The energy goes into a link next to the creep if there is one, otherwise it overflows into the container under the creep.  
+/// The container's tile is kept in `Memory.tasks.harvest.sources.<id>.container`.
+/// The first miner to arrive picks the tile, and places the container.  
+/// The source is kept in `Memory.creeps.<creep>.tasks.mine.target`.
+pub struct TaskMine;
+
+impl TaskMine {
+    pub fn new() -> TaskMine {
+        TaskMine{}
+    }
+
+    #[inline]
+    fn source_memory<'a>(&self, memory: &'a mut Memory, id: &str) -> &'a mut SourceMemory {
+        memory.tasks.harvest.sources.entry(id.to_string()).or_insert_with(SourceMemory::default)
+    }
+
+    /// Picks the source in the creep's home room whose miners die the soonest.  
+    /// A replacement ends up next to the miner it replaces this way.
+    fn assign(&self, world: &dyn World, creep: &CreepState) -> Option<String> {
+        let home = world.memory().creep(&creep.name).home.clone();
+
+        world.sources(&home).into_iter()
+            .min_by_key(|source| {
+                miners(world, &source.id).iter()
+                    .filter(|miner| miner.name != creep.name)
+                    .map(|miner| if miner.spawning { u32::max_value() } else { miner.ticks_to_live })
+                    .max()
+                    .unwrap_or(0)
+            })
+            .map(|source| source.id)
+    }
+
+    fn source_pos(&self, world: &dyn World, id: &str) -> Option<Position> {
+        let cached = self.source_memory(&mut world.memory(), id).pos.clone();
+
+        cached.or_else(|| {
+            let pos = world.source(id).map(|source| source.pos);
+            self.source_memory(&mut world.memory(), id).pos = pos.clone();
+            pos
+        })
+    }
+}
+
+impl FlagProcessor for TaskMine {}
+
+impl Task for TaskMine {
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let locked = self.creep_memory(&mut world.memory(), creep).target.clone();
+        let source_id = match locked.or_else(|| self.assign(world, creep)) {
+            Some(id) => id,
+            None => return Ok(TaskStatus::Blocked)
+        };
+        self.creep_memory(&mut world.memory(), creep).target = Some(source_id.clone());
+
+        let source_pos = match self.source_pos(world, &source_id) {
+            Some(pos) => pos,
+            None => return Ok(TaskStatus::Failed(format!("no position for source {}", source_id)))
+        };
+        let container = self.source_memory(&mut world.memory(), &source_id).container.clone();
+
+        if !creep.pos.is_near_to(&source_pos) {
+            world.creep_intent(&creep.name, CreepIntent::MoveTo(container.unwrap_or(source_pos)));
+            return Ok(TaskStatus::InProgress);
+        }
+
+        match container {
+            Some(ref tile) if *tile != creep.pos => {
+                // the miner we're replacing is still parked there
+                let occupied = world.my_creeps().iter().any(|other| other.pos == *tile);
+                if !occupied {
+                    world.creep_intent(&creep.name, CreepIntent::MoveTo(tile.clone()));
+                }
+            },
+            Some(_) => (),
+            None => {
+                self.source_memory(&mut world.memory(), &source_id).container = Some(creep.pos.clone());
+                let code = world.create_construction_site(&creep.pos, StructureKind::Container);
+                if code != ReturnCode::Ok {
+                    warn!("failed to place container for source {}: {:?}", source_id, code);
+                }
+            }
+        }
+
+        // with a link next to it, what the miner carries doesn't have to be hauled
+        if creep.carry_capacity > 0 && creep.energy >= creep.carry_capacity {
+            let link = world.structures(&creep.pos.room).into_iter().find(|structure| {
+                structure.kind == StructureKind::Link && structure.my &&
+                    structure.energy < structure.energy_capacity && creep.pos.is_near_to(&structure.pos)
+            });
+            if let Some(link) = link {
+                world.creep_intent(&creep.name, CreepIntent::Transfer(link.id));
+            }
+        }
+
+        match world.creep_intent(&creep.name, CreepIntent::Harvest(source_id.clone())) {
+            ReturnCode::Ok | ReturnCode::NotEnough => Ok(TaskStatus::InProgress), // empty sources regenerate
+            code => Ok(TaskStatus::Failed(format!("can't mine {}: {:?}", source_id, code)))
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
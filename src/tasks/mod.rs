@@ -1,8 +1,16 @@
 /// A creep moves to the closest construction site, and attempts to build it.
 pub mod build;
+/// A creep takes dropped energy, or withdraws it.
+pub mod collect;
+/// A creep collects energy, and delivers it where it's needed.
+pub mod haul;
 /// A creep moves to its assigned source, and begins harvesting.
 pub mod harvest;
+/// A creep parks next to a source for good, and harvests it.
+pub mod mine;
 /// A creep moves to the next empty object, and refills it.
 pub mod refill;
+/// Owns every task, and frees their memory.
+pub mod registry;
 /// A creep moves to its room's controller, and upgrades it.
 pub mod upgrade;
@@ -1,48 +1,161 @@
-use std::error::Error;
-use screeps::{
-    prelude::*,
-    objects::Creep,
-    constants::*,
+use std::{
+    error::Error,
+    rc::Rc
 };
+use screeps::constants::*;
 
-use crate::traits::{
-    Task,
-    FlagProcessor
+use crate::{
+    logistics::{
+        best_offer,
+        Logistics
+    },
+    memory::CreepTaskMemory,
+    reservations::{
+        ReservationKind,
+        Reservations
+    },
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        CreepIntent,
+        CreepState,
+        StructureKind,
+        World
+    }
 };
 
-/// A creep moves to the next empty object, and refills it.
-pub struct TaskRefill;
+/// A creep takes the energy it carries to the most urgent requester, see `logistics::Logistics`.
+/// 
+/// Storage is left to haulers. The requester is kept in `Memory.creeps.<creep>.tasks.refill.target`
+/// until it's full, and the energy the creep brings is reserved on it.
+pub struct TaskRefill {
+    logistics: Rc<Logistics>,
+    reservations: Rc<Reservations>
+}
 
 impl TaskRefill {
-    pub fn new() -> TaskRefill {
-        TaskRefill{}
+    pub fn new(logistics: Rc<Logistics>, reservations: Rc<Reservations>) -> TaskRefill {
+        TaskRefill{
+            logistics: logistics,
+            reservations: reservations
+        }
     }
 }
 
 impl FlagProcessor for TaskRefill {}
 
 impl Task for TaskRefill {
-    fn run(&self, creep: &Creep) -> Result<bool, Box<dyn Error>> {
-        let mut targets = creep.room().find(find::STRUCTURES);
-        let mut filt = targets.drain_filter(|structure| {
-            match structure.as_can_store_energy() {
-                Some(structure) => structure.energy() < structure.energy_capacity(),
-                None => false
-            }
-            
-        });
-        
-        if let Some(target_src) = filt.next() {
-            if creep.transfer_all(target_src.as_transferable().unwrap(), ResourceType::Energy) == ReturnCode::NotInRange {
-                creep.move_to(&target_src);
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let locked = self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyIn);
+        let had_target = locked.is_some();
+
+        let requesters: Vec<_> = self.logistics.requesters(world, &creep.pos.room).into_iter()
+                                     .filter(|offer| offer.kind != Some(StructureKind::Storage))
+                                     .collect();
+        let target = locked.and_then(|id| requesters.iter().find(|offer| offer.id == id))
+                           .or_else(|| best_offer(&requesters, &creep.pos));
+
+        let target = match target {
+            Some(target) => target,
+            None => return Ok(if had_target { TaskStatus::Done } else { TaskStatus::Blocked })
+        };
+
+        let amount = creep.energy.min(target.amount);
+        self.reservations.lock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyIn, &target.id, amount);
+
+        match world.creep_intent(&creep.name, CreepIntent::Transfer(target.id.clone())) {
+            ReturnCode::Ok => {
+                // the transfer empties the creep or fills the target, either way it's delivered
+                self.reservations.settle(self.creep_memory(&mut world.memory(), creep));
+                Ok(TaskStatus::InProgress)
+            },
+            ReturnCode::NotInRange => {
+                world.creep_intent(&creep.name, CreepIntent::MoveTo(target.pos.clone()));
+                Ok(TaskStatus::InProgress)
+            },
+            ReturnCode::Full => {
+                self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyIn);
+                Ok(TaskStatus::Done)
+            },
+            code => {
+                self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::EnergyIn);
+                Ok(TaskStatus::Failed(format!("can't refill {}: {:?}", target.id, code)))
             }
-            Ok(true)
-        } else {
-            Ok(false)
         }
     }
 
     fn name(&self) -> &'static str {
         "refill"
     }
-}
\ No newline at end of file
+
+    fn restore(&self, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.restore(memory, ReservationKind::EnergyIn);
+    }
+
+    fn release(&self, _world: &dyn World, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.free(memory, ReservationKind::EnergyIn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        mock::{
+            self,
+            MockWorld
+        },
+        Position
+    };
+
+    fn world_with_extension() -> MockWorld {
+        let world = MockWorld::new();
+        let mut extension = mock::structure("extension", StructureKind::Extension, Position::new(10, 10, "W1N1"));
+        extension.energy_capacity = 50;
+        world.add_structure(extension);
+        world
+    }
+
+    fn filler(world: &MockWorld, pos: Position) -> CreepState {
+        let mut creep = mock::creep("filler", pos, &[Part::Carry, Part::Move]);
+        creep.energy = 50;
+        world.add_creep(creep.clone());
+        creep
+    }
+
+    fn task() -> (TaskRefill, Rc<Reservations>) {
+        let reservations = Rc::new(Reservations::new());
+        let logistics = Rc::new(Logistics::new(reservations.clone()));
+        (TaskRefill::new(logistics, reservations.clone()), reservations)
+    }
+
+    #[test]
+    fn transfers_next_to_requester() {
+        let world = world_with_extension();
+        let creep = filler(&world, Position::new(11, 10, "W1N1"));
+        let (task, reservations) = task();
+
+        assert_eq!(task.run(&world, &creep).unwrap(), TaskStatus::InProgress);
+        assert_eq!(world.creep_intents(), vec![("filler".to_string(), CreepIntent::Transfer("extension".to_string()))]);
+        assert_eq!(reservations.amount("extension", ReservationKind::EnergyIn), 50);
+
+        // delivered, so the next tick doesn't reserve it again
+        let memory = task.creep_memory(&mut world.memory(), &creep).clone();
+        assert_eq!(memory.target, Some("extension".to_string()));
+        assert_eq!(memory.reserved, 0);
+    }
+
+    #[test]
+    fn reserves_while_moving() {
+        let world = world_with_extension();
+        let creep = filler(&world, Position::new(30, 30, "W1N1"));
+        let (task, reservations) = task();
+
+        assert_eq!(task.run(&world, &creep).unwrap(), TaskStatus::InProgress);
+        assert_eq!(world.creep_intents(), vec![("filler".to_string(), CreepIntent::MoveTo(Position::new(10, 10, "W1N1")))]);
+        assert_eq!(reservations.amount("extension", ReservationKind::EnergyIn), 50);
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+
+use hashbrown::{
+    HashMap,
+    HashSet
+};
+
+use crate::{
+    memory::CreepTaskMemory,
+    traits::{
+        Task,
+        TaskStatus
+    },
+    world::{
+        CreepState,
+        World
+    }
+};
+
+/// A creep's memory of a task is dropped after not running it for this long.
+const STALE_AFTER: u32 = 300;
+
+/// Owns every task, keyed by `Task::name`.
+/// 
+/// Running tasks through the registry keeps track of which creep uses which task,
+/// and frees `Memory.creeps.<creep>.tasks.<task>` once the creep stops using it, or dies.
+pub struct TaskRegistry {
+    tasks: HashMap<&'static str, Box<dyn Task>>
+}
+
+impl TaskRegistry {
+    pub fn new() -> TaskRegistry {
+        TaskRegistry{
+            tasks: HashMap::new()
+        }
+    }
+
+    /// Adds a task, replacing any other with the same name.
+    pub fn register(&mut self, task: Box<dyn Task>) {
+        if self.tasks.insert(task.name(), task).is_some() {
+            warn!("a task was registered twice");
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Task> {
+        self.tasks.get(name).map(|task| task.as_ref())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tasks.contains_key(name)
+    }
+
+    /// Runs a task by name.
+    pub fn run(&self, name: &str, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        let task = self.get(name).ok_or_else(|| format!("unknown task {}", name))?;
+        let status = task.run(world, creep)?;
+
+        if status.is_in_progress() {
+            task.creep_memory(&mut world.memory(), creep).last_run = world.time();
+        }
+
+        Ok(status)
+    }
+
+    /// Frees the creep's memory of the tasks it stopped using, such as its work tasks once it's empty.
+    pub fn leave(&self, world: &dyn World, creep: &CreepState, names: &[&'static str]) {
+        for name in names.iter() {
+            let task_memory = world.memory().creep(&creep.name).tasks.remove(*name);
+            if let (Some(task), Some(task_memory)) = (self.get(name), task_memory) {
+                task.release(world, &creep.name, &task_memory);
+            }
+        }
+    }
+
+    /// Starts the tick for every task, and frees the memory of tasks that aren't used anymore.
+    pub fn begin_tick(&self, world: &dyn World) {
+        for task in self.tasks.values() {
+            task.begin_tick(world);
+        }
+
+        self.cleanup(world);
+        self.restore(world);
+    }
+
+    fn restore(&self, world: &dyn World) {
+        let memory = world.memory();
+        for (creep, creep_memory) in memory.creeps.iter() {
+            for (name, task_memory) in creep_memory.tasks.iter() {
+                if let Some(task) = self.get(name) {
+                    task.restore(creep, task_memory);
+                }
+            }
+        }
+    }
+
+    fn cleanup(&self, world: &dyn World) {
+        let time = world.time();
+        let alive: HashSet<String> = world.my_creeps().into_iter().map(|creep| creep.name).collect();
+        let mut released: Vec<(String, String, CreepTaskMemory)> = Vec::new();
+
+        {
+            let mut memory = world.memory();
+            for (name, creep_memory) in memory.creeps.iter_mut() {
+                let alive = alive.contains(name);
+
+                let stale: Vec<String> = creep_memory.tasks.iter()
+                    .filter(|(task, task_memory)| {
+                        !alive || !self.contains(task.as_str()) || time.saturating_sub(task_memory.last_run) > STALE_AFTER
+                    })
+                    .map(|(task, _)| task.clone())
+                    .collect();
+
+                for task in stale {
+                    if let Some(task_memory) = creep_memory.tasks.remove(&task) {
+                        released.push((name.clone(), task, task_memory));
+                    }
+                }
+            }
+        }
+
+        // memory is released, so tasks can use it
+        for (creep, name, task_memory) in released {
+            if let Some(task) = self.get(&name) {
+                task.release(world, &creep, &task_memory);
+            }
+        }
+    }
+}
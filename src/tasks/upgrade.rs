@@ -1,41 +1,93 @@
-use std::error::Error;
-use screeps::{
-    prelude::*,
-    objects::{
-        Creep,
-        StructureController
-    },
-    constants::*
+use std::{
+    error::Error,
+    rc::Rc
 };
+use screeps::constants::*;
 
-use crate::traits::{
-    Task,
-    FlagProcessor
+use crate::{
+    memory::CreepTaskMemory,
+    reservations::{
+        ReservationKind,
+        Reservations
+    },
+    traits::{
+        Task,
+        TaskStatus,
+        FlagProcessor
+    },
+    world::{
+        ControllerState,
+        CreepIntent,
+        CreepState,
+        World
+    }
 };
 
+/// A fully upgraded controller only takes this much energy per tick.
+const MAX_UPGRADE_PER_TICK: u32 = 15;
+
 /// A creep moves to its room's controller, and upgrades it.
-pub struct TaskUpgrade;
+/// 
+/// The creep's work parts are reserved on the controller,  
+/// so no more creeps upgrade a level 8 controller than it can take.
+pub struct TaskUpgrade {
+    reservations: Rc<Reservations>
+}
 
 impl TaskUpgrade {
-    pub fn new() -> TaskUpgrade {
-        TaskUpgrade{}
+    pub fn new(reservations: Rc<Reservations>) -> TaskUpgrade {
+        TaskUpgrade{
+            reservations: reservations
+        }
     }
 }
 
 impl FlagProcessor for TaskUpgrade {}
 
 impl Task for TaskUpgrade {
-    fn run(&self, creep: &Creep) -> Result<bool, Box<dyn Error>> {
-        let controller: StructureController = creep.room().controller().ok_or("there is no controller")?;
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>> {
+        self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::Work);
+
+        let controller: ControllerState = match world.room(&creep.pos.room).and_then(|room| room.controller) {
+            Some(controller) => controller,
+            None => return Ok(TaskStatus::Failed("there is no controller".to_string()))
+        };
 
-        if creep.upgrade_controller(&controller) == ReturnCode::NotInRange {
-            creep.move_to(&controller);
+        if controller.level >= 8 && self.reservations.amount(&controller.id, ReservationKind::Work) >= MAX_UPGRADE_PER_TICK {
+            return Ok(TaskStatus::Blocked);
         }
 
-        Ok(true)
+        let work = creep.active_parts(Part::Work);
+        self.reservations.lock(self.creep_memory(&mut world.memory(), creep), ReservationKind::Work, &controller.id, work);
+
+        match world.creep_intent(&creep.name, CreepIntent::UpgradeController(controller.id.clone())) {
+            ReturnCode::Ok => {
+                // a work part spends one energy per tick, so this is the creep's last
+                if creep.energy <= work {
+                    self.reservations.settle(self.creep_memory(&mut world.memory(), creep));
+                }
+                Ok(TaskStatus::InProgress)
+            },
+            ReturnCode::NotInRange => {
+                world.creep_intent(&creep.name, CreepIntent::MoveTo(controller.pos.clone()));
+                Ok(TaskStatus::InProgress)
+            },
+            code => {
+                self.reservations.unlock(self.creep_memory(&mut world.memory(), creep), ReservationKind::Work);
+                Ok(TaskStatus::Failed(format!("can't upgrade {}: {:?}", controller.id, code)))
+            }
+        }
     }
 
     fn name(&self) -> &'static str {
         "upgrade"
     }
-}
\ No newline at end of file
+
+    fn restore(&self, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.restore(memory, ReservationKind::Work);
+    }
+
+    fn release(&self, _world: &dyn World, _creep: &str, memory: &CreepTaskMemory) {
+        self.reservations.free(memory, ReservationKind::Work);
+    }
+}
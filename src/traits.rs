@@ -1,22 +1,27 @@
-use screeps::{
-    memory::{
-        MemoryReference,
-        root
-    },
-    objects::{
-        Creep,
-        RoomPosition
-    },
-    constants::Part
-};
+use screeps::constants::Part;
 
 use std::{
     error::Error,
     str::SplitWhitespace
 };
 
+use crate::{
+    body::BodyTemplate,
+    memory::{
+        CreepTaskMemory,
+        Memory
+    },
+    spawning::SpawnRequest,
+    world::{
+        CreepState,
+        Position,
+        RoomState,
+        World
+    }
+};
+
 pub trait FlagProcessor {
-    fn flag(&self, _cmd: SplitWhitespace, _pos: RoomPosition) -> Result<bool, Box<dyn Error>> {
+    fn flag(&self, _cmd: SplitWhitespace, _pos: Position) -> Result<bool, Box<dyn Error>> {
         Ok(false)
     }
 }
@@ -31,38 +36,97 @@ pub trait Role: FlagProcessor {
 
     fn limit(&self) -> i32;
 
-    /// The part layout of the next creep to spawn
-    fn next_creep(&self) -> Vec<Part>;
+    /// Called once at the start of every tick, before any creep runs.
+    fn begin_tick(&self, world: &dyn World) {
+        world.memory().role(self.name()).run_count = 0;
+    }
+
+    /// The part layout of the creeps to spawn, scaled by `next_creep`.
+    fn body(&self) -> BodyTemplate;
+
+    /// The part layout of the next creep to spawn, given the room's energy capacity.
+    fn next_creep(&self, energy_capacity: u32) -> Vec<Part> {
+        let body = self.body();
+        body.generate(energy_capacity).unwrap_or_else(|| body.minimal())
+    }
 
-    fn run_count(&self) -> i32;
+    fn run_count(&self, world: &dyn World) -> i32;
 
-    fn run(&self, creep: &Creep) -> Result<(), Box<dyn Error>>;
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<(), Box<dyn Error>>;
 
     /// The lower this number, the more creeps there will be overall
     /// Default range: 10-100
     fn spawn_priority(&self) -> i32;
+
+    /// A room can't function without this role.  
+    /// When it has none left, a small creep is spawned immediately.
+    fn essential(&self) -> bool {
+        false
+    }
+
+    /// Requests a new creep for `room`, given how many creeps of this role call it home.
+    fn spawn_request(&self, _world: &dyn World, room: &RoomState, count: u32) -> Option<SpawnRequest> {
+        if (count as i32) < self.limit() {
+            Some(SpawnRequest{
+                role: self.name(),
+                room: room.name.clone(),
+                body: self.next_creep(room.energy_capacity_available),
+                priority: (count as i32 + 1) * self.spawn_priority(),
+                bootstrap: false
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// What a task did with a creep this tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The creep acted on its target, and will continue next tick.
+    InProgress,
+    /// The target is finished, the creep is free for another task.
+    Done,
+    /// The task can't be done, and why.
+    Failed(String),
+    /// There is nothing for the task to do right now.
+    Blocked
+}
+
+impl TaskStatus {
+    /// Whether the creep spent its tick on the task.
+    pub fn is_in_progress(&self) -> bool {
+        *self == TaskStatus::InProgress
+    }
 }
 
 /// Represents a creep's task.
 /// A creep should only execute one task per tick.
 pub trait Task: FlagProcessor {
-    /// Returns true if the task was executed.
-    fn run(&self, creep: &Creep) -> Result<bool, Box<dyn Error>>;
+    /// Only `TaskStatus::InProgress` means the creep acted this tick,  
+    /// otherwise the role may try another task.
+    fn run(&self, world: &dyn World, creep: &CreepState) -> Result<TaskStatus, Box<dyn Error>>;
 
     fn name(&self) -> &'static str {
         "undefined"
     }
 
-    fn creep_memory(&self, creep: &Creep) -> Result<MemoryReference, Box<dyn Error>> {
-        creep.memory()
-             .dict_or_create("tasks")
-             .and_then(|mem| mem.dict_or_create(self.name()))
-          .or(Err(Box::from("error accessing creep memory")))
-    }
+    /// Called once at the start of every tick, before any creep runs.
+    fn begin_tick(&self, _world: &dyn World) {}
+
+    /// Called at the start of every tick for each creep remembering the task, after unused memory was freed.  
+    /// Rebuilds whatever the task keeps on the heap, such as reservations.
+    fn restore(&self, _creep: &str, _memory: &CreepTaskMemory) {}
+
+    /// Called when a creep stopped using the task, or died.  
+    /// Its memory of the task has already been removed, and is passed in.
+    fn release(&self, _world: &dyn World, _creep: &str, _memory: &CreepTaskMemory) {}
 
-    fn memory(&self) -> Result<MemoryReference, Box<dyn Error>> {
-        root().dict_or_create("tasks")
-             .and_then(|mem| mem.dict_or_create(self.name()))
-          .or(Err(Box::from("error accessing global memory")))
+    /// `Memory.creeps.<creep>.tasks.<task>`
+    fn creep_memory<'a>(&self, memory: &'a mut Memory, creep: &CreepState) -> &'a mut CreepTaskMemory {
+        memory.creep(&creep.name)
+              .tasks
+              .entry(self.name().to_string())
+              .or_insert_with(CreepTaskMemory::default)
     }
 }
@@ -0,0 +1,688 @@
+use std::cell::{
+    Cell,
+    RefCell,
+    RefMut
+};
+
+use serde_json;
+use stdweb::{
+    Reference,
+    unstable::TryInto
+};
+use screeps::{
+    constants::*,
+    game,
+    objects::*,
+    prelude::*
+};
+
+use crate::memory::Memory;
+use super::*;
+
+/// The real game, accessed through `screeps-game-api`.
+pub struct LiveWorld {
+    memory: RefCell<Memory>,
+    /// The `RawMemory` string `memory` matches, the one last written or parsed.  
+    /// Anything else was changed from the console, and is parsed again.
+    raw: RefCell<String>,
+    /// `RawMemory` couldn't be parsed, so it's never written back until it's fixed.
+    broken: Cell<bool>
+}
+
+fn raw_memory() -> String {
+    js!(return RawMemory.get();).try_into().unwrap_or_default()
+}
+
+impl LiveWorld {
+    /// Parses `RawMemory`, the world lives until the VM is reset.  
+    /// If it can't be parsed, the bot runs from scratch without saving, and the player's memory is left alone.
+    pub fn new() -> LiveWorld {
+        let world = LiveWorld{
+            memory: RefCell::new(Memory::fresh()),
+            raw: RefCell::new(String::new()),
+            broken: Cell::new(false)
+        };
+        world.load(raw_memory());
+        world
+    }
+
+    /// Called at the start of every tick after the first.  
+    /// `RawMemory` is only parsed again if it isn't what the bot wrote last tick,
+    /// so edits from the console take effect, and memory that failed to parse is retried once it's fixed.
+    pub fn reload_memory(&self) {
+        let raw = raw_memory();
+        if raw != *self.raw.borrow() {
+            self.load(raw);
+        }
+    }
+
+    fn load(&self, raw: String) {
+        match Memory::parse(&raw) {
+            Ok(memory) => {
+                if self.broken.get() {
+                    info!("memory was fixed, loading it");
+                }
+                *self.memory.borrow_mut() = memory;
+                self.broken.set(false);
+            },
+            // the last memory that parsed keeps running, but isn't saved over the player's
+            Err(err) => {
+                error!("failed to load memory, it won't be saved until it's fixed: {}", err);
+                self.broken.set(true);
+            }
+        }
+        *self.raw.borrow_mut() = raw;
+    }
+}
+
+fn structure_type(kind: StructureKind) -> Option<StructureType> {
+    match kind {
+        StructureKind::Spawn => Some(StructureType::Spawn),
+        StructureKind::Extension => Some(StructureType::Extension),
+        StructureKind::Road => Some(StructureType::Road),
+        StructureKind::Wall => Some(StructureType::Wall),
+        StructureKind::Rampart => Some(StructureType::Rampart),
+        StructureKind::Link => Some(StructureType::Link),
+        StructureKind::Storage => Some(StructureType::Storage),
+        StructureKind::Tower => Some(StructureType::Tower),
+        StructureKind::Observer => Some(StructureType::Observer),
+        StructureKind::PowerSpawn => Some(StructureType::PowerSpawn),
+        StructureKind::Extractor => Some(StructureType::Extractor),
+        StructureKind::Lab => Some(StructureType::Lab),
+        StructureKind::Terminal => Some(StructureType::Terminal),
+        StructureKind::Container => Some(StructureType::Container),
+        StructureKind::Nuker => Some(StructureType::Nuker),
+        _ => None
+    }
+}
+
+fn position(pos: &RoomPosition) -> Position {
+    Position::new(pos.x(), pos.y(), &pos.room_name())
+}
+
+fn room_position(pos: &Position) -> RoomPosition {
+    RoomPosition::new(pos.x, pos.y, &pos.room)
+}
+
+#[derive(Deserialize)]
+struct RawBodyPart {
+    #[serde(rename = "type")]
+    part: String,
+    boost: Option<String>,
+    hits: u32
+}
+
+fn creep_state(creep: &Creep) -> CreepState {
+    let raw_body: String = js!(return JSON.stringify(@{creep.as_ref()}.body);).try_into().unwrap_or_default();
+    let body = serde_json::from_str::<Vec<RawBodyPart>>(&raw_body).unwrap_or_default()
+        .into_iter()
+        .filter_map(|raw| part_from_str(&raw.part).map(|part| BodyPart{
+            part: part,
+            boost: raw.boost,
+            hits: raw.hits
+        }))
+        .collect();
+
+    CreepState{
+        name: creep.name(),
+        id: creep.id(),
+        pos: position(&creep.pos()),
+        owner: creep.owner_name(),
+        my: creep.my(),
+        body: body,
+        energy: creep.energy(),
+        carry_capacity: creep.carry_capacity(),
+        hits: creep.hits(),
+        hits_max: creep.hits_max(),
+        fatigue: creep.fatigue(),
+        ticks_to_live: creep.ticks_to_live(),
+        spawning: creep.spawning()
+    }
+}
+
+/// Works with both the old `energy`/`storeCapacity` and the newer `store` API.
+#[derive(Deserialize)]
+struct RawStructure {
+    my: bool,
+    hits: u32,
+    #[serde(rename = "hitsMax")]
+    hits_max: u32,
+    energy: u32,
+    #[serde(rename = "energyCapacity")]
+    energy_capacity: u32,
+    cooldown: u32
+}
+
+fn structure_state(structure: &Structure) -> StructureState {
+    let reference: &Reference = structure.as_ref();
+    let raw: String = js!(
+        var s = @{reference};
+        var energy = s.store ? (s.store[RESOURCE_ENERGY] || 0) : (s.energy || 0);
+        var capacity = (s.store && s.store.getCapacity) ? (s.store.getCapacity(RESOURCE_ENERGY) || 0)
+                                                         : (s.energyCapacity || s.storeCapacity || 0);
+        return JSON.stringify({
+            my: !!s.my,
+            hits: s.hits || 0,
+            hitsMax: s.hitsMax || 0,
+            energy: energy,
+            energyCapacity: capacity,
+            cooldown: s.cooldown || 0
+        });
+    ).try_into().unwrap_or_default();
+    let raw: RawStructure = serde_json::from_str(&raw).unwrap_or(RawStructure{
+        my: false,
+        hits: 0,
+        hits_max: 0,
+        energy: 0,
+        energy_capacity: 0,
+        cooldown: 0
+    });
+    let kind: String = js!(return @{reference}.structureType;).try_into().unwrap_or_default();
+
+    StructureState{
+        id: structure.id(),
+        kind: StructureKind::from_str(&kind),
+        pos: position(&structure.pos()),
+        my: raw.my,
+        hits: raw.hits,
+        hits_max: raw.hits_max,
+        energy: raw.energy,
+        energy_capacity: raw.energy_capacity,
+        cooldown: raw.cooldown
+    }
+}
+
+fn source_state(source: &Source) -> SourceState {
+    SourceState{
+        id: source.id(),
+        pos: position(&source.pos()),
+        energy: source.energy(),
+        energy_capacity: source.energy_capacity(),
+        ticks_to_regeneration: source.ticks_to_regeneration()
+    }
+}
+
+fn mineral_state(mineral: &Mineral) -> MineralState {
+    MineralState{
+        id: mineral.id(),
+        pos: position(&mineral.pos())
+    }
+}
+
+fn site_state(site: &ConstructionSite) -> SiteState {
+    let kind: String = js!(return @{site.as_ref()}.structureType;).try_into().unwrap_or_default();
+
+    SiteState{
+        id: site.id(),
+        kind: StructureKind::from_str(&kind),
+        pos: position(&site.pos()),
+        progress: site.progress(),
+        progress_total: site.progress_total()
+    }
+}
+
+fn resource_state(resource: &Resource) -> ResourceState {
+    ResourceState{
+        id: resource.id(),
+        pos: position(&resource.pos()),
+        amount: resource.amount()
+    }
+}
+
+#[derive(Deserialize)]
+struct RawRemains {
+    id: String,
+    kind: String,
+    x: u32,
+    y: u32,
+    energy: u32,
+    store: u32,
+    #[serde(rename = "ticksToDecay")]
+    ticks_to_decay: u32
+}
+
+#[derive(Deserialize)]
+struct RawInvaderCore {
+    id: String,
+    x: u32,
+    y: u32,
+    level: u32,
+    hits: u32,
+    #[serde(rename = "ticksToDeploy")]
+    ticks_to_deploy: u32,
+    #[serde(rename = "ticksToCollapse")]
+    ticks_to_collapse: Option<u32>
+}
+
+/// Same as the game's `ERR_*` constants.
+fn return_code(code: i32) -> ReturnCode {
+    match code {
+        0 => ReturnCode::Ok,
+        -1 => ReturnCode::NotOwner,
+        -2 => ReturnCode::NoPath,
+        -3 => ReturnCode::NameExists,
+        -4 => ReturnCode::Busy,
+        -5 => ReturnCode::NotFound,
+        -6 => ReturnCode::NotEnough,
+        -7 => ReturnCode::InvalidTarget,
+        -8 => ReturnCode::Full,
+        -9 => ReturnCode::NotInRange,
+        -11 => ReturnCode::Tired,
+        -12 => ReturnCode::NoBodypart,
+        -14 => ReturnCode::RclNotEnough,
+        -15 => ReturnCode::GclNotEnough,
+        _ => ReturnCode::InvalidArgs
+    }
+}
+
+fn room_state(room: &Room) -> RoomState {
+    RoomState{
+        name: room.name(),
+        energy_available: room.energy_available(),
+        energy_capacity_available: room.energy_capacity_available(),
+        controller: room.controller().map(|controller| ControllerState{
+            id: controller.id(),
+            pos: position(&controller.pos()),
+            my: controller.my(),
+            level: controller.level(),
+            progress: controller.progress().unwrap_or(0),
+            progress_total: controller.progress_total().unwrap_or(0),
+            safe_mode: js!(return @{controller.as_ref()}.safeMode || 0;).try_into().unwrap_or(0),
+            safe_mode_available: js!(return @{controller.as_ref()}.safeModeAvailable || 0;).try_into().unwrap_or(0),
+            safe_mode_cooldown: js!(return @{controller.as_ref()}.safeModeCooldown || 0;).try_into().unwrap_or(0)
+        })
+    }
+}
+
+fn spawn_state(spawn: &StructureSpawn) -> SpawnState {
+    SpawnState{
+        id: spawn.id(),
+        name: spawn.name(),
+        pos: position(&spawn.pos()),
+        spawning: spawn.is_spawning()
+    }
+}
+
+impl World for LiveWorld {
+    fn time(&self) -> u32 {
+        game::time()
+    }
+
+    fn my_creeps(&self) -> Vec<CreepState> {
+        game::creeps::values().iter().map(creep_state).collect()
+    }
+
+    fn creep(&self, name: &str) -> Option<CreepState> {
+        game::creeps::get(name).map(|creep| creep_state(&creep))
+    }
+
+    fn creep_by_id(&self, id: &str) -> Option<CreepState> {
+        game::get_object_typed::<Creep>(id).unwrap_or(None).map(|creep| creep_state(&creep))
+    }
+
+    fn rooms(&self) -> Vec<RoomState> {
+        game::rooms::values().iter().map(room_state).collect()
+    }
+
+    fn room(&self, name: &str) -> Option<RoomState> {
+        game::rooms::get(name).map(|room| room_state(&room))
+    }
+
+    fn spawns(&self) -> Vec<SpawnState> {
+        game::spawns::values().iter().map(spawn_state).collect()
+    }
+
+    fn sources(&self, room: &str) -> Vec<SourceState> {
+        game::rooms::get(room)
+            .map(|room| room.find(find::SOURCES).iter().map(source_state).collect())
+            .unwrap_or_default()
+    }
+
+    fn source(&self, id: &str) -> Option<SourceState> {
+        game::get_object_typed::<Source>(id).unwrap_or(None).map(|source| source_state(&source))
+    }
+
+    fn structures(&self, room: &str) -> Vec<StructureState> {
+        game::rooms::get(room)
+            .map(|room| room.find(find::STRUCTURES).iter().map(structure_state).collect())
+            .unwrap_or_default()
+    }
+
+    fn structure(&self, id: &str) -> Option<StructureState> {
+        game::get_object_typed::<Structure>(id).unwrap_or(None).map(|structure| structure_state(&structure))
+    }
+
+    fn minerals(&self, room: &str) -> Vec<MineralState> {
+        game::rooms::get(room)
+            .map(|room| room.find(find::MINERALS).iter().map(mineral_state).collect())
+            .unwrap_or_default()
+    }
+
+    fn terrain(&self, room: &str) -> RoomTerrain {
+        let raw: String = js!(
+            var terrain = Game.map.getRoomTerrain(@{room});
+            var rows = [];
+            for (var y = 0; y < 50; y++) {
+                var row = "";
+                for (var x = 0; x < 50; x++) {
+                    var tile = terrain.get(x, y);
+                    row += (tile & TERRAIN_MASK_WALL) ? "x" : (tile & TERRAIN_MASK_SWAMP) ? "~" : " ";
+                }
+                rows.push(row);
+            }
+            return JSON.stringify(rows);
+        ).try_into().unwrap_or_default();
+
+        RoomTerrain::parse(&serde_json::from_str::<Vec<String>>(&raw).unwrap_or_default())
+    }
+
+    fn construction_sites(&self, room: &str) -> Vec<SiteState> {
+        game::rooms::get(room)
+            .map(|room| room.find(find::CONSTRUCTION_SITES).iter().map(site_state).collect())
+            .unwrap_or_default()
+    }
+
+    fn hostile_creeps(&self, room: &str) -> Vec<CreepState> {
+        game::rooms::get(room)
+            .map(|room| room.find(find::HOSTILE_CREEPS).iter().map(creep_state).collect())
+            .unwrap_or_default()
+    }
+
+    fn dropped_resources(&self, room: &str) -> Vec<ResourceState> {
+        game::rooms::get(room)
+            .map(|room| room.find(find::DROPPED_RESOURCES).iter()
+                            .filter(|resource| resource.resource_type() == ResourceType::Energy)
+                            .map(resource_state)
+                            .collect())
+            .unwrap_or_default()
+    }
+
+    fn remains(&self, room: &str) -> Vec<RemainsState> {
+        // ruins are newer than the API bindings
+        let raw: String = js!(
+            var room = Game.rooms[@{room}];
+            if (!room) {
+                return "[]";
+            }
+
+            var remains = room.find(FIND_TOMBSTONES).map(function(t) { return [t, "tombstone"]; });
+            if (typeof FIND_RUINS !== "undefined") {
+                remains = remains.concat(room.find(FIND_RUINS).map(function(r) { return [r, "ruin"]; }));
+            }
+            return JSON.stringify(remains.map(function(entry) {
+                var r = entry[0];
+                return {
+                    id: r.id,
+                    kind: entry[1],
+                    x: r.pos.x,
+                    y: r.pos.y,
+                    energy: r.store ? (r.store[RESOURCE_ENERGY] || 0) : 0,
+                    store: r.store ? _.sum(r.store) : 0,
+                    ticksToDecay: r.ticksToDecay || 0
+                };
+            }));
+        ).try_into().unwrap_or_default();
+
+        serde_json::from_str::<Vec<RawRemains>>(&raw).unwrap_or_default()
+            .into_iter()
+            .map(|raw| RemainsState{
+                id: raw.id,
+                kind: if raw.kind == "ruin" { RemainsKind::Ruin } else { RemainsKind::Tombstone },
+                pos: Position::new(raw.x, raw.y, room),
+                energy: raw.energy,
+                store: raw.store,
+                ticks_to_decay: raw.ticks_to_decay
+            })
+            .collect()
+    }
+
+    fn invader_cores(&self, room: &str) -> Vec<InvaderCoreState> {
+        // invader cores are newer than the API bindings, the collapse timer is one of their effects
+        let raw: String = js!(
+            var room = Game.rooms[@{room}];
+            if (!room || typeof STRUCTURE_INVADER_CORE === "undefined") {
+                return "[]";
+            }
+
+            var cores = room.find(FIND_HOSTILE_STRUCTURES, {
+                filter: function(s) { return s.structureType === STRUCTURE_INVADER_CORE; }
+            });
+            return JSON.stringify(cores.map(function(core) {
+                var collapse = _.find(core.effects || [], function(e) {
+                    return typeof EFFECT_COLLAPSE_TIMER !== "undefined" && e.effect === EFFECT_COLLAPSE_TIMER;
+                });
+                return {
+                    id: core.id,
+                    x: core.pos.x,
+                    y: core.pos.y,
+                    level: core.level || 0,
+                    hits: core.hits,
+                    ticksToDeploy: core.ticksToDeploy || 0,
+                    ticksToCollapse: collapse ? collapse.ticksRemaining : null
+                };
+            }));
+        ).try_into().unwrap_or_default();
+
+        serde_json::from_str::<Vec<RawInvaderCore>>(&raw).unwrap_or_default()
+            .into_iter()
+            .map(|raw| InvaderCoreState{
+                id: raw.id,
+                pos: Position::new(raw.x, raw.y, room),
+                level: raw.level,
+                hits: raw.hits,
+                ticks_to_deploy: raw.ticks_to_deploy,
+                ticks_to_collapse: raw.ticks_to_collapse
+            })
+            .collect()
+    }
+
+    fn flags(&self) -> Vec<FlagState> {
+        game::flags::values().iter().map(|flag| FlagState{
+            name: flag.name(),
+            pos: position(&flag.pos())
+        }).collect()
+    }
+
+    fn creep_intent(&self, creep: &str, intent: CreepIntent) -> ReturnCode {
+        let creep = match game::creeps::get(creep) {
+            Some(creep) => creep,
+            None => return ReturnCode::NotFound
+        };
+
+        match intent {
+            CreepIntent::MoveTo(pos) => creep.move_to(&room_position(&pos)),
+            CreepIntent::Move(direction) => creep.move_direction(direction),
+            CreepIntent::Harvest(id) => match game::get_object_typed::<Source>(&id) {
+                Ok(Some(source)) => creep.harvest(&source),
+                _ => ReturnCode::InvalidTarget
+            },
+            CreepIntent::Transfer(id) => match game::get_object_typed::<Structure>(&id) {
+                Ok(Some(structure)) => match structure.as_transferable() {
+                    Some(target) => creep.transfer_all(target, ResourceType::Energy),
+                    None => ReturnCode::InvalidTarget
+                },
+                _ => ReturnCode::InvalidTarget
+            },
+            CreepIntent::Withdraw(id) => {
+                // tombstones and ruins aren't structures
+                let code: i32 = js!(
+                    var target = Game.getObjectById(@{id});
+                    return target ? @{creep.as_ref()}.withdraw(target, RESOURCE_ENERGY) : ERR_INVALID_TARGET;
+                ).try_into().unwrap_or(-10);
+                return_code(code)
+            },
+            // any resource, from structures and remains alike
+            CreepIntent::Loot(id) => {
+                let code: i32 = js!(
+                    var target = Game.getObjectById(@{id});
+                    if (!target || !target.store) {
+                        return ERR_INVALID_TARGET;
+                    }
+                    var resources = Object.keys(target.store).filter(function(r) { return target.store[r] > 0; });
+                    if (!resources.length) {
+                        return ERR_NOT_ENOUGH_RESOURCES;
+                    }
+                    resources.sort(function(a, b) { return target.store[b] - target.store[a]; });
+                    return @{creep.as_ref()}.withdraw(target, resources[0]);
+                ).try_into().unwrap_or(-10);
+                return_code(code)
+            },
+            CreepIntent::Unload(id) => {
+                let code: i32 = js!(
+                    var creep = @{creep.as_ref()};
+                    var target = Game.getObjectById(@{id});
+                    if (!target) {
+                        return ERR_INVALID_TARGET;
+                    }
+                    var resources = Object.keys(creep.store).filter(function(r) { return creep.store[r] > 0; });
+                    return resources.length ? creep.transfer(target, resources[0]) : ERR_NOT_ENOUGH_RESOURCES;
+                ).try_into().unwrap_or(-10);
+                return_code(code)
+            },
+            CreepIntent::Pickup(id) => match game::get_object_typed::<Resource>(&id) {
+                Ok(Some(resource)) => creep.pickup(&resource),
+                _ => ReturnCode::InvalidTarget
+            },
+            CreepIntent::Build(id) => match game::get_object_typed::<ConstructionSite>(&id) {
+                Ok(Some(site)) => creep.build(&site),
+                _ => ReturnCode::InvalidTarget
+            },
+            CreepIntent::Repair(id) => match game::get_object_typed::<Structure>(&id) {
+                Ok(Some(structure)) => creep.repair(&structure),
+                _ => ReturnCode::InvalidTarget
+            },
+            CreepIntent::UpgradeController(id) => match game::get_object_typed::<StructureController>(&id) {
+                Ok(Some(controller)) => creep.upgrade_controller(&controller),
+                _ => ReturnCode::InvalidTarget
+            },
+            // creeps and structures alike
+            CreepIntent::Attack(id) => {
+                let code: i32 = js!(
+                    var target = Game.getObjectById(@{id});
+                    return target ? @{creep.as_ref()}.attack(target) : ERR_INVALID_TARGET;
+                ).try_into().unwrap_or(-10);
+                return_code(code)
+            },
+            CreepIntent::RangedAttack(id) => {
+                let code: i32 = js!(
+                    var target = Game.getObjectById(@{id});
+                    return target ? @{creep.as_ref()}.rangedAttack(target) : ERR_INVALID_TARGET;
+                ).try_into().unwrap_or(-10);
+                return_code(code)
+            },
+            CreepIntent::Heal(id) => match game::get_object_typed::<Creep>(&id) {
+                Ok(Some(target)) => creep.heal(&target),
+                _ => ReturnCode::InvalidTarget
+            },
+            CreepIntent::RangedHeal(id) => match game::get_object_typed::<Creep>(&id) {
+                Ok(Some(target)) => creep.ranged_heal(&target),
+                _ => ReturnCode::InvalidTarget
+            }
+        }
+    }
+
+    fn structure_intent(&self, structure: &str, intent: StructureIntent) -> ReturnCode {
+        if let StructureIntent::RecycleCreep(id) = intent {
+            return match (game::get_object_typed::<StructureSpawn>(structure), game::get_object_typed::<Creep>(&id)) {
+                (Ok(Some(spawn)), Ok(Some(creep))) => spawn.recycle_creep(&creep),
+                (Ok(Some(_)), _) => ReturnCode::InvalidTarget,
+                _ => ReturnCode::NotFound
+            };
+        }
+        if let StructureIntent::ActivateSafeMode = intent {
+            return match game::get_object_typed::<StructureController>(structure) {
+                Ok(Some(controller)) => {
+                    let code: i32 = js!(return @{controller.as_ref()}.activateSafeMode();).try_into().unwrap_or(-10);
+                    return_code(code)
+                },
+                _ => ReturnCode::NotFound
+            };
+        }
+        if let StructureIntent::LinkTransfer(id) = intent {
+            return match (game::get_object_typed::<StructureLink>(structure), game::get_object_typed::<StructureLink>(&id)) {
+                (Ok(Some(link)), Ok(Some(target))) => link.transfer_energy(&target, None),
+                (Ok(Some(_)), _) => ReturnCode::InvalidTarget,
+                _ => ReturnCode::NotFound
+            };
+        }
+
+        let tower = match game::get_object_typed::<StructureTower>(structure) {
+            Ok(Some(tower)) => tower,
+            _ => return ReturnCode::NotFound
+        };
+
+        match intent {
+            StructureIntent::TowerAttack(id) => match game::get_object_typed::<Creep>(&id) {
+                Ok(Some(target)) => tower.attack(&target),
+                _ => ReturnCode::InvalidTarget
+            },
+            StructureIntent::TowerHeal(id) => match game::get_object_typed::<Creep>(&id) {
+                Ok(Some(target)) => tower.heal(&target),
+                _ => ReturnCode::InvalidTarget
+            },
+            StructureIntent::TowerRepair(id) => match game::get_object_typed::<Structure>(&id) {
+                Ok(Some(target)) => tower.repair(&target),
+                _ => ReturnCode::InvalidTarget
+            },
+            StructureIntent::LinkTransfer(_) | StructureIntent::ActivateSafeMode |
+            StructureIntent::RecycleCreep(_) => ReturnCode::InvalidArgs
+        }
+    }
+
+    fn spawn_creep(&self, spawn: &str, body: &[Part], name: &str) -> ReturnCode {
+        match game::spawns::get(spawn) {
+            Some(spawn) => spawn.spawn_creep(body, name),
+            None => ReturnCode::NotFound
+        }
+    }
+
+    fn create_flag(&self, pos: &Position, name: &str, color: Color, secondary_color: Color) -> ReturnCode {
+        match room_position(pos).create_flag(name, color, secondary_color) {
+            Ok(_) => ReturnCode::Ok,
+            Err(code) => code
+        }
+    }
+
+    fn create_construction_site(&self, pos: &Position, kind: StructureKind) -> ReturnCode {
+        match structure_type(kind) {
+            Some(ty) => room_position(pos).create_construction_site(ty),
+            // factories are newer than the API bindings
+            None if kind.build_cost().is_some() => {
+                let code: i32 = js!(
+                    return new RoomPosition(@{pos.x}, @{pos.y}, @{&pos.room}).createConstructionSite(@{kind.as_str()});
+                ).try_into().unwrap_or(-10);
+                return_code(code)
+            },
+            None => ReturnCode::InvalidArgs
+        }
+    }
+
+    fn remove_flag(&self, name: &str) {
+        if let Some(flag) = game::flags::get(name) {
+            flag.remove();
+        }
+    }
+
+    fn notify(&self, message: &str) {
+        game::notify(message, None);
+    }
+
+    fn memory(&self) -> RefMut<Memory> {
+        self.memory.borrow_mut()
+    }
+
+    fn commit_memory(&self) {
+        if self.broken.get() {
+            return;
+        }
+        match self.memory.borrow().serialize() {
+            Ok(raw) => {
+                js! {
+                    RawMemory.set(@{raw.as_str()});
+                }
+                *self.raw.borrow_mut() = raw;
+            },
+            Err(err) => error!("failed to serialize memory: {}", err)
+        }
+    }
+}
@@ -0,0 +1,665 @@
+use std::cell::{
+    Cell,
+    RefCell,
+    RefMut
+};
+
+use hashbrown::HashMap;
+
+use crate::memory::Memory;
+use super::*;
+
+/// An in-memory world for native tests.
+/// 
+/// Intents are validated the way the game would validate them (ownership, range, energy),
+/// and successful ones are recorded instead of being executed.  
+/// Nothing moves unless the test changes the state itself.
+pub struct MockWorld {
+    time: Cell<u32>,
+    rooms: RefCell<Vec<RoomState>>,
+    creeps: RefCell<Vec<CreepState>>,
+    hostiles: RefCell<Vec<CreepState>>,
+    spawns: RefCell<Vec<SpawnState>>,
+    sources: RefCell<Vec<SourceState>>,
+    minerals: RefCell<Vec<MineralState>>,
+    terrain: RefCell<HashMap<String, RoomTerrain>>,
+    structures: RefCell<Vec<StructureState>>,
+    sites: RefCell<Vec<SiteState>>,
+    dropped: RefCell<Vec<ResourceState>>,
+    remains: RefCell<Vec<RemainsState>>,
+    invader_cores: RefCell<Vec<InvaderCoreState>>,
+    flags: RefCell<Vec<FlagState>>,
+    memory: RefCell<Memory>,
+    next_site: Cell<u32>,
+
+    creep_intents: RefCell<Vec<(String, CreepIntent)>>,
+    structure_intents: RefCell<Vec<(String, StructureIntent)>>,
+    spawned: RefCell<Vec<(String, Vec<Part>, String)>>,
+    notifications: RefCell<Vec<String>>
+}
+
+/// A creep at full health, with nothing carried.
+pub fn creep(name: &str, pos: Position, body: &[Part]) -> CreepState {
+    let carry_capacity = body.iter().filter(|part| **part == Part::Carry).count() as u32 * 50;
+
+    CreepState{
+        name: name.to_string(),
+        id: name.to_string(),
+        pos: pos,
+        owner: "me".to_string(),
+        my: true,
+        body: body.iter().map(|part| BodyPart{
+            part: *part,
+            boost: None,
+            hits: 100
+        }).collect(),
+        energy: 0,
+        carry_capacity: carry_capacity,
+        hits: body.len() as u32 * 100,
+        hits_max: body.len() as u32 * 100,
+        fatigue: 0,
+        ticks_to_live: 1500,
+        spawning: false
+    }
+}
+
+/// An owned structure at full health, without any energy.
+pub fn structure(id: &str, kind: StructureKind, pos: Position) -> StructureState {
+    StructureState{
+        id: id.to_string(),
+        kind: kind,
+        pos: pos,
+        my: true,
+        hits: 1000,
+        hits_max: 1000,
+        energy: 0,
+        energy_capacity: 0,
+        cooldown: 0
+    }
+}
+
+impl MockWorld {
+    pub fn new() -> MockWorld {
+        MockWorld{
+            time: Cell::new(1),
+            rooms: RefCell::new(Vec::new()),
+            creeps: RefCell::new(Vec::new()),
+            hostiles: RefCell::new(Vec::new()),
+            spawns: RefCell::new(Vec::new()),
+            sources: RefCell::new(Vec::new()),
+            minerals: RefCell::new(Vec::new()),
+            terrain: RefCell::new(HashMap::new()),
+            structures: RefCell::new(Vec::new()),
+            sites: RefCell::new(Vec::new()),
+            dropped: RefCell::new(Vec::new()),
+            remains: RefCell::new(Vec::new()),
+            invader_cores: RefCell::new(Vec::new()),
+            flags: RefCell::new(Vec::new()),
+            memory: RefCell::new(Memory::fresh()),
+            next_site: Cell::new(0),
+
+            creep_intents: RefCell::new(Vec::new()),
+            structure_intents: RefCell::new(Vec::new()),
+            spawned: RefCell::new(Vec::new()),
+            notifications: RefCell::new(Vec::new())
+        }
+    }
+
+    pub fn set_time(&self, time: u32) {
+        self.time.set(time);
+    }
+
+    pub fn add_room(&self, room: RoomState) {
+        self.rooms.borrow_mut().push(room);
+    }
+
+    pub fn add_creep(&self, creep: CreepState) {
+        self.creeps.borrow_mut().push(creep);
+    }
+
+    pub fn add_hostile(&self, creep: CreepState) {
+        self.hostiles.borrow_mut().push(creep);
+    }
+
+    pub fn add_spawn(&self, spawn: SpawnState) {
+        self.spawns.borrow_mut().push(spawn);
+    }
+
+    pub fn add_source(&self, source: SourceState) {
+        self.sources.borrow_mut().push(source);
+    }
+
+    pub fn add_mineral(&self, mineral: MineralState) {
+        self.minerals.borrow_mut().push(mineral);
+    }
+
+    /// Rooms without terrain are plain.
+    pub fn set_terrain(&self, room: &str, terrain: RoomTerrain) {
+        self.terrain.borrow_mut().insert(room.to_string(), terrain);
+    }
+
+    pub fn add_structure(&self, structure: StructureState) {
+        self.structures.borrow_mut().push(structure);
+    }
+
+    pub fn add_site(&self, site: SiteState) {
+        self.sites.borrow_mut().push(site);
+    }
+
+    pub fn add_dropped(&self, resource: ResourceState) {
+        self.dropped.borrow_mut().push(resource);
+    }
+
+    pub fn add_remains(&self, remains: RemainsState) {
+        self.remains.borrow_mut().push(remains);
+    }
+
+    pub fn add_invader_core(&self, core: InvaderCoreState) {
+        self.invader_cores.borrow_mut().push(core);
+    }
+
+    pub fn remove_invader_core(&self, id: &str) {
+        self.invader_cores.borrow_mut().retain(|core| core.id != id);
+    }
+
+    pub fn add_flag(&self, flag: FlagState) {
+        self.flags.borrow_mut().push(flag);
+    }
+
+    /// Replaces the stored state of a creep, matched by name.
+    pub fn update_creep(&self, creep: CreepState) {
+        let mut creeps = self.creeps.borrow_mut();
+        creeps.retain(|other| other.name != creep.name);
+        creeps.push(creep);
+    }
+
+    /// Replaces the stored state of a structure, matched by id.
+    pub fn update_structure(&self, structure: StructureState) {
+        let mut structures = self.structures.borrow_mut();
+        structures.retain(|other| other.id != structure.id);
+        structures.push(structure);
+    }
+
+    pub fn update_room(&self, room: RoomState) {
+        let mut rooms = self.rooms.borrow_mut();
+        rooms.retain(|other| other.name != room.name);
+        rooms.push(room);
+    }
+
+    pub fn update_spawn(&self, spawn: SpawnState) {
+        let mut spawns = self.spawns.borrow_mut();
+        spawns.retain(|other| other.id != spawn.id);
+        spawns.push(spawn);
+    }
+
+    pub fn update_source(&self, source: SourceState) {
+        let mut sources = self.sources.borrow_mut();
+        sources.retain(|other| other.id != source.id);
+        sources.push(source);
+    }
+
+    pub fn update_site(&self, site: SiteState) {
+        let mut sites = self.sites.borrow_mut();
+        sites.retain(|other| other.id != site.id);
+        sites.push(site);
+    }
+
+    pub fn remove_creep(&self, name: &str) {
+        self.creeps.borrow_mut().retain(|creep| creep.name != name);
+    }
+
+    pub fn remove_site(&self, id: &str) {
+        self.sites.borrow_mut().retain(|site| site.id != id);
+    }
+
+    pub fn site(&self, id: &str) -> Option<SiteState> {
+        self.sites.borrow().iter().find(|site| site.id == id).cloned()
+    }
+
+    /// Replaces the stored state of a dropped resource, matched by id.  
+    /// Empty piles are removed.
+    pub fn update_dropped(&self, resource: ResourceState) {
+        let mut dropped = self.dropped.borrow_mut();
+        dropped.retain(|other| other.id != resource.id);
+        if resource.amount > 0 {
+            dropped.push(resource);
+        }
+    }
+
+    /// Replaces the stored state of remains, matched by id.  
+    /// Decayed remains are removed.
+    pub fn update_remains(&self, remains: RemainsState) {
+        let mut all = self.remains.borrow_mut();
+        all.retain(|other| other.id != remains.id);
+        if remains.ticks_to_decay > 0 {
+            all.push(remains);
+        }
+    }
+
+    pub fn remains_by_id(&self, id: &str) -> Option<RemainsState> {
+        self.remains.borrow().iter().find(|remains| remains.id == id).cloned()
+    }
+
+    pub fn dropped(&self, id: &str) -> Option<ResourceState> {
+        self.dropped.borrow().iter().find(|resource| resource.id == id).cloned()
+    }
+
+    pub fn creep_intents(&self) -> Vec<(String, CreepIntent)> {
+        self.creep_intents.borrow().clone()
+    }
+
+    pub fn structure_intents(&self) -> Vec<(String, StructureIntent)> {
+        self.structure_intents.borrow().clone()
+    }
+
+    /// Every successful `spawn_creep` call, as (spawn, body, name).
+    pub fn spawned(&self) -> Vec<(String, Vec<Part>, String)> {
+        self.spawned.borrow().clone()
+    }
+
+    pub fn notifications(&self) -> Vec<String> {
+        self.notifications.borrow().clone()
+    }
+
+    /// Forgets all recorded intents, to start a new tick.
+    pub fn clear_intents(&self) {
+        self.creep_intents.borrow_mut().clear();
+        self.structure_intents.borrow_mut().clear();
+        self.spawned.borrow_mut().clear();
+    }
+
+    fn object_pos(&self, id: &str) -> Option<Position> {
+        self.sources.borrow().iter().find(|x| x.id == id).map(|x| x.pos.clone())
+            .or_else(|| self.structures.borrow().iter().find(|x| x.id == id).map(|x| x.pos.clone()))
+            .or_else(|| self.sites.borrow().iter().find(|x| x.id == id).map(|x| x.pos.clone()))
+            .or_else(|| self.dropped.borrow().iter().find(|x| x.id == id).map(|x| x.pos.clone()))
+            .or_else(|| self.remains.borrow().iter().find(|x| x.id == id).map(|x| x.pos.clone()))
+            .or_else(|| self.invader_cores.borrow().iter().find(|x| x.id == id).map(|x| x.pos.clone()))
+            .or_else(|| self.creep_by_id(id).map(|x| x.pos))
+            .or_else(|| self.rooms.borrow().iter()
+                            .filter_map(|room| room.controller.as_ref())
+                            .find(|controller| controller.id == id)
+                            .map(|controller| controller.pos.clone()))
+    }
+
+    fn validate_creep_intent(&self, creep: &CreepState, intent: &CreepIntent) -> ReturnCode {
+        let (target, range) = match *intent {
+            CreepIntent::MoveTo(_) | CreepIntent::Move(_) => {
+                return if creep.fatigue > 0 { ReturnCode::Tired } else { ReturnCode::Ok };
+            },
+            CreepIntent::Harvest(ref id) => {
+                if self.source(id).is_none() {
+                    return ReturnCode::InvalidTarget;
+                }
+                (id, 1)
+            },
+            CreepIntent::Transfer(ref id) => {
+                if creep.energy == 0 {
+                    return ReturnCode::NotEnough;
+                }
+                (id, 1)
+            },
+            CreepIntent::Withdraw(ref id) => {
+                let energy = self.structure(id).map(|structure| structure.energy)
+                                 .or_else(|| self.remains_by_id(id).map(|remains| remains.energy));
+                match energy {
+                    None => return ReturnCode::InvalidTarget,
+                    Some(0) => return ReturnCode::NotEnough,
+                    _ => ()
+                }
+                if creep.energy >= creep.carry_capacity {
+                    return ReturnCode::Full;
+                }
+                (id, 1)
+            },
+            CreepIntent::Pickup(ref id) => {
+                if self.dropped(id).is_none() {
+                    return ReturnCode::InvalidTarget;
+                }
+                if creep.energy >= creep.carry_capacity {
+                    return ReturnCode::Full;
+                }
+                (id, 1)
+            },
+            CreepIntent::Loot(ref id) => {
+                let store = self.structure(id).map(|structure| structure.energy)
+                                .or_else(|| self.remains_by_id(id).map(|remains| remains.store));
+                match store {
+                    None => return ReturnCode::InvalidTarget,
+                    Some(0) => return ReturnCode::NotEnough,
+                    _ => ()
+                }
+                if creep.energy >= creep.carry_capacity {
+                    return ReturnCode::Full;
+                }
+                (id, 1)
+            },
+            // only energy is tracked for creeps
+            CreepIntent::Unload(ref id) => {
+                if creep.energy == 0 {
+                    return ReturnCode::NotEnough;
+                }
+                (id, 1)
+            },
+            CreepIntent::Build(ref id) => (id, 3),
+            CreepIntent::Repair(ref id) => (id, 3),
+            CreepIntent::UpgradeController(ref id) => {
+                if creep.energy == 0 {
+                    return ReturnCode::NotEnough;
+                }
+                (id, 3)
+            },
+            CreepIntent::Attack(ref id) => {
+                if creep.active_parts(Part::Attack) == 0 {
+                    return ReturnCode::NoBodypart;
+                }
+                (id, 1)
+            },
+            CreepIntent::RangedAttack(ref id) => {
+                if creep.active_parts(Part::RangedAttack) == 0 {
+                    return ReturnCode::NoBodypart;
+                }
+                (id, 3)
+            },
+            CreepIntent::Heal(ref id) => {
+                if creep.active_parts(Part::Heal) == 0 {
+                    return ReturnCode::NoBodypart;
+                }
+                (id, 1)
+            },
+            CreepIntent::RangedHeal(ref id) => {
+                if creep.active_parts(Part::Heal) == 0 {
+                    return ReturnCode::NoBodypart;
+                }
+                (id, 3)
+            }
+        };
+
+        match self.object_pos(target) {
+            Some(ref pos) if creep.pos.in_range_to(pos, range) => ReturnCode::Ok,
+            Some(_) => ReturnCode::NotInRange,
+            None => ReturnCode::InvalidTarget
+        }
+    }
+}
+
+impl World for MockWorld {
+    fn time(&self) -> u32 {
+        self.time.get()
+    }
+
+    fn my_creeps(&self) -> Vec<CreepState> {
+        self.creeps.borrow().clone()
+    }
+
+    fn creep(&self, name: &str) -> Option<CreepState> {
+        self.creeps.borrow().iter().find(|creep| creep.name == name).cloned()
+    }
+
+    fn creep_by_id(&self, id: &str) -> Option<CreepState> {
+        self.creeps.borrow().iter()
+            .chain(self.hostiles.borrow().iter())
+            .find(|creep| creep.id == id)
+            .cloned()
+    }
+
+    fn rooms(&self) -> Vec<RoomState> {
+        self.rooms.borrow().clone()
+    }
+
+    fn room(&self, name: &str) -> Option<RoomState> {
+        self.rooms.borrow().iter().find(|room| room.name == name).cloned()
+    }
+
+    fn spawns(&self) -> Vec<SpawnState> {
+        self.spawns.borrow().clone()
+    }
+
+    fn sources(&self, room: &str) -> Vec<SourceState> {
+        self.sources.borrow().iter().filter(|source| source.pos.room == room).cloned().collect()
+    }
+
+    fn source(&self, id: &str) -> Option<SourceState> {
+        self.sources.borrow().iter().find(|source| source.id == id).cloned()
+    }
+
+    fn structures(&self, room: &str) -> Vec<StructureState> {
+        self.structures.borrow().iter().filter(|structure| structure.pos.room == room).cloned().collect()
+    }
+
+    fn structure(&self, id: &str) -> Option<StructureState> {
+        self.structures.borrow().iter().find(|structure| structure.id == id).cloned()
+    }
+
+    fn minerals(&self, room: &str) -> Vec<MineralState> {
+        self.minerals.borrow().iter().filter(|mineral| mineral.pos.room == room).cloned().collect()
+    }
+
+    fn terrain(&self, room: &str) -> RoomTerrain {
+        self.terrain.borrow().get(room).cloned().unwrap_or_else(RoomTerrain::plain)
+    }
+
+    fn construction_sites(&self, room: &str) -> Vec<SiteState> {
+        self.sites.borrow().iter().filter(|site| site.pos.room == room).cloned().collect()
+    }
+
+    fn hostile_creeps(&self, room: &str) -> Vec<CreepState> {
+        self.hostiles.borrow().iter().filter(|creep| creep.pos.room == room).cloned().collect()
+    }
+
+    fn dropped_resources(&self, room: &str) -> Vec<ResourceState> {
+        self.dropped.borrow().iter().filter(|resource| resource.pos.room == room).cloned().collect()
+    }
+
+    fn remains(&self, room: &str) -> Vec<RemainsState> {
+        self.remains.borrow().iter().filter(|remains| remains.pos.room == room).cloned().collect()
+    }
+
+    fn invader_cores(&self, room: &str) -> Vec<InvaderCoreState> {
+        self.invader_cores.borrow().iter().filter(|core| core.pos.room == room).cloned().collect()
+    }
+
+    fn flags(&self) -> Vec<FlagState> {
+        self.flags.borrow().clone()
+    }
+
+    fn creep_intent(&self, creep: &str, intent: CreepIntent) -> ReturnCode {
+        let creep = match self.creep(creep) {
+            Some(creep) => creep,
+            None => return ReturnCode::NotFound
+        };
+        if creep.spawning {
+            return ReturnCode::Busy;
+        }
+
+        let result = self.validate_creep_intent(&creep, &intent);
+        if result == ReturnCode::Ok {
+            self.creep_intents.borrow_mut().push((creep.name, intent));
+        }
+        result
+    }
+
+    fn structure_intent(&self, structure: &str, intent: StructureIntent) -> ReturnCode {
+        if let StructureIntent::ActivateSafeMode = intent {
+            let rooms = self.rooms.borrow();
+            let controller = match rooms.iter().filter_map(|room| room.controller.as_ref()).find(|controller| controller.id == structure) {
+                Some(controller) => controller,
+                None => return ReturnCode::NotFound
+            };
+            if !controller.my {
+                return ReturnCode::NotOwner;
+            }
+            if controller.safe_mode_available == 0 {
+                return ReturnCode::NotEnough;
+            }
+            if controller.safe_mode_cooldown > 0 {
+                return ReturnCode::Tired;
+            }
+            // only one room can be in safe mode at a time
+            if rooms.iter().filter_map(|room| room.controller.as_ref()).any(|controller| controller.safe_mode > 0) {
+                return ReturnCode::Busy;
+            }
+
+            self.structure_intents.borrow_mut().push((controller.id.clone(), intent));
+            return ReturnCode::Ok;
+        }
+
+        let kind = match intent {
+            StructureIntent::LinkTransfer(_) => StructureKind::Link,
+            StructureIntent::RecycleCreep(_) => StructureKind::Spawn,
+            _ => StructureKind::Tower
+        };
+        let structure = match self.structure(structure) {
+            Some(ref structure) if structure.kind == kind => structure.clone(),
+            _ => return ReturnCode::NotFound
+        };
+
+        if let StructureIntent::LinkTransfer(ref target) = intent {
+            if structure.cooldown > 0 {
+                return ReturnCode::Tired;
+            }
+            if structure.energy == 0 {
+                return ReturnCode::NotEnough;
+            }
+            match self.structure(target) {
+                Some(ref target) if target.kind == StructureKind::Link && target.pos.room == structure.pos.room => {
+                    if target.energy >= target.energy_capacity {
+                        return ReturnCode::Full;
+                    }
+                },
+                _ => return ReturnCode::InvalidTarget
+            }
+        } else if let StructureIntent::RecycleCreep(ref target) = intent {
+            match self.creep_by_id(target) {
+                Some(ref creep) if creep.my && creep.pos.in_range_to(&structure.pos, 1) => (),
+                Some(ref creep) if creep.my => return ReturnCode::NotInRange,
+                _ => return ReturnCode::InvalidTarget
+            }
+        } else if structure.energy < 10 {
+            return ReturnCode::NotEnough;
+        }
+
+        self.structure_intents.borrow_mut().push((structure.id, intent));
+        ReturnCode::Ok
+    }
+
+    fn spawn_creep(&self, spawn: &str, body: &[Part], name: &str) -> ReturnCode {
+        if self.creep(name).is_some() {
+            return ReturnCode::NameExists;
+        }
+        if body.is_empty() || body.len() > 50 {
+            return ReturnCode::InvalidArgs;
+        }
+
+        let cost: u32 = body.iter().map(|part| part_cost(*part)).sum();
+
+        match self.spawns.borrow_mut().iter_mut().find(|x| x.name == spawn) {
+            Some(ref spawn) if spawn.spawning => ReturnCode::Busy,
+            Some(ref spawn) if self.room(&spawn.pos.room).map(|room| room.energy_available).unwrap_or(0) < cost => {
+                ReturnCode::NotEnough
+            },
+            Some(spawn) => {
+                spawn.spawning = true;
+                // other spawns in the room can't spend the same energy this tick
+                if let Some(room) = self.rooms.borrow_mut().iter_mut().find(|room| room.name == spawn.pos.room) {
+                    room.energy_available -= cost;
+                }
+                self.spawned.borrow_mut().push((spawn.name.clone(), body.to_vec(), name.to_string()));
+                ReturnCode::Ok
+            },
+            None => ReturnCode::NotFound
+        }
+    }
+
+    fn create_flag(&self, pos: &Position, name: &str, _color: Color, _secondary_color: Color) -> ReturnCode {
+        let mut flags = self.flags.borrow_mut();
+        if flags.iter().any(|flag| flag.name == name) {
+            return ReturnCode::NameExists;
+        }
+
+        flags.push(FlagState{
+            name: name.to_string(),
+            pos: pos.clone()
+        });
+        ReturnCode::Ok
+    }
+
+    fn create_construction_site(&self, pos: &Position, kind: StructureKind) -> ReturnCode {
+        let progress_total = match kind.build_cost() {
+            Some(cost) => cost,
+            None => return ReturnCode::InvalidArgs
+        };
+        if pos.x == 0 || pos.y == 0 || pos.x >= 49 || pos.y >= 49 {
+            return ReturnCode::InvalidTarget;
+        }
+        if kind != StructureKind::Road && self.terrain(&pos.room).is_wall(pos.x as i32, pos.y as i32) {
+            return ReturnCode::InvalidTarget;
+        }
+
+        let occupied = self.sites.borrow().iter().any(|site| site.pos == *pos) ||
+                       self.structures.borrow().iter().any(|structure| {
+                           structure.pos == *pos && (structure.kind == kind || match (structure.kind, kind) {
+                               (StructureKind::Road, _) | (_, StructureKind::Road) => false,
+                               (StructureKind::Rampart, _) | (_, StructureKind::Rampart) => false,
+                               _ => true
+                           })
+                       });
+        if occupied {
+            return ReturnCode::InvalidTarget;
+        }
+
+        let id = self.next_site.get();
+        self.next_site.set(id + 1);
+        self.sites.borrow_mut().push(SiteState{
+            id: format!("site{}", id),
+            kind: kind,
+            pos: pos.clone(),
+            progress: 0,
+            progress_total: progress_total
+        });
+        ReturnCode::Ok
+    }
+
+    fn remove_flag(&self, name: &str) {
+        self.flags.borrow_mut().retain(|flag| flag.name != name);
+    }
+
+    fn notify(&self, message: &str) {
+        self.notifications.borrow_mut().push(message.to_string());
+    }
+
+    fn memory(&self) -> RefMut<Memory> {
+        self.memory.borrow_mut()
+    }
+
+    fn commit_memory(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawns_share_room_energy() {
+        let world = MockWorld::new();
+        world.add_room(RoomState{
+            name: "W1N1".to_string(),
+            energy_available: 300,
+            energy_capacity_available: 300,
+            controller: None
+        });
+        for name in ["Spawn1", "Spawn2"].iter() {
+            world.add_spawn(SpawnState{
+                id: name.to_string(),
+                name: name.to_string(),
+                pos: Position::new(25, 25, "W1N1"),
+                spawning: false
+            });
+        }
+        let body = [Part::Work, Part::Carry, Part::Move];
+
+        assert_eq!(world.spawn_creep("Spawn1", &body, "first"), ReturnCode::Ok);
+        assert_eq!(world.spawn_creep("Spawn2", &body, "second"), ReturnCode::NotEnough);
+        assert_eq!(world.room("W1N1").map(|room| room.energy_available), Some(100));
+    }
+}
@@ -0,0 +1,631 @@
+use std::{
+    cell::RefMut,
+    cmp
+};
+
+use crate::memory::Memory;
+
+use screeps::constants::{
+    Color,
+    Direction,
+    Part,
+    ReturnCode
+};
+
+/// The game API bindings, used when running inside the Screeps server.
+#[cfg(target_arch = "wasm32")]
+pub mod live;
+/// An in-memory fake, used by native unit tests and the simulator.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mock;
+
+#[cfg(target_arch = "wasm32")]
+pub use self::live::LiveWorld;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::mock::MockWorld;
+
+/// Object ids and creep names are passed around as plain strings.
+pub type ObjectId = String;
+
+/// Every direction a creep can move in, clockwise from the top.
+pub const DIRECTIONS: [Direction; 8] = [
+    Direction::Top, Direction::TopRight, Direction::Right, Direction::BottomRight,
+    Direction::Bottom, Direction::BottomLeft, Direction::Left, Direction::TopLeft
+];
+
+/// The (dx, dy) of a step in the direction.
+pub fn direction_offset(direction: Direction) -> (i32, i32) {
+    match direction {
+        Direction::Top => (0, -1),
+        Direction::TopRight => (1, -1),
+        Direction::Right => (1, 0),
+        Direction::BottomRight => (1, 1),
+        Direction::Bottom => (0, 1),
+        Direction::BottomLeft => (-1, 1),
+        Direction::Left => (-1, 0),
+        Direction::TopLeft => (-1, -1)
+    }
+}
+
+/// A position inside a room.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+    pub room: String
+}
+
+impl Position {
+    pub fn new(x: u32, y: u32, room: &str) -> Position {
+        Position{
+            x: x,
+            y: y,
+            room: room.to_string()
+        }
+    }
+
+    /// Chebyshev distance, the way the game measures range.
+    /// Positions in different rooms are infinitely far apart.
+    pub fn range_to(&self, other: &Position) -> u32 {
+        if self.room != other.room {
+            return u32::max_value();
+        }
+
+        cmp::max(
+            (self.x as i32 - other.x as i32).abs(),
+            (self.y as i32 - other.y as i32).abs()
+        ) as u32
+    }
+
+    pub fn in_range_to(&self, other: &Position, range: u32) -> bool {
+        self.range_to(other) <= range
+    }
+
+    pub fn is_near_to(&self, other: &Position) -> bool {
+        self.in_range_to(other, 1)
+    }
+
+    /// The neighbouring tile in the direction, None if it's outside of the room.
+    pub fn step(&self, direction: Direction) -> Option<Position> {
+        let (dx, dy) = direction_offset(direction);
+        let (x, y) = (self.x as i32 + dx, self.y as i32 + dy);
+        if x < 0 || y < 0 || x > 49 || y > 49 {
+            return None;
+        }
+        Some(Position::new(x as u32, y as u32, &self.room))
+    }
+
+    /// Whether the tile is on the edge of the room, where creeps leave it.
+    pub fn is_exit(&self) -> bool {
+        self.x == 0 || self.y == 0 || self.x == 49 || self.y == 49
+    }
+}
+
+/// Anything that has a position in the world.
+pub trait Positioned {
+    fn pos(&self) -> &Position;
+}
+
+/// Returns the object closest to `from`, or None if none of them are in the same room.
+pub fn find_closest_by_range<T: Positioned>(from: &Position, objects: Vec<T>) -> Option<T> {
+    objects.into_iter()
+        .filter(|object| object.pos().room == from.room)
+        .min_by_key(|object| from.range_to(object.pos()))
+}
+
+/// Serialized as the game's structure type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StructureKind {
+    Spawn,
+    Extension,
+    Road,
+    #[serde(rename = "constructedWall")]
+    Wall,
+    Rampart,
+    KeeperLair,
+    Portal,
+    Controller,
+    Link,
+    Storage,
+    Tower,
+    Observer,
+    PowerBank,
+    PowerSpawn,
+    Extractor,
+    Lab,
+    Terminal,
+    Container,
+    Nuker,
+    Factory,
+    InvaderCore,
+    Other
+}
+
+impl StructureKind {
+    pub fn from_str(name: &str) -> StructureKind {
+        match name {
+            "spawn" => StructureKind::Spawn,
+            "extension" => StructureKind::Extension,
+            "road" => StructureKind::Road,
+            "constructedWall" => StructureKind::Wall,
+            "rampart" => StructureKind::Rampart,
+            "keeperLair" => StructureKind::KeeperLair,
+            "portal" => StructureKind::Portal,
+            "controller" => StructureKind::Controller,
+            "link" => StructureKind::Link,
+            "storage" => StructureKind::Storage,
+            "tower" => StructureKind::Tower,
+            "observer" => StructureKind::Observer,
+            "powerBank" => StructureKind::PowerBank,
+            "powerSpawn" => StructureKind::PowerSpawn,
+            "extractor" => StructureKind::Extractor,
+            "lab" => StructureKind::Lab,
+            "terminal" => StructureKind::Terminal,
+            "container" => StructureKind::Container,
+            "nuker" => StructureKind::Nuker,
+            "factory" => StructureKind::Factory,
+            "invaderCore" => StructureKind::InvaderCore,
+            _ => StructureKind::Other
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            StructureKind::Spawn => "spawn",
+            StructureKind::Extension => "extension",
+            StructureKind::Road => "road",
+            StructureKind::Wall => "constructedWall",
+            StructureKind::Rampart => "rampart",
+            StructureKind::KeeperLair => "keeperLair",
+            StructureKind::Portal => "portal",
+            StructureKind::Controller => "controller",
+            StructureKind::Link => "link",
+            StructureKind::Storage => "storage",
+            StructureKind::Tower => "tower",
+            StructureKind::Observer => "observer",
+            StructureKind::PowerBank => "powerBank",
+            StructureKind::PowerSpawn => "powerSpawn",
+            StructureKind::Extractor => "extractor",
+            StructureKind::Lab => "lab",
+            StructureKind::Terminal => "terminal",
+            StructureKind::Container => "container",
+            StructureKind::Nuker => "nuker",
+            StructureKind::Factory => "factory",
+            StructureKind::InvaderCore => "invaderCore",
+            StructureKind::Other => "other"
+        }
+    }
+
+    /// Structures with an `energy`/`energyCapacity` pair, that creeps refill.
+    pub fn is_energy_sink(&self) -> bool {
+        match *self {
+            StructureKind::Spawn |
+            StructureKind::Extension |
+            StructureKind::Link |
+            StructureKind::Tower |
+            StructureKind::Lab |
+            StructureKind::PowerSpawn |
+            StructureKind::Nuker => true,
+            _ => false
+        }
+    }
+
+    /// Progress needed to build a construction site of this kind, or None if it can't be built.
+    pub fn build_cost(&self) -> Option<u32> {
+        match *self {
+            StructureKind::Spawn => Some(15_000),
+            StructureKind::Extension => Some(3_000),
+            StructureKind::Road => Some(300),
+            StructureKind::Wall => Some(1),
+            StructureKind::Rampart => Some(1),
+            StructureKind::Link => Some(5_000),
+            StructureKind::Storage => Some(30_000),
+            StructureKind::Tower => Some(5_000),
+            StructureKind::Observer => Some(8_000),
+            StructureKind::PowerSpawn => Some(100_000),
+            StructureKind::Extractor => Some(5_000),
+            StructureKind::Lab => Some(50_000),
+            StructureKind::Terminal => Some(100_000),
+            StructureKind::Container => Some(5_000),
+            StructureKind::Nuker => Some(100_000),
+            StructureKind::Factory => Some(100_000),
+            _ => None
+        }
+    }
+
+    /// How many structures of this kind a room can have at the given controller level.
+    pub fn limit(&self, level: u32) -> u32 {
+        let limits: [u32; 9] = match *self {
+            StructureKind::Spawn => [0, 1, 1, 1, 1, 1, 1, 2, 3],
+            StructureKind::Extension => [0, 0, 5, 10, 20, 30, 40, 50, 60],
+            StructureKind::Road => [2500; 9],
+            StructureKind::Wall => [0, 0, 2500, 2500, 2500, 2500, 2500, 2500, 2500],
+            StructureKind::Rampart => [0, 0, 2500, 2500, 2500, 2500, 2500, 2500, 2500],
+            StructureKind::Link => [0, 0, 0, 0, 0, 2, 3, 4, 6],
+            StructureKind::Storage => [0, 0, 0, 0, 1, 1, 1, 1, 1],
+            StructureKind::Tower => [0, 0, 0, 1, 1, 2, 2, 3, 6],
+            StructureKind::Observer => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+            StructureKind::PowerSpawn => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+            StructureKind::Extractor => [0, 0, 0, 0, 0, 0, 1, 1, 1],
+            StructureKind::Lab => [0, 0, 0, 0, 0, 0, 3, 6, 10],
+            StructureKind::Terminal => [0, 0, 0, 0, 0, 0, 1, 1, 1],
+            StructureKind::Container => [5; 9],
+            StructureKind::Nuker => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+            StructureKind::Factory => [0, 0, 0, 0, 0, 0, 0, 1, 1],
+            _ => [0; 9]
+        };
+        limits[cmp::min(level, 8) as usize]
+    }
+
+    /// Whether creeps can walk over it.
+    pub fn is_walkable(&self) -> bool {
+        match *self {
+            StructureKind::Road | StructureKind::Container | StructureKind::Rampart => true,
+            _ => false
+        }
+    }
+}
+
+/// What a tile of a room is made of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Plain,
+    Swamp,
+    Wall
+}
+
+/// The terrain of a whole room, which never changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoomTerrain {
+    tiles: Vec<Terrain>
+}
+
+impl RoomTerrain {
+    /// A room without walls or swamps.
+    pub fn plain() -> RoomTerrain {
+        RoomTerrain{
+            tiles: vec![Terrain::Plain; 2500]
+        }
+    }
+
+    /// Parses 50 rows of 50 characters: `x` is a wall, `~` is a swamp, anything else is plain.  
+    /// Missing rows and columns are plain.
+    pub fn parse<S: AsRef<str>>(rows: &[S]) -> RoomTerrain {
+        let mut terrain = RoomTerrain::plain();
+
+        for (y, row) in rows.iter().enumerate().take(50) {
+            for (x, tile) in row.as_ref().chars().enumerate().take(50) {
+                terrain.tiles[y * 50 + x] = match tile {
+                    'x' => Terrain::Wall,
+                    '~' => Terrain::Swamp,
+                    _ => Terrain::Plain
+                };
+            }
+        }
+
+        terrain
+    }
+
+    /// Tiles outside of the room are walls.
+    pub fn get(&self, x: i32, y: i32) -> Terrain {
+        if x < 0 || y < 0 || x > 49 || y > 49 {
+            return Terrain::Wall;
+        }
+        self.tiles[(y * 50 + x) as usize]
+    }
+
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) == Terrain::Wall
+    }
+}
+
+pub fn part_from_str(name: &str) -> Option<Part> {
+    match name {
+        "move" => Some(Part::Move),
+        "work" => Some(Part::Work),
+        "carry" => Some(Part::Carry),
+        "attack" => Some(Part::Attack),
+        "ranged_attack" => Some(Part::RangedAttack),
+        "tough" => Some(Part::Tough),
+        "heal" => Some(Part::Heal),
+        "claim" => Some(Part::Claim),
+        _ => None
+    }
+}
+
+pub fn part_cost(part: Part) -> u32 {
+    match part {
+        Part::Move => 50,
+        Part::Work => 100,
+        Part::Carry => 50,
+        Part::Attack => 80,
+        Part::RangedAttack => 150,
+        Part::Tough => 10,
+        Part::Heal => 250,
+        Part::Claim => 600
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyPart {
+    pub part: Part,
+    /// The mineral compound the part is boosted with.
+    pub boost: Option<String>,
+    pub hits: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreepState {
+    pub name: String,
+    pub id: ObjectId,
+    pub pos: Position,
+    pub owner: String,
+    pub my: bool,
+    pub body: Vec<BodyPart>,
+    pub energy: u32,
+    pub carry_capacity: u32,
+    pub hits: u32,
+    pub hits_max: u32,
+    pub fatigue: u32,
+    pub ticks_to_live: u32,
+    pub spawning: bool
+}
+
+impl CreepState {
+    /// Number of active parts of the given type.
+    pub fn active_parts(&self, part: Part) -> u32 {
+        self.body.iter().filter(|body_part| body_part.part == part && body_part.hits > 0).count() as u32
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructureState {
+    pub id: ObjectId,
+    pub kind: StructureKind,
+    pub pos: Position,
+    pub my: bool,
+    pub hits: u32,
+    pub hits_max: u32,
+    pub energy: u32,
+    pub energy_capacity: u32,
+    /// Ticks until a link can send again, 0 for everything else.
+    pub cooldown: u32
+}
+
+impl StructureState {
+    pub fn is_damaged(&self) -> bool {
+        self.hits < self.hits_max
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnState {
+    pub id: ObjectId,
+    pub name: String,
+    pub pos: Position,
+    pub spawning: bool
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceState {
+    pub id: ObjectId,
+    pub pos: Position,
+    pub energy: u32,
+    pub energy_capacity: u32,
+    pub ticks_to_regeneration: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MineralState {
+    pub id: ObjectId,
+    pub pos: Position
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiteState {
+    pub id: ObjectId,
+    pub kind: StructureKind,
+    pub pos: Position,
+    pub progress: u32,
+    pub progress_total: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControllerState {
+    pub id: ObjectId,
+    pub pos: Position,
+    pub my: bool,
+    pub level: u32,
+    pub progress: u32,
+    pub progress_total: u32,
+    /// Ticks left of safe mode, 0 if it isn't active.
+    pub safe_mode: u32,
+    pub safe_mode_available: u32,
+    /// Ticks until safe mode can be activated again.
+    pub safe_mode_cooldown: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoomState {
+    pub name: String,
+    pub energy_available: u32,
+    pub energy_capacity_available: u32,
+    pub controller: Option<ControllerState>
+}
+
+/// Energy dropped on the ground.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceState {
+    pub id: ObjectId,
+    pub pos: Position,
+    pub amount: u32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemainsKind {
+    Tombstone,
+    Ruin
+}
+
+/// What's left of a creep or a structure, until it decays.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemainsState {
+    pub id: ObjectId,
+    pub kind: RemainsKind,
+    pub pos: Position,
+    pub energy: u32,
+    /// Every resource stored, energy included.
+    pub store: u32,
+    pub ticks_to_decay: u32
+}
+
+/// An NPC invader core, the heart of a stronghold from level 1 up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvaderCoreState {
+    pub id: ObjectId,
+    pub pos: Position,
+    /// 0 for cores that only reserve the room, 1 to 5 for strongholds.
+    pub level: u32,
+    pub hits: u32,
+    /// Ticks until the core is deployed, it can't be damaged before that.
+    pub ticks_to_deploy: u32,
+    /// Ticks until the stronghold collapses, None for cores without a timer.
+    pub ticks_to_collapse: Option<u32>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlagState {
+    pub name: String,
+    pub pos: Position
+}
+
+macro_rules! impl_positioned {
+    ($($ty:ty),*) => {
+        $(
+            impl Positioned for $ty {
+                fn pos(&self) -> &Position {
+                    &self.pos
+                }
+            }
+        )*
+    }
+}
+
+impl_positioned!(CreepState, StructureState, SpawnState, SourceState, MineralState, SiteState, ControllerState, ResourceState, RemainsState, InvaderCoreState, FlagState);
+
+impl Positioned for Position {
+    fn pos(&self) -> &Position {
+        self
+    }
+}
+
+/// An action a creep attempts this tick.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CreepIntent {
+    MoveTo(Position),
+    /// A single step, without pathfinding.
+    Move(Direction),
+    Harvest(ObjectId),
+    /// Transfers all carried energy.
+    Transfer(ObjectId),
+    /// Withdraws as much energy as fits, from a structure or remains.
+    Withdraw(ObjectId),
+    /// Picks up dropped energy.
+    Pickup(ObjectId),
+    /// Withdraws the most plentiful resource of any type, from a structure or remains.
+    Loot(ObjectId),
+    /// Transfers all of one carried resource, of any type.
+    Unload(ObjectId),
+    Build(ObjectId),
+    Repair(ObjectId),
+    UpgradeController(ObjectId),
+    Attack(ObjectId),
+    RangedAttack(ObjectId),
+    Heal(ObjectId),
+    RangedHeal(ObjectId)
+}
+
+/// An action a structure attempts this tick.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructureIntent {
+    TowerAttack(ObjectId),
+    TowerHeal(ObjectId),
+    TowerRepair(ObjectId),
+    /// Sends all of a link's energy to another link.
+    LinkTransfer(ObjectId),
+    /// Issued on a controller.
+    ActivateSafeMode,
+    /// Kills a creep next to the spawn, returning some of its cost.
+    RecycleCreep(ObjectId)
+}
+
+/// Everything the bot reads from or does to the game.
+/// 
+/// The bot is written against this trait instead of `screeps::objects`,
+/// so that tasks and roles can run against `MockWorld` outside of the game.  
+/// Queries return snapshots, which are not updated by intents issued in the same tick.
+pub trait World {
+    fn time(&self) -> u32;
+
+    /// All of our creeps, including ones that are still spawning.
+    fn my_creeps(&self) -> Vec<CreepState>;
+
+    fn creep(&self, name: &str) -> Option<CreepState>;
+
+    fn creep_by_id(&self, id: &str) -> Option<CreepState>;
+
+    /// All rooms we have vision in.
+    fn rooms(&self) -> Vec<RoomState>;
+
+    fn room(&self, name: &str) -> Option<RoomState>;
+
+    fn spawns(&self) -> Vec<SpawnState>;
+
+    fn sources(&self, room: &str) -> Vec<SourceState>;
+
+    fn source(&self, id: &str) -> Option<SourceState>;
+
+    fn structures(&self, room: &str) -> Vec<StructureState>;
+
+    fn structure(&self, id: &str) -> Option<StructureState>;
+
+    fn minerals(&self, room: &str) -> Vec<MineralState>;
+
+    /// Available for every room, even without vision.
+    fn terrain(&self, room: &str) -> RoomTerrain;
+
+    fn construction_sites(&self, room: &str) -> Vec<SiteState>;
+
+    fn hostile_creeps(&self, room: &str) -> Vec<CreepState>;
+
+    fn dropped_resources(&self, room: &str) -> Vec<ResourceState>;
+
+    /// Tombstones and ruins.
+    fn remains(&self, room: &str) -> Vec<RemainsState>;
+
+    /// NPC invader cores, at most one per room.
+    fn invader_cores(&self, room: &str) -> Vec<InvaderCoreState>;
+
+    fn flags(&self) -> Vec<FlagState>;
+
+    fn creep_intent(&self, creep: &str, intent: CreepIntent) -> ReturnCode;
+
+    fn structure_intent(&self, structure: &str, intent: StructureIntent) -> ReturnCode;
+
+    fn spawn_creep(&self, spawn: &str, body: &[Part], name: &str) -> ReturnCode;
+
+    fn create_flag(&self, pos: &Position, name: &str, color: Color, secondary_color: Color) -> ReturnCode;
+
+    fn create_construction_site(&self, pos: &Position, kind: StructureKind) -> ReturnCode;
+
+    fn remove_flag(&self, name: &str);
+
+    fn notify(&self, message: &str);
+
+    /// The root of `Memory`, parsed again whenever it was changed outside the bot.
+    fn memory(&self) -> RefMut<Memory>;
+
+    /// Writes `memory()` back into the game.
+    fn commit_memory(&self);
+}